@@ -1,34 +1,189 @@
-use glsl_to_spirv;
-use std::fs::read_to_string;
-use std::fs::remove_file;
-use std::fs::File;
+use glsl_to_spirv::ShaderType;
+use std::env;
+use std::fs::{self, File};
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
-fn save_into_spirv(dir: &str, filename: &str) {
-    for (ext, ty) in vec![
-        (".vert", glsl_to_spirv::ShaderType::Vertex),
-        (".frag", glsl_to_spirv::ShaderType::Fragment),
-    ]
-    .into_iter()
-    {
-        let path = dir.to_owned() + "/" + filename + ext;
-        let out_path = path.clone() + ".spv";
-        let code = read_to_string(&path).unwrap();
-        let mut file = glsl_to_spirv::compile(&code, ty).unwrap_or_else(|err| {
-            eprintln!("compile {}:", path);
-            panic!("{}", err)
-        });
-        let mut buf = vec![];
-        file.read_to_end(&mut buf).unwrap();
-
-        remove_file(&out_path).unwrap();
-        let mut output = File::create(&out_path).unwrap();
-        output.write_all(&mut buf).unwrap();
-
-        println!("successfully compiled: {} -> {}", &path, &out_path);
+const SHADER_DIR: &str = "src/data";
+
+/// Shader source after `#include` expansion, remembering where every line came from.
+struct Source {
+    code: String,
+    lines: Vec<(PathBuf, usize)>,
+    deps: Vec<PathBuf>,
+}
+
+impl Source {
+    fn load(path: &Path, root: &Path) -> Result<Self, String> {
+        let mut source = Source {
+            code: String::new(),
+            lines: vec![],
+            deps: vec![],
+        };
+        source.expand(path, root, &mut vec![])?;
+        Ok(source)
+    }
+
+    fn expand(&mut self, path: &Path, root: &Path, stack: &mut Vec<PathBuf>) -> Result<(), String> {
+        if stack.iter().any(|p| p == path) {
+            return Err(format!("{}: recursive #include", path.display()));
+        }
+        let code = fs::read_to_string(path)
+            .map_err(|err| format!("{}: can't read: {}", path.display(), err))?;
+        if !self.deps.iter().any(|p| p == path) {
+            self.deps.push(path.to_owned());
+        }
+
+        stack.push(path.to_owned());
+        for (i, line) in code.lines().enumerate() {
+            match Self::parse_include(line) {
+                Some(Ok((name, false))) => {
+                    let dir = path.parent().unwrap_or(root);
+                    self.expand(&dir.join(name), root, stack)?;
+                }
+                Some(Ok((name, true))) => self.expand(&root.join(name), root, stack)?,
+                Some(Err(msg)) => return Err(format!("{}:{}: {}", path.display(), i + 1, msg)),
+                None => {
+                    self.code.push_str(line);
+                    self.code.push('\n');
+                    self.lines.push((path.to_owned(), i + 1));
+                }
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    /// Parses `#include "file"` (relative to the including file) or
+    /// `#include <file>` (relative to the shader root). The flag is true for the latter.
+    fn parse_include(line: &str) -> Option<Result<(&str, bool), &'static str>> {
+        let rest = line.trim_start().strip_prefix('#')?.trim_start();
+        let arg = rest.strip_prefix("include")?.trim();
+        let parsed = if let Some(name) = arg.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Ok((name, false))
+        } else if let Some(name) = arg.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Ok((name, true))
+        } else {
+            Err("malformed #include, expected \"file\" or <file>")
+        };
+        Some(parsed)
+    }
+
+    /// Rewrites glslangValidator diagnostics (`ERROR: <tmpfile>:<line>: msg`) so that
+    /// they point to the original file and line.
+    fn map_errors(&self, log: &str) -> String {
+        log.lines()
+            .filter_map(|line| {
+                let (severity, rest) = if let Some(rest) = line.strip_prefix("ERROR: ") {
+                    ("error", rest)
+                } else if let Some(rest) = line.strip_prefix("WARNING: ") {
+                    ("warning", rest)
+                } else {
+                    return None;
+                };
+                match Self::split_location(rest) {
+                    Some((n, msg)) if n >= 1 && n <= self.lines.len() => {
+                        let (path, line) = &self.lines[n - 1];
+                        Some(format!(
+                            "{}:{}: {}: {}",
+                            path.display(),
+                            line,
+                            severity,
+                            msg
+                        ))
+                    }
+                    _ => Some(format!("{}: {}", severity, rest)),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Finds the first `:<digits>:` in a diagnostic and splits it into line number and message.
+    fn split_location(s: &str) -> Option<(usize, &str)> {
+        let bytes = s.as_bytes();
+        for (i, _) in s.match_indices(':') {
+            let digits = bytes[i + 1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            let end = i + 1 + digits;
+            if digits > 0 && bytes.get(end) == Some(&b':') {
+                let n = s[i + 1..end].parse().ok()?;
+                return Some((n, s[end + 1..].trim()));
+            }
+        }
+        None
+    }
+}
+
+fn shader_type(path: &Path) -> Option<ShaderType> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(ShaderType::Vertex),
+        "frag" => Some(ShaderType::Fragment),
+        "comp" => Some(ShaderType::Compute),
+        "geom" => Some(ShaderType::Geometry),
+        _ => None,
     }
 }
 
+fn find_shaders(dir: &Path, shaders: &mut Vec<PathBuf>) {
+    let mut entries = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("can't read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_shaders(&path, shaders);
+        } else if shader_type(&path).is_some() {
+            shaders.push(path);
+        }
+    }
+}
+
+fn save_into_spirv(path: &Path, root: &Path, out_dir: &Path) -> Result<(), String> {
+    let ty = shader_type(path).unwrap();
+    let source = Source::load(path, root)?;
+    for dep in &source.deps {
+        println!("cargo:rerun-if-changed={}", dep.display());
+    }
+
+    let mut file =
+        glsl_to_spirv::compile(&source.code, ty).map_err(|log| source.map_errors(&log))?;
+    let mut buf = vec![];
+    file.read_to_end(&mut buf).unwrap();
+
+    let out_path = out_dir.join(
+        path.strip_prefix(root)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+            + ".spv",
+    );
+    fs::create_dir_all(out_path.parent().unwrap()).unwrap();
+    let mut output = File::create(&out_path).unwrap();
+    output.write_all(&buf).unwrap();
+    Ok(())
+}
+
 fn main() {
-    save_into_spirv("src/data", "triangle");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let root = Path::new(SHADER_DIR);
+    println!("cargo:rerun-if-changed={}", root.display());
+
+    let mut shaders = vec![];
+    find_shaders(root, &mut shaders);
+
+    let errors = shaders
+        .iter()
+        .filter_map(|path| save_into_spirv(path, root, &out_dir).err())
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        for err in &errors {
+            eprintln!("{}", err);
+        }
+        panic!("failed to compile {} shader(s)", errors.len());
+    }
 }
@@ -0,0 +1,351 @@
+use gfx_hal::{
+    adapter::{Adapter, DeviceType},
+    prelude::*,
+    Backend, Features, Limits,
+};
+use std::str::FromStr;
+
+/// The features an adapter spec can require with `feature=`.
+const FEATURES: &[(&str, Features)] = &[
+    ("sampler_anisotropy", Features::SAMPLER_ANISOTROPY),
+    ("image_cube_array", Features::IMAGE_CUBE_ARRAY),
+    ("independent_blending", Features::INDEPENDENT_BLENDING),
+    ("geometry_shader", Features::GEOMETRY_SHADER),
+    ("tessellation_shader", Features::TESSELLATION_SHADER),
+    ("sample_rate_shading", Features::SAMPLE_RATE_SHADING),
+    ("multi_draw_indirect", Features::MULTI_DRAW_INDIRECT),
+    ("depth_clamp", Features::DEPTH_CLAMP),
+    ("non_fill_polygon_mode", Features::NON_FILL_POLYGON_MODE),
+    ("wide_lines", Features::LINE_WIDTH),
+    ("format_bc", Features::FORMAT_BC),
+    ("format_etc2", Features::FORMAT_ETC2),
+    ("format_astc_ldr", Features::FORMAT_ASTC_LDR),
+    (
+        "vertex_stores_and_atomics",
+        Features::VERTEX_STORES_AND_ATOMICS,
+    ),
+    (
+        "fragment_stores_and_atomics",
+        Features::FRAGMENT_STORES_AND_ATOMICS,
+    ),
+    ("shader_float64", Features::SHADER_FLOAT64),
+];
+
+/// Lower bounds on `Limits`. Zero accepts anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequiredLimits {
+    pub max_image_2d_size: u32,
+    pub max_bound_descriptor_sets: u16,
+    pub max_storage_buffer_range: u64,
+    pub max_compute_work_group_invocations: usize,
+    pub max_sampler_anisotropy: f32,
+}
+
+impl RequiredLimits {
+    /// Describes every limit of `limits` below the required one.
+    fn unmet(&self, limits: &Limits) -> Vec<String> {
+        let mut unmet = vec![];
+        let mut check = |name: &str, actual: f64, required: f64| {
+            if actual < required {
+                unmet.push(format!("{} is {} < {}", name, actual, required));
+            }
+        };
+        check(
+            "max_image_2d_size",
+            f64::from(limits.max_image_2d_size),
+            f64::from(self.max_image_2d_size),
+        );
+        check(
+            "max_bound_descriptor_sets",
+            f64::from(limits.max_bound_descriptor_sets),
+            f64::from(self.max_bound_descriptor_sets),
+        );
+        check(
+            "max_storage_buffer_range",
+            limits.max_storage_buffer_range as f64,
+            self.max_storage_buffer_range as f64,
+        );
+        check(
+            "max_compute_work_group_invocations",
+            limits.max_compute_work_group_invocations as f64,
+            self.max_compute_work_group_invocations as f64,
+        );
+        check(
+            "max_sampler_anisotropy",
+            f64::from(limits.max_sampler_anisotropy),
+            f64::from(self.max_sampler_anisotropy),
+        );
+        unmet
+    }
+}
+
+/// How `select` picks an adapter.
+///
+/// Adapters whose name doesn't contain `name`, that lack a required feature or limit or
+/// can't render to the surface, if there is one, are skipped. The rest are ranked by
+/// `preference` first, then discrete, integrated, virtual, CPU and other devices, keeping
+/// the enumeration order among equals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterPolicy {
+    pub preference: Option<DeviceType>,
+    /// Matched case-insensitively.
+    pub name: Option<String>,
+    pub features: Features,
+    pub limits: RequiredLimits,
+}
+
+impl Default for AdapterPolicy {
+    /// Accepts any adapter that can render to the surface.
+    fn default() -> Self {
+        AdapterPolicy {
+            preference: None,
+            name: None,
+            features: Features::empty(),
+            limits: RequiredLimits::default(),
+        }
+    }
+}
+
+impl AdapterPolicy {
+    /// Applies an adapter spec, a comma separated list of `discrete`, `integrated`,
+    /// `virtual` or `cpu` for the preferred device type, `name=` followed by a name
+    /// substring, `feature=` followed by one of `FEATURES` and the lower bounds
+    /// `min_image_2d`, `min_bound_descriptor_sets`, `min_storage_buffer_range`,
+    /// `min_compute_work_group_invocations` and `min_sampler_anisotropy`, e.g.
+    /// `discrete,name=intel,feature=sampler_anisotropy,min_image_2d=8192`. Fails on any
+    /// other token, leaving the policy unchanged.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut policy = self.clone();
+        for token in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+        {
+            match token.to_lowercase().as_str() {
+                "discrete" => policy.preference = Some(DeviceType::DiscreteGpu),
+                "integrated" => policy.preference = Some(DeviceType::IntegratedGpu),
+                "virtual" => policy.preference = Some(DeviceType::VirtualGpu),
+                "cpu" | "software" => policy.preference = Some(DeviceType::Cpu),
+                lower if lower.starts_with("name=") => {
+                    policy.name = Some(token["name=".len()..].to_owned());
+                }
+                lower if lower.starts_with("feature=") => {
+                    let name = lower["feature=".len()..].trim();
+                    let &(_, feature) = FEATURES
+                        .iter()
+                        .find(|(feature, _)| *feature == name)
+                        .ok_or_else(|| {
+                            let names = FEATURES.iter().map(|(name, _)| *name);
+                            format!(
+                                "unknown adapter feature `{}`, expected one of {}",
+                                name,
+                                names.collect::<Vec<_>>().join(", ")
+                            )
+                        })?;
+                    policy.features |= feature;
+                }
+                lower => {
+                    let (key, value) = lower.split_once('=').ok_or_else(|| unknown(token))?;
+                    let limits = &mut policy.limits;
+                    match key.trim() {
+                        "min_image_2d" => limits.max_image_2d_size = parse(token, value)?,
+                        "min_bound_descriptor_sets" => {
+                            limits.max_bound_descriptor_sets = parse(token, value)?
+                        }
+                        "min_storage_buffer_range" => {
+                            limits.max_storage_buffer_range = parse(token, value)?
+                        }
+                        "min_compute_work_group_invocations" => {
+                            limits.max_compute_work_group_invocations = parse(token, value)?
+                        }
+                        "min_sampler_anisotropy" => {
+                            limits.max_sampler_anisotropy = parse(token, value)?
+                        }
+                        _ => return Err(unknown(token)),
+                    }
+                }
+            }
+        }
+        *self = policy;
+        Ok(())
+    }
+
+    /// Why `adapter` can't be used, if it can't.
+    fn reject<B: Backend>(
+        &self,
+        adapter: &Adapter<B>,
+        surface: Option<&B::Surface>,
+    ) -> Option<String> {
+        if let Some(name) = &self.name {
+            if !adapter
+                .info
+                .name
+                .to_lowercase()
+                .contains(&name.to_lowercase())
+            {
+                return Some(format!("name doesn't contain `{}`", name));
+            }
+        }
+        let missing = self.features - adapter.physical_device.features();
+        if !missing.is_empty() {
+            return Some(format!("missing features {:?}", missing));
+        }
+        let unmet = self.limits.unmet(&adapter.physical_device.limits());
+        if !unmet.is_empty() {
+            return Some(unmet.join(", "));
+        }
+        if !adapter
+            .queue_families
+            .iter()
+            .any(|family| supports::<B>(surface, family))
+        {
+            return Some(match surface {
+                Some(_) => "can't render to the window".to_owned(),
+                None => "has no graphics queue".to_owned(),
+            });
+        }
+        None
+    }
+
+    /// Lower ranks are better.
+    fn rank(&self, device_type: &DeviceType) -> usize {
+        if self.preference.as_ref() == Some(device_type) {
+            return 0;
+        }
+        match device_type {
+            DeviceType::DiscreteGpu => 1,
+            DeviceType::IntegratedGpu => 2,
+            DeviceType::VirtualGpu => 3,
+            DeviceType::Cpu => 4,
+            DeviceType::Other => 5,
+        }
+    }
+}
+
+/// The error for a token of an adapter spec that `AdapterPolicy::apply` doesn't know.
+fn unknown(token: &str) -> String {
+    format!(
+        "unknown adapter spec `{}`, expected discrete, integrated, virtual, cpu, \
+         name=<substring>, feature=<name> or min_<limit>=<value>",
+        token
+    )
+}
+
+/// Parses the value of a `min_` token of an adapter spec.
+fn parse<T: FromStr>(token: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid adapter limit `{}`", token))
+}
+
+/// Whether `family` can render to `surface`, or render at all without one.
+pub fn supports<B: Backend>(surface: Option<&B::Surface>, family: &B::QueueFamily) -> bool {
+    family.queue_type().supports_graphics()
+        && surface.is_none_or(|surface| surface.supports_queue_family(family))
+}
+
+/// Picks the best of `adapters` for rendering to `surface`, or offscreen without one,
+/// according to `policy`, logging every adapter and why it was or wasn't chosen.
+pub fn select<B: Backend>(
+    adapters: Vec<Adapter<B>>,
+    policy: &AdapterPolicy,
+    surface: Option<&B::Surface>,
+) -> Option<Adapter<B>> {
+    let mut best: Option<(usize, usize)> = None;
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = &adapter.info;
+        let verdict = match policy.reject(adapter, surface) {
+            Some(reason) => format!("skipped, {}", reason),
+            None => {
+                let rank = policy.rank(&info.device_type);
+                if best.is_none_or(|(_, best_rank)| rank < best_rank) {
+                    best = Some((index, rank));
+                }
+                "suitable".to_owned()
+            }
+        };
+        log::info!(
+            "adapter {}: {} ({:?}, vendor {:#06x}, device {:#06x}): {}",
+            index,
+            info.name,
+            info.device_type,
+            info.vendor,
+            info.device,
+            verdict
+        );
+    }
+
+    let (index, rank) = best?;
+    let adapter = adapters.into_iter().nth(index)?;
+    let reason = match &policy.preference {
+        Some(preference) if rank == 0 => format!("it is the preferred {:?}", preference),
+        Some(preference) => format!(
+            "no suitable {:?} was found and it ranks highest of the rest",
+            preference
+        ),
+        None => "it ranks highest of the suitable adapters".to_owned(),
+    };
+    log::info!(
+        "using adapter {} ({}): {}",
+        index,
+        adapter.info.name,
+        reason
+    );
+    Some(adapter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_parses_types_and_names() {
+        let mut policy = AdapterPolicy::default();
+        policy.apply(" Discrete , name=GeForce RTX,").unwrap();
+        assert_eq!(policy.preference, Some(DeviceType::DiscreteGpu));
+        assert_eq!(policy.name.as_deref(), Some("GeForce RTX"));
+
+        policy.apply("software").unwrap();
+        assert_eq!(policy.preference, Some(DeviceType::Cpu));
+        assert_eq!(policy.name.as_deref(), Some("GeForce RTX"));
+
+        policy
+            .apply("feature=sampler_anisotropy, Feature=Geometry_Shader,min_image_2d=8192")
+            .unwrap();
+        assert_eq!(
+            policy.features,
+            Features::SAMPLER_ANISOTROPY | Features::GEOMETRY_SHADER
+        );
+        assert_eq!(policy.limits.max_image_2d_size, 8192);
+
+        policy
+            .apply(
+                "min_bound_descriptor_sets=8,min_storage_buffer_range=134217728,\
+                 min_compute_work_group_invocations=1024,min_sampler_anisotropy=16",
+            )
+            .unwrap();
+        assert_eq!(
+            policy.limits,
+            RequiredLimits {
+                max_image_2d_size: 8192,
+                max_bound_descriptor_sets: 8,
+                max_storage_buffer_range: 134_217_728,
+                max_compute_work_group_invocations: 1024,
+                max_sampler_anisotropy: 16.0,
+            }
+        );
+    }
+
+    #[test]
+    fn apply_rejects_unknown_tokens() {
+        let mut policy = AdapterPolicy::default();
+        assert!(policy.apply("dicrete").is_err());
+        assert!(policy.apply("discrete,intel").is_err());
+        assert!(policy.apply("feature=raytracing").is_err());
+        assert!(policy.apply("min_image_2d=large").is_err());
+        assert!(policy.apply("min_bound_descriptor_sets=65536").is_err());
+        assert!(policy.apply("max_image_2d=8192").is_err());
+        assert_eq!(policy, AdapterPolicy::default());
+    }
+}
@@ -0,0 +1,314 @@
+use gfx_hal::{adapter::Adapter, prelude::*, queue::QueueGroup, Backend, Features};
+use std::{io, process, thread};
+use winit::dpi::PhysicalSize;
+use winit::event::VirtualKeyCode;
+use winit::window::Window;
+
+use crate::adapter::{self, AdapterPolicy};
+use crate::back;
+use crate::clock::{Clock, Time};
+use crate::config::{Config, PresentMode};
+use crate::input::{self, Input, InputState};
+use crate::renderer::{EffectKind, Frame, Renderer};
+use crate::stats::FrameStats;
+use crate::window_mode::WindowMode;
+
+/// An application driven by `run`. Every callback runs on the render thread.
+pub trait App: Send + 'static {
+    /// Called once before the first frame, e.g. to add particle emitters.
+    fn init(&mut self, _frame: &mut Frame<back::Backend>) {}
+
+    /// Called `time.fixed_steps` times at the start of every frame, each advancing the
+    /// simulation by `time.fixed_step` seconds, so it runs the same at any frame rate.
+    fn fixed_update(&mut self, _time: &Time, _input: &InputState) {}
+
+    /// Called at the start of every frame, after `fixed_update`.
+    fn update(&mut self, _time: &Time, _input: &InputState) {}
+
+    /// Sets up the frame about to be rendered.
+    fn render(&mut self, frame: &mut Frame<back::Backend>);
+
+    /// Called before the frame in which the window has a new `size`, in physical pixels.
+    fn on_resize(&mut self, _size: PhysicalSize<u32>) {}
+
+    /// Called after the last frame, before the renderer goes away.
+    fn on_exit(&mut self) {}
+}
+
+/// The keys toggling each of `EffectKind::ALL`.
+const EFFECT_KEYS: [VirtualKeyCode; 5] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct AppOptions {
+    pub config: Config,
+    /// Amended by `config.adapter`.
+    pub adapter: AdapterPolicy,
+}
+
+/// Opens a window and runs `app` until it is closed or Escape is pressed.
+///
+/// The window's events are handled on the calling thread, which must be the main one.
+/// Rendering happens on a thread of its own. Besides what `app` does with the input,
+/// Tab switches the camera controller, C its projection, P pauses the clock, `[` and `]`
+/// halve and double its speed, V toggles vsync and F11 or Alt+Enter toggle fullscreen.
+/// The clock is also paused while the window is unfocused. The keys 1 to 5 toggle the
+/// post-processing effects in the order of `EffectKind::ALL` and O moves the first
+/// effect to the end. Frame statistics are shown in the title and logged every second.
+///
+/// With `config.headless_output` set, no window is opened. The configured number of
+/// frames is rendered at 60 frames per second of game time and the last one is saved.
+pub fn run<A: App>(app: A, options: AppOptions) -> ! {
+    if let Some(path) = &options.config.headless_output {
+        match run_headless(app, &options) {
+            Ok(()) => {
+                log::info!("saved {}", path.display());
+                process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Can't save {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+
+    let config = &options.config;
+    let event_loop = winit::event_loop::EventLoop::new();
+    let wb = winit::window::WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(winit::dpi::Size::Physical(winit::dpi::PhysicalSize::new(
+            config.width,
+            config.height,
+        )))
+        .with_min_inner_size(winit::dpi::Size::Logical(winit::dpi::LogicalSize::new(
+            64.0, 64.0,
+        )));
+    let window = wb.build(&event_loop).expect("failed to create window");
+    let mut window_mode = WindowMode::new(config, &window);
+    if config.fullscreen {
+        window_mode.enter(&window);
+    }
+
+    let (input_sender, input) = input::channel(window.inner_size(), window.scale_factor());
+    let handler = thread::spawn(move || render_thread(app, &options, window, window_mode, input));
+    let mut handler = Some(handler);
+
+    event_loop.run(move |event, _, control_flow| {
+        if let winit::event::Event::WindowEvent { event, .. } = event {
+            match &event {
+                winit::event::WindowEvent::CloseRequested
+                | winit::event::WindowEvent::KeyboardInput {
+                    input:
+                        winit::event::KeyboardInput {
+                            virtual_keycode: Some(winit::event::VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => {
+                    input_sender.close();
+                    log::info!("window closed");
+                    if let Some(handler) = handler.take() {
+                        handler.join().unwrap();
+                    }
+                    *control_flow = winit::event_loop::ControlFlow::Exit;
+                }
+                _ => input_sender.send(&event),
+            }
+        }
+    });
+}
+
+fn render_thread<A: App>(
+    mut app: A,
+    options: &AppOptions,
+    window: Window,
+    mut window_mode: WindowMode,
+    mut input: Input,
+) {
+    let instance = create_instance(&options.config);
+    let mut surface = unsafe {
+        instance
+            .create_surface(&window)
+            .expect("failed to create a surface")
+    };
+    let (adapter, device, mut queue_group) = match open_device(&instance, options, Some(&surface)) {
+        Ok(opened) => opened,
+        Err(err) => {
+            eprintln!("Can't open a device: {}", err);
+            process::exit(1);
+        }
+    };
+    let queue = &mut queue_group.queues[0];
+
+    {
+        let mut renderer = Renderer::new(
+            Some(&mut surface),
+            &adapter,
+            &device,
+            queue_group.family,
+            &options.config,
+        );
+        app.init(&mut renderer.frame());
+
+        let mut clock = Clock::default();
+        let mut paused_unfocused = false;
+        let mut stats = FrameStats::default();
+        loop {
+            let input = input.poll();
+            if input.close_requested {
+                break;
+            }
+            if !input.focused && !clock.is_paused() {
+                clock.pause();
+                paused_unfocused = true;
+            } else if input.focused && paused_unfocused {
+                clock.resume();
+                paused_unfocused = false;
+            }
+            if input.key_pressed(VirtualKeyCode::P) {
+                clock.toggle_pause();
+            }
+            if input.key_pressed(VirtualKeyCode::LBracket) {
+                clock.set_scale(clock.scale() / 2.0);
+            }
+            if input.key_pressed(VirtualKeyCode::RBracket) {
+                clock.set_scale(clock.scale() * 2.0);
+            }
+            let alt = input.key_held(VirtualKeyCode::LAlt) || input.key_held(VirtualKeyCode::RAlt);
+            if input.key_pressed(VirtualKeyCode::F11)
+                || (alt && input.key_pressed(VirtualKeyCode::Return))
+            {
+                window_mode.toggle(&window);
+            }
+            if input.key_pressed(VirtualKeyCode::V) {
+                if let Some(mode) = renderer.present_mode() {
+                    renderer.set_present_mode(if mode.vsync() {
+                        PresentMode::Immediate
+                    } else {
+                        PresentMode::Fifo
+                    });
+                }
+            }
+            let mut frame = renderer.frame();
+            let post = frame.post_process_mut();
+            for (key, &kind) in EFFECT_KEYS.iter().zip(&EffectKind::ALL) {
+                if input.key_pressed(*key) {
+                    post.toggle(kind);
+                }
+            }
+            if input.key_pressed(VirtualKeyCode::O) {
+                let mut order = post
+                    .effects()
+                    .iter()
+                    .map(|effect| effect.kind)
+                    .collect::<Vec<_>>();
+                order.rotate_left(1);
+                post.set_order(&order);
+                log::info!("effect order: {:?}", order);
+            }
+            let time = clock.tick();
+            if input.resized {
+                app.on_resize(input.size);
+            }
+            renderer.update(&time, input);
+            for _ in 0..time.fixed_steps {
+                app.fixed_update(&time, input);
+            }
+            app.update(&time, input);
+            app.render(&mut renderer.frame());
+            renderer.render(queue);
+            if let Some(summary) = stats.end_frame(renderer.blocked()) {
+                window.set_title(&format!("{} - {}", options.config.title, summary.short()));
+                log::info!("{}", summary);
+            }
+        }
+        app.on_exit();
+    }
+
+    unsafe {
+        instance.destroy_surface(surface);
+    }
+}
+
+/// Renders `options.config.headless_frames` frames offscreen and saves the last one.
+fn run_headless<A: App>(mut app: A, options: &AppOptions) -> io::Result<()> {
+    let config = &options.config;
+    let instance = create_instance(config);
+    let (adapter, device, mut queue_group) =
+        open_device(&instance, options, None).map_err(io::Error::other)?;
+    let queue = &mut queue_group.queues[0];
+
+    let mut renderer = Renderer::new(None, &adapter, &device, queue_group.family, config);
+    app.init(&mut renderer.frame());
+
+    let input = InputState::new(PhysicalSize::new(config.width, config.height), 1.0);
+    let mut clock = Clock::default();
+    let mut stats = FrameStats::default();
+    for _ in 0..config.headless_frames {
+        let time = clock.advance(1.0 / 60.0);
+        renderer.update(&time, &input);
+        for _ in 0..time.fixed_steps {
+            app.fixed_update(&time, &input);
+        }
+        app.update(&time, &input);
+        app.render(&mut renderer.frame());
+        renderer.render(queue);
+        if let Some(summary) = stats.end_frame(renderer.blocked()) {
+            log::info!("{}", summary);
+        }
+    }
+    if let Some(summary) = stats.summary() {
+        log::info!("{}", summary);
+    }
+    let result = renderer.save_frame(config.headless_output.as_ref().unwrap());
+    app.on_exit();
+    result
+}
+
+fn create_instance(config: &Config) -> back::Instance {
+    back::Instance::create(&format!("gfx-rs {}", config.title), 1)
+        .expect("failed to create an instance of gfx")
+}
+
+type OpenDevice = (
+    Adapter<back::Backend>,
+    <back::Backend as Backend>::Device,
+    QueueGroup<back::Backend>,
+);
+
+/// Picks an adapter that can render to `surface`, or offscreen without one, and opens a
+/// device with one graphics queue on it.
+fn open_device(
+    instance: &back::Instance,
+    options: &AppOptions,
+    surface: Option<&<back::Backend as Backend>::Surface>,
+) -> Result<OpenDevice, String> {
+    let mut policy = options.adapter.clone();
+    if let Some(spec) = &options.config.adapter {
+        policy.apply(spec)?;
+    }
+    let adapter = adapter::select(instance.enumerate_adapters(), &policy, surface)
+        .ok_or("no adapter is suitable, see the log for why each was skipped")?;
+
+    let family = adapter
+        .queue_families
+        .iter()
+        .find(|family| adapter::supports::<back::Backend>(surface, family))
+        .unwrap();
+    let features =
+        adapter.physical_device.features() & (Features::SAMPLER_ANISOTROPY | policy.features);
+    let mut gpu = unsafe {
+        adapter
+            .physical_device
+            .open(&[(family, &[1.0])], features)
+            .unwrap()
+    };
+
+    let queue_group = gpu.queue_groups.pop().unwrap();
+    Ok((adapter, gpu.device, queue_group))
+}
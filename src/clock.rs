@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+
+/// Frames taking longer than this, e.g. after a breakpoint or while the window is being
+/// dragged, are counted as this long so fixed steps can catch up.
+const MAX_DELTA: Duration = Duration::from_millis(250);
+
+/// At most this many fixed steps are simulated per frame. Beyond it the simulation falls
+/// behind instead of taking longer and longer to catch up.
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// The range `Clock::set_scale` clamps to.
+const MIN_SCALE: f32 = 1.0 / 64.0;
+const MAX_SCALE: f32 = 64.0;
+
+/// The timing of one frame, from `Clock::tick`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Time {
+    /// Scaled seconds since the previous frame, 0 while paused.
+    pub delta: f32,
+    /// Scaled seconds the clock has been running for.
+    pub total: f64,
+    /// Seconds since the previous frame regardless of pausing and scaling, e.g. for the
+    /// camera or UI.
+    pub real_delta: f32,
+    /// Seconds since the clock was created.
+    pub real_total: f64,
+    /// Frames ticked before this one.
+    pub frame: u64,
+    /// How many `fixed_step`s to simulate this frame.
+    pub fixed_steps: u32,
+    pub fixed_step: f32,
+    /// How far the clock is into the next fixed step, from 0 to 1, for interpolating
+    /// between the last two simulated states.
+    pub alpha: f32,
+    pub paused: bool,
+}
+
+/// Measures frame times and turns them into scaled, pausable game time.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    start: Instant,
+    last: Instant,
+    paused: bool,
+    scale: f32,
+    fixed_step: f32,
+    accumulator: f32,
+    frames: u64,
+    time: Time,
+}
+
+impl Default for Clock {
+    /// A running clock with a scale of 1 and fixed steps of 1/60 s.
+    fn default() -> Self {
+        Self::new(1.0 / 60.0)
+    }
+}
+
+impl Clock {
+    pub fn new(fixed_step: f32) -> Self {
+        assert!(fixed_step > 0.0, "The fixed step must be positive");
+        let now = Instant::now();
+        Clock {
+            start: now,
+            last: now,
+            paused: false,
+            scale: 1.0,
+            fixed_step,
+            accumulator: 0.0,
+            frames: 0,
+            time: Time {
+                fixed_step,
+                ..Time::default()
+            },
+        }
+    }
+
+    /// Starts a new frame.
+    pub fn tick(&mut self) -> Time {
+        let now = Instant::now();
+        let real_delta = now.duration_since(self.last).min(MAX_DELTA).as_secs_f32();
+        self.last = now;
+        self.step(real_delta, now.duration_since(self.start).as_secs_f64())
+    }
+
+    /// Starts a new frame `real_delta` seconds after the previous one, regardless of how
+    /// much time really passed, e.g. to render offline at a fixed rate.
+    pub fn advance(&mut self, real_delta: f32) -> Time {
+        self.last = Instant::now();
+        self.step(real_delta, self.time.real_total + f64::from(real_delta))
+    }
+
+    fn step(&mut self, real_delta: f32, real_total: f64) -> Time {
+        let delta = if self.paused {
+            0.0
+        } else {
+            real_delta * self.scale
+        };
+        self.accumulator += delta;
+        let fixed_steps = (self.accumulator / self.fixed_step) as u32;
+        self.accumulator -= fixed_steps as f32 * self.fixed_step;
+        let fixed_steps = fixed_steps.min(MAX_FIXED_STEPS);
+
+        self.time = Time {
+            delta,
+            total: self.time.total + f64::from(delta),
+            real_delta,
+            real_total,
+            frame: self.frames,
+            fixed_steps,
+            fixed_step: self.fixed_step,
+            alpha: self.accumulator / self.fixed_step,
+            paused: self.paused,
+        };
+        self.frames += 1;
+        self.time
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// How fast game time runs compared to real time.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Clamped to 1/64 to 64.
+    pub fn set_scale(&mut self, scale: f32) {
+        assert!(!scale.is_nan(), "The time scale can't be NaN");
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn advance_counts_fixed_steps_and_keeps_the_remainder() {
+        let mut clock = Clock::new(0.1);
+        let time = clock.advance(0.25);
+        assert_eq!(time.fixed_steps, 2);
+        assert_near(time.alpha, 0.5);
+        assert_eq!(time.frame, 0);
+
+        // The remaining half step completes with the next frame.
+        let time = clock.advance(0.06);
+        assert_eq!(time.fixed_steps, 1);
+        assert_near(time.alpha, 0.1);
+        assert_near(time.total as f32, 0.31);
+        assert_near(time.real_total as f32, 0.31);
+        assert_eq!(time.frame, 1);
+    }
+
+    #[test]
+    fn advance_caps_fixed_steps_and_drops_the_excess() {
+        let mut clock = Clock::new(0.25);
+        let time = clock.advance(3.125);
+        assert_eq!(time.fixed_steps, MAX_FIXED_STEPS);
+        assert_near(time.alpha, 0.5);
+
+        // The dropped steps aren't made up for later.
+        let time = clock.advance(0.125);
+        assert_eq!(time.fixed_steps, 1);
+        assert_near(time.alpha, 0.0);
+    }
+
+    #[test]
+    fn pausing_stops_game_time_only() {
+        let mut clock = Clock::new(0.1);
+        clock.advance(0.15);
+        clock.pause();
+        let time = clock.advance(0.2);
+        assert!(time.paused);
+        assert_eq!(time.delta, 0.0);
+        assert_eq!(time.fixed_steps, 0);
+        assert_near(time.alpha, 0.5);
+        assert_near(time.total as f32, 0.15);
+        assert_near(time.real_delta, 0.2);
+        assert_near(time.real_total as f32, 0.35);
+
+        clock.resume();
+        let time = clock.advance(0.05);
+        assert!(!time.paused);
+        assert_eq!(time.fixed_steps, 1);
+        assert_near(time.total as f32, 0.2);
+    }
+
+    #[test]
+    fn scale_applies_to_game_time() {
+        let mut clock = Clock::new(0.1);
+        clock.set_scale(0.5);
+        let time = clock.advance(0.4);
+        assert_near(time.delta, 0.2);
+        assert_eq!(time.fixed_steps, 2);
+        assert_near(time.real_delta, 0.4);
+
+        clock.set_scale(0.0);
+        assert_eq!(clock.scale(), MIN_SCALE);
+        clock.set_scale(1000.0);
+        assert_eq!(clock.scale(), MAX_SCALE);
+
+        clock.toggle_pause();
+        assert!(clock.is_paused());
+        clock.toggle_pause();
+        assert!(!clock.is_paused());
+    }
+}
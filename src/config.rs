@@ -0,0 +1,521 @@
+use crate::adapter::AdapterPolicy;
+use gfx_hal::window;
+use pico_args::Arguments;
+use serde::Deserialize;
+use std::{
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt, fs, io,
+    path::Path,
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// The environment variable holding an adapter spec, between the config file and
+/// `--adapter`.
+pub const ADAPTER_VAR: &str = "GFX_ADAPTER";
+
+pub const USAGE: &str = "\
+Usage: gfx-practice-example [OPTIONS]
+
+Options:
+    -c, --config <FILE>          Read the settings below from a TOML file, using the
+                                 option names with `_` instead of `-`
+        --width <PIXELS>         [default: 1024]
+        --height <PIXELS>        [default: 768]
+        --title <TITLE>          [default: triangle]
+        --fullscreen             Start in fullscreen, toggled with F11 or Alt+Enter
+        --fullscreen-mode <MODE> borderless or exclusive [default: borderless]
+        --monitor <INDEX>        The monitor to go fullscreen on, as logged at startup
+                                 [default: the one the window is on]
+        --present-mode <MODE>    fifo, relaxed, mailbox or immediate [default: fifo]
+        --no-vsync               Same as --present-mode immediate
+        --swapchain-images <N>   [default: chosen by the surface]
+        --msaa <SAMPLES>         Samples per pixel of the scene, 1 to 64 [default: 1]
+        --adapter <SPEC>         Overrides GFX_ADAPTER, e.g. `discrete`, `name=intel` or
+                                 `feature=sampler_anisotropy,min_image_2d=8192`
+        --frames-in-flight <N>   Frames recorded while the GPU works on earlier ones
+                                 [default: 1]
+        --clear-color <R,G,B[,A]>
+        --background <FILE>      A PNG, JPEG, KTX2 or DDS image drawn behind the
+                                 scene [default: a checkerboard]
+        --mesh <FILE|SHAPE>      An OBJ or glTF model drawn at the origin, or one of
+                                 polygon, circle, rect, rounded-rect, grid, cube,
+                                 cuboid, sphere and cylinder
+        --headless <PNG>         Render without a window and save the last frame
+        --headless-frames <N>    Frames rendered before saving [default: 1]
+    -h, --help";
+
+/// How frames are queued for display, in order of increasing latency and tearing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// Waits for vertical blank, supported everywhere.
+    Fifo,
+    /// Like `Fifo`, but late frames are shown immediately.
+    Relaxed,
+    /// Waits for vertical blank, replacing the queued frame instead of blocking.
+    Mailbox,
+    /// No vsync.
+    Immediate,
+}
+
+impl PresentMode {
+    /// Whether frames wait for vertical blank, at least when they are on time.
+    pub fn vsync(self) -> bool {
+        match self {
+            PresentMode::Fifo | PresentMode::Relaxed | PresentMode::Mailbox => true,
+            PresentMode::Immediate => false,
+        }
+    }
+
+    /// This mode followed by the ones to use if it isn't supported, closest first. Every
+    /// surface supports `Fifo`.
+    pub fn fallbacks(self) -> &'static [PresentMode] {
+        match self {
+            PresentMode::Fifo => &[PresentMode::Fifo],
+            PresentMode::Relaxed => &[PresentMode::Relaxed, PresentMode::Fifo],
+            PresentMode::Mailbox => &[
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::Fifo,
+            ],
+            PresentMode::Immediate => &[
+                PresentMode::Immediate,
+                PresentMode::Mailbox,
+                PresentMode::Fifo,
+            ],
+        }
+    }
+
+    pub fn to_gfx(self) -> window::PresentMode {
+        match self {
+            PresentMode::Fifo => window::PresentMode::FIFO,
+            PresentMode::Relaxed => window::PresentMode::RELAXED,
+            PresentMode::Mailbox => window::PresentMode::MAILBOX,
+            PresentMode::Immediate => window::PresentMode::IMMEDIATE,
+        }
+    }
+}
+
+impl FromStr for PresentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" | "vsync" => Ok(PresentMode::Fifo),
+            "relaxed" => Ok(PresentMode::Relaxed),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => Err(format!("unknown present mode `{}`", s)),
+        }
+    }
+}
+
+/// How the window covers a monitor in fullscreen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenMode {
+    /// A window without decorations covering the monitor at its current video mode.
+    Borderless,
+    /// Takes over the monitor, switching to the video mode closest to its native one.
+    Exclusive,
+}
+
+impl FromStr for FullscreenMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "borderless" => Ok(FullscreenMode::Borderless),
+            "exclusive" => Ok(FullscreenMode::Exclusive),
+            _ => Err(format!("unknown fullscreen mode `{}`", s)),
+        }
+    }
+}
+
+/// Startup settings of the window and renderer, from `Config::load`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The initial size of the window, or of the image rendered headless, in physical
+    /// pixels.
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub fullscreen: bool,
+    /// The mode used when starting in or switching to fullscreen.
+    pub fullscreen_mode: FullscreenMode,
+    /// The index of the monitor to go fullscreen on, in the order they are logged at
+    /// startup. `None` uses the one the window is on.
+    pub monitor: Option<usize>,
+    /// Falls back to a similar mode if the surface doesn't support it.
+    pub present_mode: PresentMode,
+    /// Images in the swapchain, clamped to what the surface supports. `None` leaves it to
+    /// the surface.
+    pub swapchain_images: Option<u32>,
+    /// Samples per pixel of the scene, lowered to what the adapter supports.
+    pub msaa: u8,
+    /// An adapter spec for `AdapterPolicy::apply`.
+    pub adapter: Option<String>,
+    pub frames_in_flight: usize,
+    pub clear_color: [f32; 4],
+    /// An image drawn behind the scene. `None` draws a checkerboard.
+    pub background: Option<PathBuf>,
+    /// A model drawn at the origin.
+    pub mesh: Option<PathBuf>,
+    /// Renders without a window and saves the last frame here as a PNG.
+    pub headless_output: Option<PathBuf>,
+    pub headless_frames: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            width: 1024,
+            height: 768,
+            title: "triangle".to_owned(),
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::Borderless,
+            monitor: None,
+            present_mode: PresentMode::Fifo,
+            swapchain_images: None,
+            msaa: 1,
+            adapter: None,
+            frames_in_flight: 1,
+            clear_color: [0.8, 0.8, 0.8, 1.0],
+            background: None,
+            mesh: None,
+            headless_output: None,
+            headless_frames: 1,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file named by `--config`, then `GFX_ADAPTER`, then the other
+    /// command line options, each overriding the settings before it.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::parse(Arguments::from_env())
+    }
+
+    /// Reads a TOML file. Missing settings keep their defaults.
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.into(), err))?;
+        let config: Config =
+            toml::from_str(&text).map_err(|err| ConfigError::Toml(path.into(), err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn size(&self) -> window::Extent2D {
+        window::Extent2D {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn parse(mut args: Arguments) -> Result<Self, ConfigError> {
+        if args.contains(["-h", "--help"]) {
+            return Err(ConfigError::Help);
+        }
+        let mut config = match args.opt_value_from_os_str(["-c", "--config"], parse_path)? {
+            Some(path) => Self::read(&path)?,
+            None => Config::default(),
+        };
+        if let Ok(spec) = env::var(ADAPTER_VAR) {
+            config.adapter = Some(spec);
+        }
+
+        if let Some(width) = args.opt_value_from_str("--width")? {
+            config.width = width;
+        }
+        if let Some(height) = args.opt_value_from_str("--height")? {
+            config.height = height;
+        }
+        if let Some(title) = args.opt_value_from_str("--title")? {
+            config.title = title;
+        }
+        if args.contains("--fullscreen") {
+            config.fullscreen = true;
+        }
+        if let Some(mode) = args.opt_value_from_str("--fullscreen-mode")? {
+            config.fullscreen_mode = mode;
+        }
+        if let Some(monitor) = args.opt_value_from_str("--monitor")? {
+            config.monitor = Some(monitor);
+        }
+        if args.contains("--no-vsync") {
+            config.present_mode = PresentMode::Immediate;
+        }
+        if let Some(present_mode) = args.opt_value_from_str("--present-mode")? {
+            config.present_mode = present_mode;
+        }
+        if let Some(images) = args.opt_value_from_str("--swapchain-images")? {
+            config.swapchain_images = Some(images);
+        }
+        if let Some(msaa) = args.opt_value_from_str("--msaa")? {
+            config.msaa = msaa;
+        }
+        if let Some(spec) = args.opt_value_from_str("--adapter")? {
+            config.adapter = Some(spec);
+        }
+        if let Some(frames) = args.opt_value_from_str("--frames-in-flight")? {
+            config.frames_in_flight = frames;
+        }
+        if let Some(color) = args.opt_value_from_fn("--clear-color", parse_color)? {
+            config.clear_color = color;
+        }
+        if let Some(path) = args.opt_value_from_os_str("--background", parse_path)? {
+            config.background = Some(path);
+        }
+        if let Some(path) = args.opt_value_from_os_str("--mesh", parse_path)? {
+            config.mesh = Some(path);
+        }
+        if let Some(path) = args.opt_value_from_os_str("--headless", parse_path)? {
+            config.headless_output = Some(path);
+        }
+        if let Some(frames) = args.opt_value_from_str("--headless-frames")? {
+            config.headless_frames = frames;
+        }
+
+        let unused = args.finish();
+        if !unused.is_empty() {
+            return Err(ConfigError::Unused(unused));
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: &str| Err(ConfigError::Invalid(message.to_owned()));
+        if self.width == 0 || self.height == 0 {
+            return invalid("the width and height must be positive");
+        }
+        if self.swapchain_images == Some(0) {
+            return invalid("the swapchain needs at least one image");
+        }
+        if !self.msaa.is_power_of_two() || self.msaa > 64 {
+            return invalid("msaa must be 1, 2, 4, 8, 16, 32 or 64");
+        }
+        if self.frames_in_flight == 0 {
+            return invalid("at least one frame must be in flight");
+        }
+        if self.headless_frames == 0 {
+            return invalid("at least one frame must be rendered headless");
+        }
+        if !in_unit_range(self.clear_color) {
+            return invalid("the clear color components must be from 0 to 1");
+        }
+        if let Some(spec) = &self.adapter {
+            AdapterPolicy::default()
+                .apply(spec)
+                .map_err(ConfigError::Invalid)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_path(s: &OsStr) -> Result<PathBuf, String> {
+    Ok(PathBuf::from(s))
+}
+
+/// Parses `r,g,b` or `r,g,b,a` with components from 0 to 1.
+fn parse_color(s: &str) -> Result<[f32; 4], String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid color `{}`: {}", s, err))?;
+    let color = match components[..] {
+        [r, g, b] => [r, g, b, 1.0],
+        [r, g, b, a] => [r, g, b, a],
+        _ => return Err(format!("invalid color `{}`: expected 3 or 4 components", s)),
+    };
+    if !in_unit_range(color) {
+        return Err(format!(
+            "invalid color `{}`: components must be from 0 to 1",
+            s
+        ));
+    }
+    Ok(color)
+}
+
+fn in_unit_range(color: [f32; 4]) -> bool {
+    color.iter().all(|c| (0.0..=1.0).contains(c))
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// `--help` was passed.
+    Help,
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+    Args(pico_args::Error),
+    Unused(Vec<OsString>),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => f.write_str(USAGE),
+            ConfigError::Io(path, err) => write!(f, "Can't read {}: {}", path.display(), err),
+            ConfigError::Toml(path, err) => write!(f, "Invalid config {}: {}", path.display(), err),
+            ConfigError::Args(err) => write!(f, "{}", err),
+            ConfigError::Unused(args) => write!(f, "Unexpected arguments {:?}", args),
+            ConfigError::Invalid(message) => write!(f, "Invalid config: {}", message),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(_, err) => Some(err),
+            ConfigError::Toml(_, err) => Some(err),
+            ConfigError::Args(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<pico_args::Error> for ConfigError {
+    fn from(err: pico_args::Error) -> Self {
+        ConfigError::Args(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ConfigError> {
+        Config::parse(Arguments::from_vec(
+            args.iter().map(OsString::from).collect(),
+        ))
+    }
+
+    #[test]
+    fn parse_overrides_defaults() {
+        let config = parse(&[
+            "--width",
+            "640",
+            "--height=480",
+            "--fullscreen",
+            "--fullscreen-mode",
+            "exclusive",
+            "--present-mode",
+            "mailbox",
+            "--msaa",
+            "4",
+            "--adapter",
+            "discrete",
+            "--frames-in-flight",
+            "2",
+            "--clear-color",
+            "0, 0.5, 1",
+            "--background",
+            "sky.ktx2",
+            "--mesh",
+            "teapot.glb",
+            "--headless",
+            "out.png",
+        ])
+        .unwrap();
+        assert_eq!((config.width, config.height), (640, 480));
+        assert!(config.fullscreen);
+        assert_eq!(config.fullscreen_mode, FullscreenMode::Exclusive);
+        assert_eq!(config.present_mode, PresentMode::Mailbox);
+        assert_eq!(config.msaa, 4);
+        assert_eq!(config.adapter.as_deref(), Some("discrete"));
+        assert_eq!(config.frames_in_flight, 2);
+        assert_eq!(config.clear_color, [0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(config.background, Some(PathBuf::from("sky.ktx2")));
+        assert_eq!(config.mesh, Some(PathBuf::from("teapot.glb")));
+        assert_eq!(config.headless_output, Some(PathBuf::from("out.png")));
+        assert_eq!(config.title, Config::default().title);
+    }
+
+    #[test]
+    fn parse_rejects_bad_arguments() {
+        assert!(matches!(parse(&["--help"]), Err(ConfigError::Help)));
+        assert!(matches!(
+            parse(&["--widht", "640"]),
+            Err(ConfigError::Unused(_))
+        ));
+        assert!(matches!(
+            parse(&["--width", "wide"]),
+            Err(ConfigError::Args(_))
+        ));
+        assert!(matches!(
+            parse(&["--present-mode", "sometimes"]),
+            Err(ConfigError::Args(_))
+        ));
+        assert!(matches!(
+            parse(&["--adapter", "dicrete"]),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn parse_color_accepts_three_or_four_components_in_range() {
+        assert_eq!(parse_color("1,0,0"), Ok([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_color(" 0.1, 0.2 ,0.3,0"), Ok([0.1, 0.2, 0.3, 0.0]));
+        assert!(parse_color("1,0").is_err());
+        assert!(parse_color("1,0,0,0,0").is_err());
+        assert!(parse_color("1,0,red").is_err());
+        assert!(parse_color("255,0,0").is_err());
+        assert!(parse_color("0,0,-0.5").is_err());
+        assert!(parse_color("0,0,NaN").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_settings() {
+        assert!(Config::default().validate().is_ok());
+        let invalid = |config: Config| matches!(config.validate(), Err(ConfigError::Invalid(_)));
+        assert!(invalid(Config {
+            width: 0,
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            swapchain_images: Some(0),
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            msaa: 3,
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            msaa: 128,
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            frames_in_flight: 0,
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            headless_frames: 0,
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            clear_color: [2.0, 0.0, 0.0, 1.0],
+            ..Config::default()
+        }));
+        assert!(invalid(Config {
+            adapter: Some("fastest".to_owned()),
+            ..Config::default()
+        }));
+    }
+
+    #[test]
+    fn toml_uses_defaults_and_rejects_unknown_keys() {
+        let config: Config =
+            toml::from_str("width = 800\nclear_color = [0.0, 0.0, 0.0, 1.0]").unwrap();
+        assert_eq!(config.width, 800);
+        assert_eq!(config.height, Config::default().height);
+        assert_eq!(config.clear_color, [0.0, 0.0, 0.0, 1.0]);
+        assert!(toml::from_str::<Config>("widht = 800").is_err());
+    }
+}
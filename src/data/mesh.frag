@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Direction towards the light, in world space.
+const vec3 LIGHT = vec3(0.3, 1.0, 0.5);
+const float AMBIENT = 0.3;
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 2) in vec4 v_color;
+layout(location = 0) out vec4 target0;
+
+void main() {
+    float diffuse = max(dot(normalize(v_normal), normalize(LIGHT)), 0.0);
+    target0 = vec4(v_color.rgb * (AMBIENT + (1.0 - AMBIENT) * diffuse), v_color.a);
+}
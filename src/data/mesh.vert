@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+} camera;
+
+layout(set = 0, binding = 1) uniform Object {
+    mat4 model;
+    // The inverse transpose of `model`, see `math::normal_matrix`.
+    mat4 normal;
+} object;
+
+layout(location = 0) in vec3 a_pos;
+layout(location = 1) in vec3 a_normal;
+layout(location = 2) in vec2 a_uv;
+layout(location = 3) in vec4 a_color;
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec2 v_uv;
+layout(location = 2) out vec4 v_color;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    v_normal = mat3(object.normal) * a_normal;
+    v_uv = a_uv;
+    v_color = a_color;
+    gl_Position = camera.proj * camera.view * object.model * vec4(a_pos, 1.0);
+}
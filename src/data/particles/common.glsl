@@ -0,0 +1,6 @@
+// A particle as stored in the emitter's buffer: position and age in seconds, velocity
+// and lifetime in seconds. It is dead once its age reaches its lifetime.
+struct Particle {
+    vec4 pos_age;
+    vec4 vel_life;
+};
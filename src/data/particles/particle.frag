@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 v_color;
+layout(location = 1) in vec2 v_uv;
+layout(location = 0) out vec4 target0;
+
+// A soft disc, premultiplied so additive blending weights it by alpha.
+void main() {
+    float alpha = v_color.a * (1.0 - smoothstep(0.5, 1.0, length(v_uv)));
+    target0 = vec4(v_color.rgb * alpha, alpha);
+}
@@ -0,0 +1,68 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Expands every particle instance into a camera-facing quad of 6 vertices, colored
+// and sized by the emitter's curves at the particle's normalized age.
+
+layout(set = 0, binding = 0) uniform Params {
+    mat4 view_proj;
+    vec4 right;
+    vec4 up;
+    // Up to 4 keys per curve, unused keys repeat the last one.
+    vec4 colors[4];
+    vec4 color_times;
+    vec4 sizes;
+    vec4 size_times;
+} u;
+
+layout(location = 0) in vec4 a_pos_age;
+layout(location = 1) in vec4 a_vel_life;
+layout(location = 0) out vec4 v_color;
+layout(location = 1) out vec2 v_uv;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+const vec2 CORNERS[6] = vec2[6](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0));
+
+float key_weight(float t, float t0, float t1) {
+    return t1 > t0 ? clamp((t - t0) / (t1 - t0), 0.0, 1.0) : step(t1, t);
+}
+
+vec4 sample_color(float t) {
+    vec4 color = u.colors[0];
+    for (int k = 1; k < 4; k++) {
+        color = mix(color, u.colors[k], key_weight(t, u.color_times[k - 1], u.color_times[k]));
+    }
+    return color;
+}
+
+float sample_size(float t) {
+    float size = u.sizes[0];
+    for (int k = 1; k < 4; k++) {
+        size = mix(size, u.sizes[k], key_weight(t, u.size_times[k - 1], u.size_times[k]));
+    }
+    return size;
+}
+
+void main() {
+    if (a_pos_age.w >= a_vel_life.w) {
+        // Dead particles collapse to a point and produce no fragments.
+        v_color = vec4(0.0);
+        v_uv = vec2(0.0);
+        gl_Position = vec4(0.0);
+        return;
+    }
+
+    vec2 corner = CORNERS[gl_VertexIndex];
+    float t = a_pos_age.w / a_vel_life.w;
+    float half_size = 0.5 * sample_size(t);
+    vec3 pos = a_pos_age.xyz + (u.right.xyz * corner.x + u.up.xyz * corner.y) * half_size;
+
+    v_color = sample_color(t);
+    v_uv = corner;
+    gl_Position = u.view_proj * vec4(pos, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform texture2D u_input;
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 0) out vec4 target0;
+
+void main() {
+    target0 = vec4(texture(sampler2D(u_input, u_sampler), v_uv).rgb, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#include "common.glsl"
+
+// params.x: radius in texels
+void main() {
+    const float weights[3] = float[](0.25, 0.125, 0.0625);
+    vec2 step = u.texel_size * u.params.x;
+    vec3 color = vec3(0.0);
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            float weight = weights[abs(x) + abs(y)];
+            color += weight * sample_input(v_uv + vec2(x, y) * step);
+        }
+    }
+    target0 = vec4(color, 1.0);
+}
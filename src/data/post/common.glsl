@@ -0,0 +1,15 @@
+// Interface shared by the post-processing effects: the previous pass's output and
+// the effect's parameters, plus the size of one texel of the input.
+layout(set = 0, binding = 0) uniform texture2D u_input;
+layout(set = 0, binding = 1) uniform sampler u_sampler;
+layout(set = 0, binding = 2) uniform Params {
+    vec4 params;
+    vec2 texel_size;
+} u;
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 0) out vec4 target0;
+
+vec3 sample_input(vec2 uv) {
+    return texture(sampler2D(u_input, u_sampler), uv).rgb;
+}
@@ -0,0 +1,40 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#include "common.glsl"
+
+// params.x: subpixel blend amount, params.y: edge threshold, params.z: maximum span in texels
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+float luma(vec2 uv) {
+    return dot(sample_input(uv), LUMA);
+}
+
+void main() {
+    vec2 texel = u.texel_size;
+    float nw = luma(v_uv + vec2(-1.0, -1.0) * texel);
+    float ne = luma(v_uv + vec2(1.0, -1.0) * texel);
+    float sw = luma(v_uv + vec2(-1.0, 1.0) * texel);
+    float se = luma(v_uv + vec2(1.0, 1.0) * texel);
+    vec3 center = sample_input(v_uv);
+    float m = dot(center, LUMA);
+
+    float lo = min(m, min(min(nw, ne), min(sw, se)));
+    float hi = max(m, max(max(nw, ne), max(sw, se)));
+    if (hi - lo < max(u.params.y, hi * 0.125)) {
+        target0 = vec4(center, 1.0);
+        return;
+    }
+
+    vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    float reduce = max((nw + ne + sw + se) * 0.25 * u.params.x * 0.25, 1.0 / 128.0);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-u.params.z), vec2(u.params.z)) * texel;
+
+    vec3 a = 0.5 * (sample_input(v_uv + dir * (1.0 / 3.0 - 0.5))
+        + sample_input(v_uv + dir * (2.0 / 3.0 - 0.5)));
+    vec3 b = a * 0.5 + 0.25 * (sample_input(v_uv - dir * 0.5)
+        + sample_input(v_uv + dir * 0.5));
+    float lb = dot(b, LUMA);
+    target0 = vec4(lb < lo || lb > hi ? a : b, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#include "common.glsl"
+
+// params.x: gamma
+void main() {
+    vec3 color = sample_input(v_uv);
+    target0 = vec4(pow(max(color, vec3(0.0)), vec3(1.0 / u.params.x)), 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#include "common.glsl"
+
+// params.x: intensity, params.y: radius where darkening starts, params.z: softness
+void main() {
+    vec3 color = sample_input(v_uv);
+    float dist = distance(v_uv, vec2(0.5)) * sqrt(2.0);
+    float shade = smoothstep(u.params.y, u.params.y + u.params.z, dist);
+    target0 = vec4(color * (1.0 - u.params.x * shade), 1.0);
+}
@@ -3,9 +3,14 @@
 
 layout(constant_id = 0) const float scale = 1.0f;
 
-layout(set = 0, binding = 0) uniform UBOCoord {
-    vec2 coord;
-} coord_data;
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+} camera;
+
+layout(set = 0, binding = 1) uniform Object {
+    mat4 model;
+} object;
 
 layout(location = 0) in vec2 a_pos;
 layout(location = 1) in vec3 a_color;
@@ -17,5 +22,5 @@ out gl_PerVertex {
 
 void main() {
     v_color = a_color;
-    gl_Position = vec4(scale * (a_pos + coord_data.coord), 0.0, 1.0);
+    gl_Position = camera.proj * camera.view * object.model * vec4(scale * a_pos, 0.0, 1.0);
 }
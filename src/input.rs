@@ -0,0 +1,393 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+use winit::dpi::PhysicalSize;
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Window events the render thread cares about, detached from winit's event lifetimes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    Key {
+        key: VirtualKeyCode,
+        state: ElementState,
+    },
+    MouseButton {
+        button: MouseButton,
+        state: ElementState,
+    },
+    /// In physical pixels from the top left corner of the window.
+    CursorMoved([f32; 2]),
+    CursorLeft,
+    /// In lines, positive when scrolling up and right.
+    Scroll([f32; 2]),
+    Focused(bool),
+    Resized(PhysicalSize<u32>),
+    /// The window also takes `size` when the scale factor changes.
+    ScaleFactorChanged {
+        scale_factor: f64,
+        size: PhysicalSize<u32>,
+    },
+    FileDropped(PathBuf),
+    CloseRequested,
+}
+
+/// Pixels scrolled per line by touchpads reporting exact deltas.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => InputEvent::Key { key, state },
+            WindowEvent::MouseInput { button, state, .. } => {
+                InputEvent::MouseButton { button, state }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                InputEvent::CursorMoved([position.x as f32, position.y as f32])
+            }
+            WindowEvent::CursorLeft { .. } => InputEvent::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => InputEvent::Scroll(match delta {
+                MouseScrollDelta::LineDelta(x, y) => [x, y],
+                MouseScrollDelta::PixelDelta(pixels) => [
+                    (pixels.x / PIXELS_PER_LINE) as f32,
+                    (pixels.y / PIXELS_PER_LINE) as f32,
+                ],
+            }),
+            WindowEvent::Focused(focused) => InputEvent::Focused(focused),
+            WindowEvent::Resized(size) => InputEvent::Resized(size),
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                ref new_inner_size,
+            } => InputEvent::ScaleFactorChanged {
+                scale_factor,
+                size: **new_inner_size,
+            },
+            WindowEvent::DroppedFile(ref path) => InputEvent::FileDropped(path.clone()),
+            WindowEvent::CloseRequested => InputEvent::CloseRequested,
+            _ => return None,
+        })
+    }
+}
+
+/// What the input looked like at the start of a frame.
+///
+/// Held keys and buttons and the cursor position carry over between frames, everything
+/// else only covers the events since the previous frame.
+#[derive(Debug, Clone)]
+pub struct InputState {
+    pub keys: HashSet<VirtualKeyCode>,
+    pub pressed: HashSet<VirtualKeyCode>,
+    pub buttons: HashSet<MouseButton>,
+    /// `None` while the cursor is outside the window.
+    pub cursor: Option<[f32; 2]>,
+    /// How far the cursor moved inside the window.
+    pub cursor_delta: [f32; 2],
+    pub scroll: [f32; 2],
+    pub focused: bool,
+    pub size: PhysicalSize<u32>,
+    pub scale_factor: f64,
+    /// Whether `size` changed.
+    pub resized: bool,
+    pub dropped_files: Vec<PathBuf>,
+    pub close_requested: bool,
+    /// Every event, in order.
+    pub events: Vec<InputEvent>,
+}
+
+impl InputState {
+    pub fn new(size: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        InputState {
+            keys: HashSet::new(),
+            pressed: HashSet::new(),
+            buttons: HashSet::new(),
+            cursor: None,
+            cursor_delta: [0.0; 2],
+            scroll: [0.0; 2],
+            focused: true,
+            size,
+            scale_factor,
+            resized: false,
+            dropped_files: vec![],
+            close_requested: false,
+            events: vec![],
+        }
+    }
+
+    pub fn key_held(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Whether `key` went down since the previous frame, ignoring key repeat.
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.pressed.contains(&key)
+    }
+
+    pub fn button_held(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.cursor_delta = [0.0; 2];
+        self.scroll = [0.0; 2];
+        self.resized = false;
+        self.dropped_files.clear();
+        self.events.clear();
+    }
+
+    fn apply(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key { key, state } => match state {
+                ElementState::Pressed => {
+                    if self.keys.insert(key) {
+                        self.pressed.insert(key);
+                    }
+                }
+                ElementState::Released => {
+                    self.keys.remove(&key);
+                }
+            },
+            InputEvent::MouseButton { button, state } => match state {
+                ElementState::Pressed => {
+                    self.buttons.insert(button);
+                }
+                ElementState::Released => {
+                    self.buttons.remove(&button);
+                }
+            },
+            InputEvent::CursorMoved(position) => {
+                if let Some(last) = self.cursor {
+                    self.cursor_delta[0] += position[0] - last[0];
+                    self.cursor_delta[1] += position[1] - last[1];
+                }
+                self.cursor = Some(position);
+            }
+            InputEvent::CursorLeft => self.cursor = None,
+            InputEvent::Scroll([x, y]) => {
+                self.scroll[0] += x;
+                self.scroll[1] += y;
+            }
+            InputEvent::Focused(focused) => {
+                self.focused = focused;
+                // Releases outside the window never arrive.
+                if !focused {
+                    self.keys.clear();
+                    self.buttons.clear();
+                }
+            }
+            InputEvent::Resized(size) => {
+                self.resized |= size != self.size;
+                self.size = size;
+            }
+            InputEvent::ScaleFactorChanged { scale_factor, size } => {
+                self.scale_factor = scale_factor;
+                self.resized |= size != self.size;
+                self.size = size;
+            }
+            InputEvent::FileDropped(ref path) => self.dropped_files.push(path.clone()),
+            InputEvent::CloseRequested => self.close_requested = true,
+        }
+        self.events.push(event);
+    }
+}
+
+/// The winit thread's end of the input channel.
+pub struct InputSender {
+    sender: Sender<InputEvent>,
+}
+
+impl InputSender {
+    /// Forwards `event` if it is one of `InputEvent`'s. Events sent after the receiving
+    /// end is gone are dropped.
+    pub fn send(&self, event: &WindowEvent) {
+        if let Some(event) = InputEvent::from_window_event(event) {
+            self.sender.send(event).ok();
+        }
+    }
+
+    /// Asks the render thread to stop.
+    pub fn close(&self) {
+        self.sender.send(InputEvent::CloseRequested).ok();
+    }
+}
+
+/// The render thread's end of the input channel.
+pub struct Input {
+    receiver: Receiver<InputEvent>,
+    state: InputState,
+}
+
+impl Input {
+    /// Starts a new frame with the events sent since the previous one. A closed channel
+    /// counts as a close request.
+    pub fn poll(&mut self) -> &InputState {
+        self.state.begin_frame();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => self.state.apply(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.state.close_requested = true;
+                    break;
+                }
+            }
+        }
+        &self.state
+    }
+}
+
+/// Creates a channel for a window currently `size` large at `scale_factor`.
+pub fn channel(size: PhysicalSize<u32>, scale_factor: f64) -> (InputSender, Input) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        InputSender { sender },
+        Input {
+            receiver,
+            state: InputState::new(size, scale_factor),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> InputState {
+        InputState::new(PhysicalSize::new(800, 600), 1.0)
+    }
+
+    fn key(key: VirtualKeyCode, state: ElementState) -> InputEvent {
+        InputEvent::Key { key, state }
+    }
+
+    #[test]
+    fn key_repeat_is_not_pressed_again() {
+        let mut input = state();
+        input.apply(key(VirtualKeyCode::A, ElementState::Pressed));
+        assert!(input.key_pressed(VirtualKeyCode::A));
+
+        input.begin_frame();
+        input.apply(key(VirtualKeyCode::A, ElementState::Pressed));
+        assert!(!input.key_pressed(VirtualKeyCode::A));
+        assert!(input.key_held(VirtualKeyCode::A));
+        assert_eq!(input.events.len(), 1);
+    }
+
+    #[test]
+    fn keys_are_pressed_held_and_released_across_frames() {
+        let mut input = state();
+        input.apply(key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(input.key_pressed(VirtualKeyCode::W));
+        assert!(input.key_held(VirtualKeyCode::W));
+
+        input.begin_frame();
+        assert!(!input.key_pressed(VirtualKeyCode::W));
+        assert!(input.key_held(VirtualKeyCode::W));
+
+        input.apply(key(VirtualKeyCode::W, ElementState::Released));
+        assert!(!input.key_held(VirtualKeyCode::W));
+
+        // A tap within one frame still counts as a press.
+        input.begin_frame();
+        input.apply(key(VirtualKeyCode::Space, ElementState::Pressed));
+        input.apply(key(VirtualKeyCode::Space, ElementState::Released));
+        assert!(input.key_pressed(VirtualKeyCode::Space));
+        assert!(!input.key_held(VirtualKeyCode::Space));
+
+        input.apply(InputEvent::MouseButton {
+            button: MouseButton::Left,
+            state: ElementState::Pressed,
+        });
+        assert!(input.button_held(MouseButton::Left));
+        input.apply(InputEvent::MouseButton {
+            button: MouseButton::Left,
+            state: ElementState::Released,
+        });
+        assert!(!input.button_held(MouseButton::Left));
+    }
+
+    #[test]
+    fn cursor_delta_adds_up_moves_inside_the_window() {
+        let mut input = state();
+        // Entering the window isn't a move.
+        input.apply(InputEvent::CursorMoved([10.0, 10.0]));
+        assert_eq!(input.cursor_delta, [0.0; 2]);
+        input.apply(InputEvent::CursorMoved([15.0, 8.0]));
+        input.apply(InputEvent::CursorMoved([20.0, 12.0]));
+        assert_eq!(input.cursor_delta, [10.0, 2.0]);
+        assert_eq!(input.cursor, Some([20.0, 12.0]));
+
+        input.apply(InputEvent::CursorLeft);
+        input.apply(InputEvent::CursorMoved([100.0, 100.0]));
+        assert_eq!(input.cursor_delta, [10.0, 2.0]);
+
+        input.begin_frame();
+        assert_eq!(input.cursor_delta, [0.0; 2]);
+        assert_eq!(input.cursor, Some([100.0, 100.0]));
+    }
+
+    #[test]
+    fn scroll_and_per_frame_state_reset_with_each_frame() {
+        let mut input = state();
+        input.apply(InputEvent::Scroll([0.0, 1.0]));
+        input.apply(InputEvent::Scroll([0.5, 2.0]));
+        input.apply(InputEvent::Resized(PhysicalSize::new(640, 480)));
+        input.apply(InputEvent::FileDropped(PathBuf::from("model.glb")));
+        assert_eq!(input.scroll, [0.5, 3.0]);
+        assert!(input.resized);
+        assert_eq!(input.dropped_files, [PathBuf::from("model.glb")]);
+
+        input.begin_frame();
+        assert_eq!(input.scroll, [0.0; 2]);
+        assert!(!input.resized);
+        assert!(input.dropped_files.is_empty());
+        assert!(input.events.is_empty());
+        assert_eq!(input.size, PhysicalSize::new(640, 480));
+
+        // Resizing to the same size isn't a resize.
+        input.apply(InputEvent::Resized(PhysicalSize::new(640, 480)));
+        assert!(!input.resized);
+    }
+
+    #[test]
+    fn losing_focus_releases_everything_held() {
+        let mut input = state();
+        input.apply(key(VirtualKeyCode::LShift, ElementState::Pressed));
+        input.apply(InputEvent::MouseButton {
+            button: MouseButton::Right,
+            state: ElementState::Pressed,
+        });
+        input.apply(InputEvent::Focused(false));
+        assert!(!input.focused);
+        assert!(input.keys.is_empty());
+        assert!(input.buttons.is_empty());
+
+        // Pressing again after regaining focus is a new press.
+        input.begin_frame();
+        input.apply(InputEvent::Focused(true));
+        input.apply(key(VirtualKeyCode::LShift, ElementState::Pressed));
+        assert!(input.key_pressed(VirtualKeyCode::LShift));
+    }
+
+    #[test]
+    fn poll_applies_sent_events_and_treats_a_closed_channel_as_closing() {
+        let (sender, mut input) = channel(PhysicalSize::new(800, 600), 1.0);
+        sender.sender.send(InputEvent::Scroll([0.0, 1.0])).unwrap();
+        let state = input.poll();
+        assert_eq!(state.scroll, [0.0, 1.0]);
+        assert!(!state.close_requested);
+
+        drop(sender);
+        assert!(input.poll().close_requested);
+    }
+}
@@ -4,119 +4,139 @@ use gfx_backend_metal as back;
 #[cfg(feature = "vulkan")]
 use gfx_backend_vulkan as back;
 
+mod adapter;
+mod app;
+mod clock;
+mod config;
+mod input;
 mod renderer;
-use renderer::Renderer;
-
-use std::sync::atomic::{AtomicBool, Ordering};
-
-use gfx_hal::{prelude::*, window, Features};
-use std::thread;
+mod stats;
+mod window_mode;
+use app::{App, AppOptions};
+use clock::Time;
+use config::{Config, ConfigError};
+use input::InputState;
+use renderer::{math, EmitterDesc, EmitterId, Frame, Gradient, Simulation};
+
+/// A triangle circling the origin above a fountain of sparks, trailing smoke simulated
+/// on the CPU. The triangle turns towards the camera and the background dims while the
+/// clock is paused.
+struct Demo {
+    /// The angle before and after the last fixed step.
+    angles: (f32, f32),
+    alpha: f32,
+    paused: bool,
+    clear_color: [f32; 4],
+    trail: Option<EmitterId>,
+}
 
-use std::sync::Arc;
+impl Demo {
+    fn new(clear_color: [f32; 4]) -> Self {
+        Demo {
+            angles: (0.0, 0.0),
+            alpha: 0.0,
+            paused: false,
+            clear_color,
+            trail: None,
+        }
+    }
+}
 
-const DIMS: window::Extent2D = window::Extent2D {
-    width: 1024,
-    height: 768,
-};
+impl App for Demo {
+    fn init(&mut self, frame: &mut Frame<back::Backend>) {
+        // Slightly from above, further back in a portrait window so the fountain fits.
+        let dims = frame.dims();
+        let distance = 1.5 * (dims.height as f32 / dims.width as f32).max(1.0);
+        frame.look_at([0.0, 0.4, distance], [0.0; 3]);
+
+        frame.particles_mut().add_emitter(EmitterDesc {
+            position: [0.0, -0.3, 0.0],
+            spawn_rate: 300.0,
+            lifetime: 1.0..2.0,
+            velocity: [0.0, 0.6, 0.0],
+            spread: 0.2,
+            gravity: [0.0, -0.4, 0.0],
+            color: Gradient::new(vec![
+                (0.0, [1.0, 0.9, 0.4, 1.0]),
+                (0.5, [1.0, 0.3, 0.1, 0.8]),
+                (1.0, [0.5, 0.0, 0.0, 0.0]),
+            ]),
+            size: Gradient::new(vec![(0.0, 0.03), (1.0, 0.08)]),
+            capacity: 640,
+            ..EmitterDesc::default()
+        });
+        self.trail = Some(frame.particles_mut().add_emitter(EmitterDesc {
+            spawn_rate: 60.0,
+            lifetime: 0.5..1.0,
+            velocity: [0.0; 3],
+            spread: 0.02,
+            color: Gradient::new(vec![
+                (0.0, [0.4, 0.6, 1.0, 0.6]),
+                (1.0, [0.1, 0.1, 0.3, 0.0]),
+            ]),
+            size: Gradient::new(vec![(0.0, 0.02), (1.0, 0.05)]),
+            capacity: 64,
+            simulation: Simulation::Cpu,
+            ..EmitterDesc::default()
+        }));
+    }
+
+    fn fixed_update(&mut self, time: &Time, _input: &InputState) {
+        // Half a turn per second.
+        let (_, angle) = self.angles;
+        self.angles = (angle, angle + std::f32::consts::PI * time.fixed_step);
+    }
+
+    fn update(&mut self, time: &Time, _input: &InputState) {
+        self.alpha = time.alpha;
+        self.paused = time.paused;
+    }
+
+    fn render(&mut self, frame: &mut Frame<back::Backend>) {
+        let (previous, current) = self.angles;
+        let angle = previous + (current - previous) * self.alpha;
+        let position = [0.1 * angle.cos(), 0.1 * angle.sin(), 0.0];
+        let camera = frame.camera();
+        let [x, _, z] = math::sub(camera.position, camera.target);
+        frame.set_model(math::mul(
+            math::translation(position),
+            math::rotation([0.0, 1.0, 0.0], x.atan2(z)),
+        ));
+        // The model turns once every four seconds, at half its size.
+        frame.set_mesh_model(math::mul(
+            math::rotation([0.0, 1.0, 0.0], angle / 2.0),
+            math::scaling([0.5; 3]),
+        ));
+        if let Some(trail) = self.trail {
+            frame.particles_mut().emitter_mut(trail).position = position;
+        }
+        let [r, g, b, a] = self.clear_color;
+        frame.set_clear_color(if self.paused {
+            [r * 0.5, g * 0.5, b * 0.5, a]
+        } else {
+            self.clear_color
+        });
+    }
+}
 
 fn main() {
     env_logger::init();
-    #[cfg(debug_assersion)]
-    let mut fps_counter = fps_counter::FPSCounter::new();
-    let event_loop = winit::event_loop::EventLoop::new();
-    let wb = winit::window::WindowBuilder::new()
-        .with_title("triangle")
-        .with_inner_size(winit::dpi::Size::Physical(winit::dpi::PhysicalSize::new(
-            DIMS.width,
-            DIMS.height,
-        )))
-        .with_min_inner_size(winit::dpi::Size::Logical(winit::dpi::LogicalSize::new(
-            64.0, 64.0,
-        )));
-    let window = wb.build(&event_loop).expect("failed to create window");
-
-    let window_should_closed = Arc::new(AtomicBool::new(false));
-    let resized = Arc::new(AtomicBool::new(false));
-
-    let window_should_closed_mutex = Arc::clone(&window_should_closed);
-    let resized_cloned = Arc::clone(&resized);
-    let handler = thread::spawn(move || {
-        let instance = back::Instance::create("gfx-rs triangle", 1)
-            .expect("failed to create an instance of gfx");
-        let mut adapters = instance.enumerate_adapters();
-        let mut surface = unsafe {
-            instance
-                .create_surface(&window)
-                .expect("failed to create a surface")
-        };
-        let adapter = adapters.remove(0);
-
-        let family = adapter
-            .queue_families
-            .iter()
-            .find(|family| {
-                surface.supports_queue_family(family) && family.queue_type().supports_graphics()
-            })
-            .unwrap();
-        let mut gpu = unsafe {
-            adapter
-                .physical_device
-                .open(&[(&family, &[1.0])], Features::empty())
-                .unwrap()
-        };
-
-        let mut queue_group = gpu.queue_groups.pop().unwrap();
-        let queue = &mut queue_group.queues[0];
-        let device = gpu.device;
-
-        {
-            let mut renderer = Renderer::new(
-                &mut surface,
-                &adapter,
-                &device,
-                queue_group.family,
-                DIMS,
-                resized_cloned,
-            );
-
-            while !window_should_closed_mutex.load(Ordering::Relaxed) {
-                #[cfg(debug_assersion)]
-                println!("frame: {}", fps_counter.tick());
-                renderer.render(queue);
-            }
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(ConfigError::Help) => {
+            println!("{}", config::USAGE);
+            return;
         }
-
-        unsafe {
-            instance.destroy_surface(surface);
-        }
-    });
-
-    let mut handler = Some(handler);
-
-    event_loop.run(move |event, _, control_flow| {
-        if let winit::event::Event::WindowEvent { event, .. } = event {
-            match event {
-                winit::event::WindowEvent::CloseRequested
-                | winit::event::WindowEvent::KeyboardInput {
-                    input:
-                        winit::event::KeyboardInput {
-                            virtual_keycode: Some(winit::event::VirtualKeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => {
-                    window_should_closed.store(true, Ordering::Relaxed);
-                    println!("closed");
-                    if let Some(handler) = handler.take() {
-                        handler.join().unwrap();
-                    }
-                    *control_flow = winit::event_loop::ControlFlow::Exit;
-                }
-                winit::event::WindowEvent::Resized(_) => {
-                    resized.store(true, Ordering::Relaxed);
-                }
-                _ => {}
-            }
+        Err(err) => {
+            eprintln!("{}\nRun with --help for the options.", err);
+            std::process::exit(2);
         }
-    });
+    };
+    app::run(
+        Demo::new(config.clear_color),
+        AppOptions {
+            config,
+            ..AppOptions::default()
+        },
+    );
 }
@@ -1,5 +1,5 @@
 use gfx_hal::{
-    adapter, buffer as b, command, format as f, image as i, pass, pool,
+    adapter, buffer as b, command, format as f, image as i, pool,
     prelude::*,
     pso,
     queue::{family::QueueFamilyId, Submission},
@@ -7,203 +7,620 @@ use gfx_hal::{
 };
 
 use std::borrow::Borrow;
+use std::io;
 use std::iter;
 use std::mem::ManuallyDrop;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::clock::Time;
+use crate::config::{Config, PresentMode};
+use crate::input::InputState;
 
+mod background;
 mod buffer;
+mod camera;
+mod descriptor_allocator;
 mod descriptor_set;
+mod frame;
+mod geometry;
+mod graph;
+mod headless;
+pub mod math;
 mod memory;
+mod mesh;
+mod particles;
 mod pipeline;
+mod post;
+mod reflect;
+mod render_target;
+mod shader;
 mod swapchain;
+mod texture;
 mod vertex;
 
+use background::Background;
 use buffer::Buffer;
-use descriptor_set::DescriptorSet;
+use camera::{Camera, CameraController, CameraUniform};
+use descriptor_set::{DescriptorLayout, DescriptorSet};
+use graph::{
+    BufferAccess, BufferId, ImageAccess, ImageDesc, ImageId, ImageSize, LoadOp, PassId, PassKind,
+    RenderGraph,
+};
+use headless::Headless;
 use memory::Memory;
-use pipeline::Pipeline;
+use mesh::{Mesh, MeshUniform};
+use particles::ParticleSystem;
+use pipeline::{Pipeline, PipelineOptions};
+use post::PostProcess;
+use render_target::RenderTarget;
 use std::ptr;
 use swapchain::Swapchain;
-use vertex::{Vertex, TRIANGLE};
+use vertex::{MeshVertex, Vertex, TRIANGLE};
+
+pub use frame::Frame;
+pub use particles::{EmitterDesc, EmitterId, Gradient, Simulation};
+pub use post::EffectKind;
+
+/// What the render graph is built from besides the enabled effects and the emitters.
+struct GraphDesc {
+    backbuffer_format: f::Format,
+    /// Layouts the backbuffer starts and ends the frame in.
+    backbuffer_layouts: Range<i::Layout>,
+    scene_format: f::Format,
+    depth_format: f::Format,
+    samples: i::NumSamples,
+}
+
+/// The passes and images of the graph `Renderer::render` records.
+struct Passes {
+    backbuffer: ImageId,
+    /// The particle buffer of every emitter, in the order they were added.
+    emitters: Vec<BufferId>,
+    /// Only there with GPU emitters.
+    simulate: Option<PassId>,
+    scene: PassId,
+    /// The image the scene is drawn to, multisampled with MSAA.
+    scene_color: ImageId,
+    /// The effect passes in order, with the image each one reads.
+    effects: Vec<(EffectKind, PassId, ImageId)>,
+    present: PassId,
+    /// The image blitted to the backbuffer.
+    output: ImageId,
+}
+
+impl GraphDesc {
+    /// Declares a frame that simulates the GPU emitters, draws the scene, runs `effects`
+    /// in order and blits the result to the backbuffer.
+    fn build<'a, B: Backend>(
+        &self,
+        device: &'a B::Device,
+        adapter: &adapter::Adapter<B>,
+        effects: &[EffectKind],
+        emitters: &[Simulation],
+        extent: i::Extent,
+    ) -> (RenderGraph<'a, B>, Passes) {
+        let mut graph = RenderGraph::new(device, adapter);
+        let backbuffer = graph.import_image(
+            "backbuffer",
+            self.backbuffer_format,
+            self.backbuffer_layouts.clone(),
+        );
+        let emitter_buffers = (0..emitters.len())
+            .map(|n| graph.import_buffer(&format!("particles {}", n)))
+            .collect::<Vec<_>>();
+        let simulate = if emitters.contains(&Simulation::Gpu) {
+            let mut pass = graph.add_pass("simulate particles", PassKind::Compute);
+            for (&buffer, &simulation) in emitter_buffers.iter().zip(emitters) {
+                if simulation == Simulation::Gpu {
+                    pass.buffer(buffer, BufferAccess::StorageWrite);
+                }
+            }
+            Some(pass.id())
+        } else {
+            None
+        };
 
+        let image = |graph: &mut RenderGraph<'a, B>, name: &str, samples| {
+            graph.create_image(
+                name,
+                ImageDesc {
+                    format: self.scene_format,
+                    size: ImageSize::Relative(1.0),
+                    samples,
+                },
+            )
+        };
+        // Cleared to `Renderer::clear_color` before every frame.
+        let clear = LoadOp::Clear(clear_value([0.0; 4]));
+        let scene = image(&mut graph, "scene", 1);
+        let scene_color = if self.samples > 1 {
+            image(&mut graph, "multisampled scene", self.samples)
+        } else {
+            scene
+        };
+        let depth = graph.create_image(
+            "depth",
+            ImageDesc {
+                format: self.depth_format,
+                size: ImageSize::Relative(1.0),
+                samples: self.samples,
+            },
+        );
+        let mut pass = graph.add_pass("scene", PassKind::Graphics);
+        pass.color(scene_color, clear).depth(
+            depth,
+            LoadOp::Clear(command::ClearValue {
+                depth_stencil: command::ClearDepthStencil {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            }),
+        );
+        if scene_color != scene {
+            pass.resolve(scene_color, scene);
+        }
+        for &buffer in &emitter_buffers {
+            pass.buffer(buffer, BufferAccess::Vertex);
+        }
+        let scene_pass = pass.id();
+
+        let mut input = scene;
+        let mut effect_passes = vec![];
+        for &kind in effects {
+            let name = format!("{:?}", kind).to_lowercase();
+            let output = image(&mut graph, &name, 1);
+            let pass = graph
+                .add_pass(&name, PassKind::Graphics)
+                .image(input, ImageAccess::Sampled)
+                .color(output, LoadOp::DontCare)
+                .id();
+            effect_passes.push((kind, pass, input));
+            input = output;
+        }
+        let present = graph
+            .add_pass("present", PassKind::Graphics)
+            .image(input, ImageAccess::Sampled)
+            .color(backbuffer, clear)
+            .id();
+
+        graph.compile();
+        graph.resize(extent);
+        let passes = Passes {
+            backbuffer,
+            emitters: emitter_buffers,
+            simulate,
+            scene: scene_pass,
+            scene_color,
+            effects: effect_passes,
+            present,
+            output: input,
+        };
+        (graph, passes)
+    }
+}
+
+/// The uniforms of one frame in flight and the descriptor set binding them.
+struct FrameUniforms<'a, B: Backend> {
+    camera: Memory<'a, B, CameraUniform>,
+    object: Memory<'a, B, math::Mat4>,
+    descriptor_set: DescriptorSet<'a, B>,
+}
+
+/// The model from `Config::mesh`, placed by a model matrix of its own.
+struct SceneMesh<'a, B: Backend> {
+    mesh: Mesh<'a, B>,
+    pipeline: Pipeline<'a, B>,
+    /// By frame slot, each set also binding the slot's camera.
+    objects: Vec<(Memory<'a, B, MeshUniform>, DescriptorSet<'a, B>)>,
+    model: math::Mat4,
+}
+
+/// Draws the scene into the window's swapchain, or offscreen when created without a
+/// surface.
+///
+/// Up to `Config::frames_in_flight` frames are recorded ahead of the GPU. Every buffer
+/// the CPU writes each frame has a copy per frame in flight, so a frame never sees the
+/// uniforms of the next one.
 pub struct Renderer<'a, B: Backend> {
     frame: usize,
     device: &'a B::Device,
+    adapter: &'a adapter::Adapter<B>,
     frames_in_flight: usize,
     command_buffers: Option<Vec<B::CommandBuffer>>,
     submission_complete_semaphores: Vec<B::Semaphore>,
     submission_complete_fences: Vec<B::Fence>,
     command_pool: ManuallyDrop<B::CommandPool>,
-    descriptor_set: Option<ManuallyDrop<DescriptorSet<'a, B>>>,
+    /// By frame slot.
+    uniforms: Vec<FrameUniforms<'a, B>>,
     memory: ManuallyDrop<Memory<'a, B, Vertex>>,
-    uniform_memory: ManuallyDrop<Memory<'a, B, f32>>,
-    swapchain: ManuallyDrop<Swapchain<'a, B>>,
-    render_pass: ManuallyDrop<B::RenderPass>,
+    camera: Camera,
+    controller: CameraController,
+    swapchain: Option<ManuallyDrop<Swapchain<'a, B>>>,
+    headless: Option<ManuallyDrop<Headless<'a, B>>>,
+    graph: ManuallyDrop<RenderGraph<'a, B>>,
+    graph_desc: GraphDesc,
+    passes: Passes,
+    /// The enabled effects and the emitters `graph` was built for.
+    graph_key: (Vec<EffectKind>, Vec<Simulation>),
+    post: ManuallyDrop<PostProcess<'a, B>>,
+    background: ManuallyDrop<Background<'a, B>>,
+    mesh: Option<SceneMesh<'a, B>>,
     pipeline: ManuallyDrop<Pipeline<'a, B>>,
-    resized: Arc<AtomicBool>,
+    particles: ManuallyDrop<ParticleSystem<'a, B>>,
+    swapchain_outdated: bool,
+    blocked: Duration,
+    time: Time,
+    clear_color: [f32; 4],
+    model: math::Mat4,
 }
 
 impl<'a, B> Renderer<'a, B>
 where
     B: Backend,
 {
+    /// Creates a renderer presenting to `surface`, or rendering offscreen without one.
     pub fn new(
-        surface: &'a mut B::Surface,
+        surface: Option<&'a mut B::Surface>,
         adapter: &'a adapter::Adapter<B>,
         device: &'a B::Device,
         family: QueueFamilyId,
-        init_dims: window::Extent2D,
-        resized: Arc<AtomicBool>,
+        config: &Config,
     ) -> Self {
         let memory_types = adapter.physical_device.memory_properties().memory_types;
         let limits = adapter.physical_device.limits();
 
-        let descriptor_set = DescriptorSet::new(device);
+        let vs = &shader::TRIANGLE_VERT;
+        let fs = &shader::TRIANGLE_FRAG;
+        let shader_infos = [vs, fs]
+            .iter()
+            .map(|shader| shader.reflect())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let descriptor_layout =
+            DescriptorLayout::from_shaders(device, &shader_infos.iter().collect::<Vec<_>>(), 0)
+                .unwrap_or_else(|err| panic!("{}", err));
         let vertex_buffer = Buffer::new(device, TRIANGLE.to_vec(), b::Usage::VERTEX, &limits);
         let memory = Memory::new(vertex_buffer, &memory_types);
-        let uniform_buffer = Buffer::new(device, vec![0.0, 0.0], b::Usage::UNIFORM, &limits);
-        let uniform_memory = Memory::new(uniform_buffer, &memory_types);
 
-        unsafe {
-            device.write_descriptor_sets(Some(pso::DescriptorSetWrite {
-                set: &descriptor_set.set,
-                binding: 0,
-                array_offset: 0,
-                descriptors: Some(pso::Descriptor::Buffer(
-                    &*uniform_memory.buffer.buf,
-                    b::SubRange::WHOLE,
-                )),
-            }));
-        }
+        let (swapchain, headless) = match surface {
+            Some(surface) => {
+                let swapchain = Swapchain::new(
+                    device,
+                    surface,
+                    adapter,
+                    config.size(),
+                    config.present_mode,
+                    config.swapchain_images,
+                );
+                (Some(ManuallyDrop::new(swapchain)), None)
+            }
+            None => {
+                let headless = Headless::new(device, adapter, (config.width, config.height));
+                (None, Some(ManuallyDrop::new(headless)))
+            }
+        };
+        let extent = Self::extent(&swapchain, &headless);
+        let mut camera = Camera::default();
+        camera.set_viewport(window::Extent2D {
+            width: extent.width,
+            height: extent.height,
+        });
+        let frames_in_flight = config.frames_in_flight;
+        let uniforms = (0..frames_in_flight)
+            .map(|_| {
+                let camera_buffer =
+                    Buffer::new(device, vec![camera.uniform()], b::Usage::UNIFORM, &limits);
+                let camera_memory = Memory::new(camera_buffer, &memory_types);
+                let object_buffer =
+                    Buffer::new(device, vec![math::IDENTITY], b::Usage::UNIFORM, &limits);
+                let object_memory = Memory::new(object_buffer, &memory_types);
+                let mut descriptor_set = DescriptorSet::new(&descriptor_layout);
+                descriptor_set.write_buffer(0, &camera_memory.buffer);
+                descriptor_set.write_buffer(1, &object_memory.buffer);
+                FrameUniforms {
+                    camera: camera_memory,
+                    object: object_memory,
+                    descriptor_set,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Headless frames are copied out of the backbuffer after the graph has run.
+        let (backbuffer_format, backbuffer_layouts) = match &swapchain {
+            Some(swapchain) => (swapchain.format, i::Layout::Undefined..i::Layout::Present),
+            None => (
+                Headless::<B>::FORMAT,
+                i::Layout::Undefined..i::Layout::TransferSrcOptimal,
+            ),
+        };
+        let graph_desc = GraphDesc {
+            backbuffer_format,
+            backbuffer_layouts,
+            scene_format: PostProcess::scene_format(adapter),
+            depth_format: RenderTarget::depth_format(adapter),
+            samples: RenderTarget::supported_samples(adapter, config.msaa, true),
+        };
+        log::info!(
+            "rendering the scene to {:?} with {} samples per pixel",
+            graph_desc.scene_format,
+            graph_desc.samples
+        );
+        // The pipelines are built against the passes of this graph and stay valid for
+        // the rebuilt ones, whose scene and present passes are compatible with these.
+        let (graph, passes) = graph_desc.build(device, adapter, &[], &[], extent);
 
-        let swapchain = Swapchain::new(device, surface, adapter, init_dims);
-        let render_pass = Self::create_render_pass(device, swapchain.format);
+        let post = PostProcess::new(
+            device,
+            adapter,
+            frames_in_flight,
+            graph_desc.scene_format,
+            graph.render_pass(passes.present),
+        );
+        let samples = graph_desc.samples;
+        let background = Background::new(
+            device,
+            adapter,
+            graph.render_pass(passes.scene),
+            samples,
+            config.background.clone(),
+        );
+        let depth = Some(pso::DepthTest {
+            fun: pso::Comparison::LessEqual,
+            write: true,
+        });
         let pipeline = Pipeline::new::<Vertex>(
             device,
-            "src/data/triangle.vert.spv",
-            "src/data/triangle.frag.spv",
-            &*render_pass,
-            Some(&*descriptor_set.set_layout),
+            vs,
+            fs,
+            graph.render_pass(passes.scene),
+            &[&uniforms[0].descriptor_set],
+            PipelineOptions {
+                vs_specialization: gfx_hal::spec_const_list![0 => 0.8f32],
+                depth,
+                samples,
+                ..PipelineOptions::default()
+            },
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        let mesh = config.mesh.as_ref().and_then(|path| {
+            let mesh = Mesh::load(device, adapter, path)
+                .map_err(|err| log::error!("Can't load {}: {}", path.display(), err))
+                .ok()?;
+            let vs = &shader::MESH_VERT;
+            let fs = &shader::MESH_FRAG;
+            let infos = [vs, fs]
+                .iter()
+                .map(|shader| shader.reflect())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|err| panic!("{}", err));
+            let layout =
+                DescriptorLayout::from_shaders(device, &infos.iter().collect::<Vec<_>>(), 0)
+                    .unwrap_or_else(|err| panic!("{}", err));
+            let objects = uniforms
+                .iter()
+                .map(|uniforms| {
+                    let object = MeshUniform::new(math::IDENTITY);
+                    let buffer = Buffer::new(device, vec![object], b::Usage::UNIFORM, &limits);
+                    let memory = Memory::new(buffer, &memory_types);
+                    let mut set = DescriptorSet::new(&layout);
+                    set.write_buffer(0, &uniforms.camera.buffer);
+                    set.write_buffer(1, &memory.buffer);
+                    (memory, set)
+                })
+                .collect::<Vec<_>>();
+            let pipeline = Pipeline::new::<MeshVertex>(
+                device,
+                vs,
+                fs,
+                graph.render_pass(passes.scene),
+                &[&objects[0].1],
+                PipelineOptions {
+                    blend: None,
+                    depth,
+                    samples,
+                    ..PipelineOptions::default()
+                },
+            )
+            .unwrap_or_else(|err| panic!("{}", err));
+            Some(SceneMesh {
+                mesh,
+                pipeline,
+                objects,
+                model: math::IDENTITY,
+            })
+        });
+        let particles = ParticleSystem::new(
+            device,
+            adapter,
+            frames_in_flight,
+            graph.render_pass(passes.scene),
+            samples,
         );
 
-        let mut command_pool = Self::create_command_pool(&device, family);
-        let frames_in_flight: usize = 1;
+        let mut command_pool = Self::create_command_pool(device, family);
 
-        let command_buffers = Self::allocate_command_buffer(&mut command_pool, 1);
-        let submission_complete_semaphores = Self::create_semaphores(&device, frames_in_flight);
-        let submission_complete_fences = Self::create_fences(&device, frames_in_flight);
+        let command_buffers = Self::allocate_command_buffer(&mut command_pool, frames_in_flight);
+        let submission_complete_semaphores = Self::create_semaphores(device, frames_in_flight);
+        let submission_complete_fences = Self::create_fences(device, frames_in_flight);
 
-        Renderer {
+        let mut renderer = Renderer {
             device,
+            adapter,
             submission_complete_semaphores,
             submission_complete_fences,
             frames_in_flight,
             command_pool: ManuallyDrop::new(command_pool),
-            descriptor_set: Some(ManuallyDrop::new(descriptor_set)),
+            uniforms,
             memory: ManuallyDrop::new(memory),
-            uniform_memory: ManuallyDrop::new(uniform_memory),
-            swapchain: ManuallyDrop::new(swapchain),
-            render_pass,
+            controller: CameraController::new(&camera),
+            camera,
+            swapchain,
+            headless,
+            graph: ManuallyDrop::new(graph),
+            graph_desc,
+            passes,
+            graph_key: (vec![], vec![]),
+            post: ManuallyDrop::new(post),
+            background: ManuallyDrop::new(background),
+            mesh,
             pipeline: ManuallyDrop::new(pipeline),
+            particles: ManuallyDrop::new(particles),
             command_buffers: Some(command_buffers),
             frame: 0,
-            resized,
-        }
+            swapchain_outdated: false,
+            blocked: Duration::default(),
+            time: Time::default(),
+            clear_color: config.clear_color,
+            model: math::IDENTITY,
+        };
+        renderer.rebuild_graph();
+        renderer
     }
 
     pub fn render(&mut self, queue: &mut B::CommandQueue) {
-        if self.resized.load(Ordering::Relaxed) {
-            self.swapchain.recreate();
-            self.resized.store(false, Ordering::Relaxed);
+        self.blocked = Duration::default();
+        if self.swapchain_outdated {
+            self.recreate_swapchain();
+            self.swapchain_outdated = false;
             return;
         }
-        let surface_image = unsafe {
-            match self.swapchain.surface.acquire_image(!0) {
-                Ok((image, _)) => image,
-                Err(_) => {
-                    self.swapchain.recreate();
-                    return;
+        if self.graph_key() != self.graph_key {
+            // The transient images and descriptor sets of frames in flight are replaced.
+            self.device.wait_idle().expect("Can't wait for device");
+            self.rebuild_graph();
+        }
+        self.background.upload(&mut self.command_pool, queue);
+        let wait_start = Instant::now();
+        let surface_image = match &mut self.swapchain {
+            Some(swapchain) => unsafe {
+                match swapchain.surface.acquire_image(!0) {
+                    Ok((image, _)) => Some(image),
+                    Err(_) => {
+                        self.recreate_swapchain();
+                        return;
+                    }
                 }
-            }
+            },
+            None => None,
         };
-
-        println!("{:?}", self.swapchain.dims);
-        let frame_buffer = unsafe {
-            self.device.create_framebuffer(
-                &self.render_pass,
-                iter::once(surface_image.borrow()),
-                i::Extent {
-                    width: self.swapchain.dims.width,
-                    height: self.swapchain.dims.height,
-                    depth: 1,
-                },
-            )
-        }
-        .expect("Could not create frame buffer");
+        self.blocked += wait_start.elapsed();
 
         let frame_idx = self.frame % self.frames_in_flight;
 
         unsafe {
             let fence = &self.submission_complete_fences[frame_idx];
+            let wait_start = Instant::now();
             self.device
                 .wait_for_fence(fence, !0)
                 .expect("Can't wait for fence");
+            self.blocked += wait_start.elapsed();
             self.device
                 .reset_fence(fence)
                 .expect("Can't wait for fence");
-            // if frame_idx == 0 {
-            self.command_pool.reset(false);
-            // }
+            self.command_buffers.as_mut().unwrap()[frame_idx].reset(false);
         }
 
-        self.uniform_memory.buffer.content[0] =
-            0.1 * (self.frame as f32 * std::f32::consts::PI / 60.0).cos();
-        self.uniform_memory.buffer.content[1] =
-            0.1 * (self.frame as f32 * std::f32::consts::PI / 60.0).sin();
-        self.uniform_memory.update_data(0);
-
-        let cmd_buffer = &mut self.command_buffers.as_mut().unwrap()[0];
+        let uniforms = &mut self.uniforms[frame_idx];
+        uniforms.camera.buffer.content[0] = self.camera.uniform();
+        uniforms.camera.update_data(0);
+        uniforms.object.buffer.content[0] = self.model;
+        uniforms.object.update_data(0);
+        if let Some(mesh) = &mut self.mesh {
+            let (object, _) = &mut mesh.objects[frame_idx];
+            object.buffer.content[0] = MeshUniform::new(mesh.model);
+            object.update_data(0);
+        }
+        self.particles
+            .update(frame_idx, self.time.delta, &self.camera.particle_view());
+        self.post.update(frame_idx, self.graph.extent());
+        let clear = clear_value(self.clear_color);
+        self.graph
+            .set_clear_value(self.passes.scene, self.passes.scene_color, clear);
+        self.graph
+            .set_clear_value(self.passes.present, self.passes.backbuffer, clear);
+
+        let cmd_buffer = &mut self.command_buffers.as_mut().unwrap()[frame_idx];
+        let backbuffer: &B::ImageView = match (&surface_image, &self.headless) {
+            (Some(image), _) => image.borrow(),
+            (None, Some(headless)) => headless.view(),
+            (None, None) => unreachable!(),
+        };
         unsafe {
             cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
-            cmd_buffer.set_viewports(0, &[self.swapchain.viewport.clone()]);
-            cmd_buffer.set_scissors(0, &[self.swapchain.viewport.rect]);
-            cmd_buffer.bind_graphics_pipeline(&self.pipeline.pipeline);
-            cmd_buffer.bind_vertex_buffers(
-                0,
-                iter::once((&*self.memory.buffer.buf, b::SubRange::WHOLE)),
+            let passes = &self.passes;
+            let post = &*self.post;
+            let background = &*self.background;
+            let mesh = self.mesh.as_ref();
+            let pipeline = &*self.pipeline;
+            let particles = &*self.particles;
+            let vertex_buffer = &*self.memory.buffer.buf;
+            let descriptor_set = &self.uniforms[frame_idx].descriptor_set;
+            let buffers = passes
+                .emitters
+                .iter()
+                .copied()
+                .zip(
+                    particles
+                        .particle_buffers(frame_idx)
+                        .into_iter()
+                        .map(|(buffer, _)| buffer),
+                )
+                .collect::<Vec<_>>();
+            self.graph.execute(
+                cmd_buffer,
+                frame_idx,
+                &[(passes.backbuffer, backbuffer)],
+                &buffers,
+                |pass, cmd_buffer| {
+                    if Some(pass) == passes.simulate {
+                        particles.record_compute(frame_idx, cmd_buffer);
+                    } else if pass == passes.scene {
+                        background.draw(cmd_buffer);
+                        if let Some(mesh) = mesh {
+                            let (_, set) = &mesh.objects[frame_idx];
+                            cmd_buffer.bind_graphics_pipeline(&mesh.pipeline.pipeline);
+                            cmd_buffer.bind_graphics_descriptor_sets(
+                                &mesh.pipeline.pipeline_layout,
+                                0,
+                                set.raw(),
+                                &[],
+                            );
+                            mesh.mesh.draw(cmd_buffer);
+                        }
+                        cmd_buffer.bind_graphics_pipeline(&pipeline.pipeline);
+                        cmd_buffer.bind_vertex_buffers(
+                            0,
+                            iter::once((vertex_buffer, b::SubRange::WHOLE)),
+                        );
+                        cmd_buffer.bind_graphics_descriptor_sets(
+                            &pipeline.pipeline_layout,
+                            0,
+                            descriptor_set.raw(),
+                            &[],
+                        );
+                        cmd_buffer.draw(0..3, 0..1);
+                        particles.draw(frame_idx, cmd_buffer);
+                    } else if pass == passes.present {
+                        post.blit(cmd_buffer);
+                    } else if let Some(&(kind, ..)) =
+                        passes.effects.iter().find(|&&(_, id, _)| id == pass)
+                    {
+                        post.record(frame_idx, kind, cmd_buffer);
+                    }
+                },
             );
-
-            assert!(self.descriptor_set.is_some());
-            if let Some(descriptor_set) = &self.descriptor_set {
-                cmd_buffer.bind_graphics_descriptor_sets(
-                    &self.pipeline.pipeline_layout,
-                    0,
-                    Some(&descriptor_set.set),
-                    &[],
-                );
+            if let Some(headless) = &self.headless {
+                headless.record_readback(cmd_buffer);
             }
-            cmd_buffer.begin_render_pass(
-                &self.render_pass,
-                &frame_buffer,
-                self.swapchain.viewport.rect,
-                &[command::ClearValue {
-                    color: command::ClearColor {
-                        float32: [0.8, 0.8, 0.8, 1.0],
-                    },
-                }],
-                command::SubpassContents::Inline,
-            );
-            cmd_buffer.draw(0..3, 0..1);
-            cmd_buffer.end_render_pass();
             cmd_buffer.finish();
 
+            // Only presenting waits for the semaphore.
+            let semaphore = &self.submission_complete_semaphores[frame_idx];
             let submission = Submission {
                 command_buffers: iter::once(&cmd_buffer),
                 wait_semaphores: None,
-                signal_semaphores: iter::once(&self.submission_complete_semaphores[frame_idx]),
+                signal_semaphores: self.swapchain.as_ref().map(|_| semaphore),
             };
 
             queue.submit(
@@ -211,51 +628,160 @@ where
                 Some(&self.submission_complete_fences[frame_idx]),
             );
 
-            let result = queue.present_surface(
-                &mut self.swapchain.surface,
-                surface_image,
-                Some(&self.submission_complete_semaphores[frame_idx]),
-            );
-
-            self.device.destroy_framebuffer(frame_buffer);
+            if let (Some(swapchain), Some(surface_image)) = (&mut self.swapchain, surface_image) {
+                let wait_start = Instant::now();
+                let result = queue.present_surface(
+                    swapchain.surface,
+                    surface_image,
+                    Some(&self.submission_complete_semaphores[frame_idx]),
+                );
+                self.blocked += wait_start.elapsed();
 
-            if result.is_err() {
-                self.swapchain.recreate();
+                if result.is_err() {
+                    self.recreate_swapchain();
+                }
             }
         }
 
         self.frame += 1;
     }
 
-    fn create_render_pass(device: &B::Device, format: f::Format) -> ManuallyDrop<B::RenderPass> {
-        let attachment = pass::Attachment {
-            format: Some(format),
-            samples: 1,
-            ops: pass::AttachmentOps::new(
-                pass::AttachmentLoadOp::Clear,
-                pass::AttachmentStoreOp::Store,
-            ),
-            stencil_ops: pass::AttachmentOps::DONT_CARE,
-            layouts: i::Layout::Undefined..i::Layout::Present,
-        };
+    /// Advances the scene to `time` and applies a frame of input. The swapchain follows
+    /// the window size on the next `render`.
+    pub fn update(&mut self, time: &Time, input: &InputState) {
+        self.time = *time;
+        if let (true, Some(swapchain)) = (input.resized, &mut self.swapchain) {
+            swapchain.dims = window::Extent2D {
+                width: input.size.width,
+                height: input.size.height,
+            };
+            self.swapchain_outdated = true;
+        }
+        // The camera keeps moving while the clock is paused.
+        self.controller
+            .update(time.real_delta, input, &mut self.camera);
+    }
+
+    /// What the application controls about the next `render`.
+    pub fn frame(&mut self) -> Frame<'_, 'a, B> {
+        Frame::new(self)
+    }
 
-        let subpass = pass::SubpassDesc {
-            colors: &[(0, i::Layout::ColorAttachmentOptimal)],
-            depth_stencil: None,
-            inputs: &[],
-            resolves: &[],
-            preserves: &[],
+    /// How long the last `render` was blocked on the GPU and the presentation engine,
+    /// waiting for a swapchain image, an earlier frame or vsync. GPU-bound frames show up
+    /// here, as the time the GPU itself took isn't measured, see `stats::Summary`.
+    pub fn blocked(&self) -> Duration {
+        self.blocked
+    }
+
+    /// The present mode asked for, `None` without a surface. The swapchain may fall back
+    /// to another one.
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        self.swapchain
+            .as_ref()
+            .map(|swapchain| swapchain.present_mode)
+    }
+
+    /// Recreates the swapchain with `mode` before the next frame.
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        if let Some(swapchain) = &mut self.swapchain {
+            swapchain.present_mode = mode;
+            self.swapchain_outdated = true;
+        }
+    }
+
+    /// The size of the swapchain images, or of the offscreen image.
+    pub fn dims(&self) -> window::Extent2D {
+        let extent = Self::extent(&self.swapchain, &self.headless);
+        window::Extent2D {
+            width: extent.width,
+            height: extent.height,
+        }
+    }
+
+    /// Saves the last rendered frame to `path` as a PNG. Only renderers without a surface
+    /// keep their frames.
+    pub fn save_frame(&mut self, path: &Path) -> io::Result<()> {
+        let headless = self
+            .headless
+            .as_mut()
+            .ok_or_else(|| io::Error::other("Only headless frames can be saved"))?;
+        self.device.wait_idle().expect("Can't wait for device");
+        headless.save(path)
+    }
+
+    fn recreate_swapchain(&mut self) {
+        let swapchain = match &mut self.swapchain {
+            Some(swapchain) => swapchain,
+            None => return,
         };
+        // The old swapchain's images may still be in use by frames in flight.
+        self.device.wait_idle().expect("Can't wait for device");
+        swapchain.recreate();
+        let dims = swapchain.dims;
+        let extent = Self::extent(&self.swapchain, &self.headless);
+        self.graph.resize(extent);
+        self.bind_post_inputs();
+        self.camera.set_viewport(dims);
+    }
 
-        ManuallyDrop::new(
-            unsafe { device.create_render_pass(&[attachment], &[subpass], &[]) }
-                .expect("Can't create render pass"),
-        )
+    /// The enabled effects and the emitters, which the graph is built for.
+    fn graph_key(&self) -> (Vec<EffectKind>, Vec<Simulation>) {
+        let emitters = self
+            .particles
+            .particle_buffers(0)
+            .into_iter()
+            .map(|(_, simulation)| simulation)
+            .collect();
+        (self.post.enabled(), emitters)
+    }
+
+    /// Builds the graph for the current `graph_key`. The GPU must be done with the old one.
+    fn rebuild_graph(&mut self) {
+        let key = self.graph_key();
+        let extent = Self::extent(&self.swapchain, &self.headless);
+        let (graph, passes) =
+            self.graph_desc
+                .build(self.device, self.adapter, &key.0, &key.1, extent);
+        *self.graph = graph;
+        self.passes = passes;
+        self.graph_key = key;
+        self.bind_post_inputs();
+    }
+
+    /// Points the effects and the blit at the graph's current images.
+    fn bind_post_inputs(&mut self) {
+        let graph = &self.graph;
+        let inputs = self
+            .passes
+            .effects
+            .iter()
+            .map(|&(kind, _, input)| (kind, graph.view(input)))
+            .collect::<Vec<_>>();
+        self.post
+            .set_inputs(&inputs, graph.view(self.passes.output));
+    }
+
+    fn extent(
+        swapchain: &Option<ManuallyDrop<Swapchain<'a, B>>>,
+        headless: &Option<ManuallyDrop<Headless<'a, B>>>,
+    ) -> i::Extent {
+        match (swapchain, headless) {
+            (Some(swapchain), _) => i::Extent {
+                width: swapchain.viewport.rect.w as u32,
+                height: swapchain.viewport.rect.h as u32,
+                depth: 1,
+            },
+            (None, Some(headless)) => headless.dims(),
+            (None, None) => unreachable!(),
+        }
     }
 
     fn create_command_pool(device: &B::Device, family: QueueFamilyId) -> B::CommandPool {
-        unsafe { device.create_command_pool(family, pool::CommandPoolCreateFlags::empty()) }
-            .expect("Can't create command pooll")
+        unsafe {
+            device.create_command_pool(family, pool::CommandPoolCreateFlags::RESET_INDIVIDUAL)
+        }
+        .expect("Can't create command pooll")
     }
 
     fn allocate_command_buffer(
@@ -290,16 +816,20 @@ where
     }
 }
 
+fn clear_value(color: [f32; 4]) -> command::ClearValue {
+    command::ClearValue {
+        color: command::ClearColor { float32: color },
+    }
+}
+
 impl<'a, B: Backend> Drop for Renderer<'a, B> {
     fn drop(&mut self) {
         let device = &self.device;
         device.wait_idle().unwrap();
         unsafe {
-            if let Some(mut descriptor_set) = self.descriptor_set.take() {
-                ManuallyDrop::drop(&mut descriptor_set);
-            }
+            self.mesh = None;
+            self.uniforms.clear();
             ManuallyDrop::drop(&mut self.memory);
-            ManuallyDrop::drop(&mut self.uniform_memory);
 
             for s in self.submission_complete_semaphores.drain(..) {
                 device.destroy_semaphore(s);
@@ -315,8 +845,16 @@ impl<'a, B: Backend> Drop for Renderer<'a, B> {
             }
             device.destroy_command_pool(ManuallyDrop::into_inner(ptr::read(&self.command_pool)));
 
-            device.destroy_render_pass(ManuallyDrop::into_inner(ptr::read(&self.render_pass)));
-            ManuallyDrop::drop(&mut self.swapchain);
+            ManuallyDrop::drop(&mut self.particles);
+            ManuallyDrop::drop(&mut self.post);
+            ManuallyDrop::drop(&mut self.background);
+            ManuallyDrop::drop(&mut self.graph);
+            if let Some(mut swapchain) = self.swapchain.take() {
+                ManuallyDrop::drop(&mut swapchain);
+            }
+            if let Some(mut headless) = self.headless.take() {
+                ManuallyDrop::drop(&mut headless);
+            }
             ManuallyDrop::drop(&mut self.pipeline);
         }
     }
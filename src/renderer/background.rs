@@ -0,0 +1,149 @@
+use gfx_hal::{adapter::Adapter, buffer as b, image as i, prelude::*, Backend};
+use std::iter;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::buffer::Buffer;
+use super::descriptor_set::{DescriptorLayout, DescriptorSet};
+use super::memory::Memory;
+use super::pipeline::{Pipeline, PipelineOptions};
+use super::shader;
+use super::texture::{Texture, TextureOptions};
+use super::vertex::{TexturedVertex, QUAD};
+
+/// Size of the checkerboard drawn without a background image, and of its squares.
+const CHECKERBOARD_SIZE: u32 = 256;
+const CHECKER_SIZE: u32 = 32;
+
+/// A textured quad drawn behind the rest of the scene.
+///
+/// The image is uploaded by the first `upload`, which needs a queue, so it can't happen
+/// in `new`.
+pub struct Background<'a, B: Backend> {
+    device: &'a B::Device,
+    adapter: &'a Adapter<B>,
+    path: Option<PathBuf>,
+    layout: Rc<DescriptorLayout<'a, B>>,
+    pipeline: Pipeline<'a, B>,
+    quad: Memory<'a, B, TexturedVertex>,
+    texture: Option<(Texture<'a, B>, DescriptorSet<'a, B>)>,
+}
+
+impl<'a, B: Backend> Background<'a, B> {
+    /// Prepares to draw the image at `path`, or a checkerboard without one, in a render
+    /// pass compatible with `render_pass`.
+    pub fn new(
+        device: &'a B::Device,
+        adapter: &'a Adapter<B>,
+        render_pass: &B::RenderPass,
+        samples: i::NumSamples,
+        path: Option<PathBuf>,
+    ) -> Self {
+        let vs = &shader::TEXTURED_VERT;
+        let fs = &shader::TEXTURED_FRAG;
+        let infos = [vs, fs]
+            .iter()
+            .map(|shader| shader.reflect())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let layout = DescriptorLayout::from_shaders(device, &infos.iter().collect::<Vec<_>>(), 0)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let pipeline = Pipeline::new::<TexturedVertex>(
+            device,
+            vs,
+            fs,
+            render_pass,
+            &[&DescriptorSet::new(&layout)],
+            PipelineOptions {
+                samples,
+                ..PipelineOptions::default()
+            },
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let limits = adapter.physical_device.limits();
+        let quad = Buffer::new(device, QUAD.to_vec(), b::Usage::VERTEX, &limits);
+        let quad = Memory::new(quad, &memory_types);
+
+        Background {
+            device,
+            adapter,
+            path,
+            layout,
+            pipeline,
+            quad,
+            texture: None,
+        }
+    }
+
+    /// Uploads the image unless that already happened. An image that can't be loaded is
+    /// logged and replaced by the checkerboard.
+    pub fn upload(&mut self, command_pool: &mut B::CommandPool, queue: &mut B::CommandQueue) {
+        if self.texture.is_some() {
+            return;
+        }
+        let options = TextureOptions {
+            anisotropy: Some(16),
+            ..TextureOptions::default()
+        };
+        let loaded = self.path.as_ref().and_then(|path| {
+            Texture::load(
+                self.device,
+                self.adapter,
+                command_pool,
+                queue,
+                path,
+                options.clone(),
+            )
+            .map_err(|err| log::error!("Can't load {}: {}", path.display(), err))
+            .ok()
+        });
+        let texture = loaded.unwrap_or_else(|| {
+            Texture::from_rgba(
+                self.device,
+                self.adapter,
+                command_pool,
+                queue,
+                &checkerboard(),
+                options,
+            )
+        });
+        log::info!(
+            "background is {}x{} {:?} with {} mip levels",
+            texture.dims.width,
+            texture.dims.height,
+            texture.format,
+            texture.mip_levels
+        );
+
+        let mut set = DescriptorSet::new(&self.layout);
+        set.write_texture(0, &texture);
+        set.write_texture(1, &texture);
+        self.texture = Some((texture, set));
+    }
+
+    /// Draws the quad once `upload` has happened. Must be recorded inside a render pass
+    /// compatible with the one given to `new`, with the viewport already set.
+    pub unsafe fn draw(&self, cmd_buffer: &mut B::CommandBuffer) {
+        let set = match &self.texture {
+            Some((_, set)) => set,
+            None => return,
+        };
+        cmd_buffer.bind_graphics_pipeline(&self.pipeline.pipeline);
+        cmd_buffer.bind_vertex_buffers(0, iter::once((&*self.quad.buffer.buf, b::SubRange::WHOLE)));
+        cmd_buffer.bind_graphics_descriptor_sets(&self.pipeline.pipeline_layout, 0, set.raw(), &[]);
+        cmd_buffer.draw(0..QUAD.len() as u32, 0..1);
+    }
+}
+
+/// Dark grey squares, to show the filtering of a texture.
+fn checkerboard() -> image::RgbaImage {
+    image::RgbaImage::from_fn(CHECKERBOARD_SIZE, CHECKERBOARD_SIZE, |x, y| {
+        if (x / CHECKER_SIZE + y / CHECKER_SIZE) & 1 == 0 {
+            image::Rgba([48, 48, 56, 255])
+        } else {
+            image::Rgba([24, 24, 30, 255])
+        }
+    })
+}
@@ -1,5 +1,7 @@
-use gfx_hal::{buffer, prelude::*, Backend, Limits};
+use gfx_hal::{buffer, memory as m, prelude::*, pso, Backend, Limits};
+use std::iter;
 use std::mem::{self, ManuallyDrop};
+use std::ops::Range;
 use std::ptr;
 
 pub struct Buffer<'a, B: Backend, T> {
@@ -22,8 +24,7 @@ impl<'a, B: Backend, T> Buffer<'a, B, T> {
         let buffer_len = content.len() as u64 * buffer_stride;
 
         assert_ne!(buffer_len, 0);
-        let memory_size = ((buffer_len + non_coherent_alignment - 1) / non_coherent_alignment)
-            * non_coherent_alignment;
+        let memory_size = buffer_len.div_ceil(non_coherent_alignment) * non_coherent_alignment;
 
         Buffer {
             device,
@@ -39,6 +40,30 @@ impl<'a, B: Backend, T> Buffer<'a, B, T> {
         let buffer_stride = mem::size_of::<T>();
         self.content.len() as u64 * buffer_stride as u64
     }
+
+    /// Makes `accesses.start` in `stages.start` visible to `accesses.end` in `stages.end`.
+    pub unsafe fn barrier(
+        &self,
+        cmd_buffer: &mut B::CommandBuffer,
+        stages: Range<pso::PipelineStage>,
+        accesses: Range<buffer::Access>,
+    ) {
+        cmd_buffer.pipeline_barrier(
+            stages,
+            m::Dependencies::empty(),
+            iter::once(m::Barrier::whole_buffer(&*self.buf, accesses)),
+        );
+    }
+
+    /// Orders vertex reads of the previous frame before compute shaders write this buffer again.
+    pub unsafe fn vertex_to_compute_barrier(&self, cmd_buffer: &mut B::CommandBuffer) {
+        self.barrier(
+            cmd_buffer,
+            pso::PipelineStage::VERTEX_INPUT..pso::PipelineStage::COMPUTE_SHADER,
+            buffer::Access::VERTEX_BUFFER_READ
+                ..buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE,
+        );
+    }
 }
 
 impl<'a, B: Backend, T> Drop for Buffer<'a, B, T> {
@@ -0,0 +1,167 @@
+use gfx_hal::window::Extent2D;
+
+use super::math::{self, Mat4, Vec3};
+use super::particles::ParticleView;
+
+mod controller;
+
+pub use controller::CameraController;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// Shows `height` world units vertically, regardless of distance.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/// A camera at `position` looking at `target`. The aspect ratio follows the swapchain
+/// through `set_viewport`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    /// Width over height of the viewport.
+    pub aspect: f32,
+}
+
+/// `Camera` block of `triangle.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct CameraUniform {
+    pub view: Mat4,
+    pub proj: Mat4,
+}
+
+impl Default for Camera {
+    /// A 45 degree perspective camera on the +Z axis looking at the origin.
+    fn default() -> Self {
+        Camera {
+            position: [0.0, 0.0, 1.5],
+            target: [0.0; 3],
+            up: [0.0, 1.0, 0.0],
+            projection: Projection::Perspective {
+                fov_y: 45f32.to_radians(),
+                near: 0.1,
+                far: 100.0,
+            },
+            aspect: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        self.position = position;
+        self.target = target;
+    }
+
+    /// Switches between perspective and orthographic, keeping the size of things at the
+    /// target.
+    pub fn toggle_projection(&mut self) {
+        let distance = math::length(math::sub(self.target, self.position)).max(1e-3);
+        self.projection = match self.projection {
+            Projection::Perspective { fov_y, near, far } => Projection::Orthographic {
+                height: 2.0 * distance * (fov_y / 2.0).tan(),
+                near,
+                far,
+            },
+            Projection::Orthographic { height, near, far } => Projection::Perspective {
+                fov_y: 2.0 * (height / (2.0 * distance)).atan(),
+                near,
+                far,
+            },
+        };
+    }
+
+    /// Matches the aspect ratio to a viewport or swapchain of `dims`.
+    pub fn set_viewport(&mut self, dims: Extent2D) {
+        self.aspect = dims.width.max(1) as f32 / dims.height.max(1) as f32;
+    }
+
+    pub fn view(&self) -> Mat4 {
+        math::look_at(self.position, self.target, self.up)
+    }
+
+    pub fn proj(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                math::perspective(fov_y, self.aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                math::orthographic(height, self.aspect, near, far)
+            }
+        }
+    }
+
+    pub fn view_proj(&self) -> Mat4 {
+        math::mul(self.proj(), self.view())
+    }
+
+    /// The direction the camera looks in.
+    pub fn forward(&self) -> Vec3 {
+        math::normalize(math::sub(self.target, self.position))
+    }
+
+    /// The camera's X axis in world space.
+    pub fn right(&self) -> Vec3 {
+        math::normalize(math::cross(self.forward(), self.up))
+    }
+
+    /// The camera's Y axis in world space, `up` made perpendicular to `forward`.
+    pub fn up_vector(&self) -> Vec3 {
+        math::cross(self.right(), self.forward())
+    }
+
+    pub fn uniform(&self) -> CameraUniform {
+        CameraUniform {
+            view: self.view(),
+            proj: self.proj(),
+        }
+    }
+
+    /// Billboards facing this camera.
+    pub fn particle_view(&self) -> ParticleView {
+        ParticleView {
+            view_proj: self.view_proj(),
+            right: self.right(),
+            up: self.up_vector(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_projection_keeps_the_size_at_the_target() {
+        let mut camera = Camera::default();
+        camera.look_at([0.0, 0.0, 2.0], [0.0; 3]);
+        let edge = [0.0, 0.5, 0.0];
+        let project = |camera: &Camera| {
+            let view = math::transform_point(camera.view(), edge);
+            let clip = math::transform_point(camera.proj(), view);
+            // The perspective divide, by the distance in front of the camera.
+            match camera.projection {
+                Projection::Perspective { .. } => clip[1] / -view[2],
+                Projection::Orthographic { .. } => clip[1],
+            }
+        };
+        let perspective = project(&camera);
+
+        camera.toggle_projection();
+        assert!(matches!(camera.projection, Projection::Orthographic { .. }));
+        assert!((project(&camera) - perspective).abs() < 1e-5);
+
+        camera.toggle_projection();
+        match camera.projection {
+            Projection::Perspective { fov_y, .. } => {
+                assert!((fov_y - 45f32.to_radians()).abs() < 1e-5)
+            }
+            _ => panic!("Expected a perspective projection"),
+        }
+    }
+}
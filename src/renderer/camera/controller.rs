@@ -0,0 +1,183 @@
+use std::f32::consts::FRAC_PI_2;
+
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use super::super::math::{self, Vec3};
+use super::{Camera, Projection};
+use crate::input::InputState;
+
+/// Keeps the camera from flipping over at the poles.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerKind {
+    /// Left drag rotates around the target, middle drag pans it and scrolling zooms.
+    Orbit,
+    /// WASD moves, Q and E go down and up, left drag looks around and shift speeds up.
+    Fly,
+}
+
+/// Drives a `Camera` from the input of each frame. Tab switches between the two kinds
+/// and C between a perspective and an orthographic projection.
+///
+/// Both kinds keep the camera's heading as a yaw around Y and a pitch above the XZ
+/// plane, measured from the target towards the camera. The heading carries over when
+/// switching.
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    pub kind: ControllerKind,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    /// Fraction of the target distance panned per pixel dragged.
+    pub pan_speed: f32,
+    /// Fraction of the target distance zoomed per scrolled line.
+    pub zoom_speed: f32,
+    /// World units per second flown, doubled while shift is held.
+    pub fly_speed: f32,
+    yaw: f32,
+    pitch: f32,
+    target: Vec3,
+    position: Vec3,
+    distance: f32,
+}
+
+impl CameraController {
+    /// An orbit controller starting from `camera`'s current position and target.
+    pub fn new(camera: &Camera) -> Self {
+        let mut controller = CameraController {
+            kind: ControllerKind::Orbit,
+            rotate_speed: 0.005,
+            pan_speed: 0.0015,
+            zoom_speed: 0.1,
+            fly_speed: 1.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            target: camera.target,
+            position: camera.position,
+            distance: 1.0,
+        };
+        controller.sync(camera);
+        controller
+    }
+
+    /// Switches to `kind` without moving `camera`.
+    pub fn set_kind(&mut self, kind: ControllerKind, camera: &Camera) {
+        if kind == self.kind {
+            return;
+        }
+        self.sync(camera);
+        if kind == ControllerKind::Orbit {
+            // Orbit around the point as far ahead as the last orbit target was.
+            self.target = math::add(
+                camera.position,
+                math::scale(camera.forward(), self.distance),
+            );
+        }
+        self.kind = kind;
+    }
+
+    /// Continues from wherever `camera` was moved to.
+    pub fn reset(&mut self, camera: &Camera) {
+        self.sync(camera);
+    }
+
+    /// Moves `camera` by a frame of `input` that took `dt` seconds.
+    pub fn update(&mut self, dt: f32, input: &InputState, camera: &mut Camera) {
+        if input.key_pressed(VirtualKeyCode::Tab) {
+            let kind = match self.kind {
+                ControllerKind::Orbit => ControllerKind::Fly,
+                ControllerKind::Fly => ControllerKind::Orbit,
+            };
+            self.set_kind(kind, camera);
+        }
+        if input.key_pressed(VirtualKeyCode::C) {
+            camera.toggle_projection();
+        }
+
+        let dragging = |button| input.button_held(button) && input.cursor.is_some();
+        let [dx, dy] = input.cursor_delta;
+        if dragging(MouseButton::Left) {
+            self.yaw -= dx * self.rotate_speed;
+            self.pitch = (self.pitch + dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        match self.kind {
+            ControllerKind::Orbit => {
+                let [dx, dy] = if dragging(MouseButton::Middle) && !dragging(MouseButton::Left) {
+                    input.cursor_delta
+                } else {
+                    [0.0; 2]
+                };
+                let pan = math::add(
+                    math::scale(camera.right(), -dx),
+                    math::scale(camera.up_vector(), dy),
+                );
+                self.target = math::add(
+                    self.target,
+                    math::scale(pan, self.pan_speed * self.distance),
+                );
+                let distance =
+                    (self.distance * (1.0 - self.zoom_speed).powf(input.scroll[1])).max(0.01);
+                // Moving closer doesn't make things bigger without perspective.
+                if let Projection::Orthographic { height, .. } = &mut camera.projection {
+                    *height *= distance / self.distance;
+                }
+                self.distance = distance;
+                self.position =
+                    math::add(self.target, math::scale(self.direction(), self.distance));
+            }
+            ControllerKind::Fly => {
+                let forward = math::scale(self.direction(), -1.0);
+                let right = math::normalize(math::cross(forward, camera.up));
+                let axis = |positive, negative| {
+                    input.key_held(positive) as i32 as f32 - input.key_held(negative) as i32 as f32
+                };
+                let movement = math::add(
+                    math::add(
+                        math::scale(forward, axis(VirtualKeyCode::W, VirtualKeyCode::S)),
+                        math::scale(right, axis(VirtualKeyCode::D, VirtualKeyCode::A)),
+                    ),
+                    math::scale(camera.up, axis(VirtualKeyCode::E, VirtualKeyCode::Q)),
+                );
+                let boost = if input.key_held(VirtualKeyCode::LShift)
+                    || input.key_held(VirtualKeyCode::RShift)
+                {
+                    2.0
+                } else {
+                    1.0
+                };
+                if math::length(movement) > 0.0 {
+                    self.position = math::add(
+                        self.position,
+                        math::scale(math::normalize(movement), self.fly_speed * boost * dt),
+                    );
+                }
+                self.target = math::add(self.position, forward);
+            }
+        }
+
+        camera.position = self.position;
+        camera.target = self.target;
+    }
+
+    /// Takes the heading and position from `camera`.
+    fn sync(&mut self, camera: &Camera) {
+        let offset = math::sub(camera.position, camera.target);
+        let length = math::length(offset);
+        let [x, y, z] = math::normalize(offset);
+        self.yaw = x.atan2(z);
+        self.pitch = y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+        self.position = camera.position;
+        self.target = camera.target;
+        if self.kind == ControllerKind::Orbit && length > 0.0 {
+            self.distance = length;
+        }
+    }
+
+    /// From the target towards the camera.
+    fn direction(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw]
+    }
+}
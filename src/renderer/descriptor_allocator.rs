@@ -0,0 +1,259 @@
+use gfx_hal::{prelude::*, pso, Backend};
+use std::iter;
+
+const INITIAL_SETS_PER_POOL: usize = 16;
+const MAX_SETS_PER_POOL: usize = 1024;
+
+/// A descriptor set together with the pool it was allocated from.
+pub struct Allocation<B: Backend> {
+    pub set: B::DescriptorSet,
+    pool: usize,
+}
+
+struct Pool<B: Backend> {
+    raw: B::DescriptorPool,
+    usage: Usage,
+}
+
+/// How many sets a pool has room for and has handed out, kept apart from the pool so the
+/// allocation rules don't need a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Usage {
+    capacity: usize,
+    allocated: usize,
+    /// Set when the pool ran out of descriptors before reaching `capacity`, e.g.
+    /// because it is fragmented.
+    exhausted: bool,
+}
+
+impl Usage {
+    fn new(capacity: usize) -> Self {
+        Usage {
+            capacity,
+            allocated: 0,
+            exhausted: false,
+        }
+    }
+
+    fn has_room(&self) -> bool {
+        !self.exhausted && self.allocated < self.capacity
+    }
+
+    fn free(&mut self) {
+        self.allocated -= 1;
+        self.exhausted = false;
+    }
+
+    fn reset(&mut self) {
+        self.allocated = 0;
+        self.exhausted = false;
+    }
+}
+
+/// The capacity of the pool added after `pools`, twice the last one up to
+/// `MAX_SETS_PER_POOL`.
+fn next_capacity(pools: &[Usage]) -> usize {
+    pools.last().map_or(INITIAL_SETS_PER_POOL, |usage| {
+        (usage.capacity * 2).min(MAX_SETS_PER_POOL)
+    })
+}
+
+/// The descriptors a pool needs per set of a layout with `bindings`, one range per
+/// descriptor type.
+fn ranges(bindings: &[pso::DescriptorSetLayoutBinding]) -> Vec<pso::DescriptorRangeDesc> {
+    let mut ranges: Vec<pso::DescriptorRangeDesc> = vec![];
+    for binding in bindings {
+        match ranges.iter_mut().find(|range| range.ty == binding.ty) {
+            Some(range) => range.count += binding.count,
+            None => ranges.push(pso::DescriptorRangeDesc {
+                ty: binding.ty,
+                count: binding.count,
+            }),
+        }
+    }
+    ranges
+}
+
+/// Allocates descriptor sets of a single layout, adding a larger pool whenever the
+/// existing ones run out.
+///
+/// Sets can be returned one by one with `free`, or all at once with `reset`, e.g. at the
+/// start of a frame for sets that only live for that frame.
+///
+/// A layout without bindings gets no pools, since a pool needs at least one descriptor
+/// range, and no sets either.
+pub struct DescriptorAllocator<'a, B: Backend> {
+    device: &'a B::Device,
+    ranges: Vec<pso::DescriptorRangeDesc>,
+    pools: Vec<Pool<B>>,
+}
+
+impl<'a, B: Backend> DescriptorAllocator<'a, B> {
+    pub fn new(device: &'a B::Device, bindings: &[pso::DescriptorSetLayoutBinding]) -> Self {
+        DescriptorAllocator {
+            device,
+            ranges: ranges(bindings),
+            pools: vec![],
+        }
+    }
+
+    /// Allocates a set, or returns `None` if the layout has no bindings.
+    pub fn allocate(&mut self, layout: &B::DescriptorSetLayout) -> Option<Allocation<B>> {
+        if self.ranges.is_empty() {
+            return None;
+        }
+        for (index, pool) in self.pools.iter_mut().enumerate() {
+            if !pool.usage.has_room() {
+                continue;
+            }
+            match unsafe { pool.raw.allocate_set(layout) } {
+                Ok(set) => {
+                    pool.usage.allocated += 1;
+                    return Some(Allocation { set, pool: index });
+                }
+                Err(pso::AllocationError::OutOfPoolMemory)
+                | Err(pso::AllocationError::FragmentedPool) => pool.usage.exhausted = true,
+                Err(err) => panic!("Can't allocate descriptor set: {:?}", err),
+            }
+        }
+
+        let usages = self.pools.iter().map(|pool| pool.usage).collect::<Vec<_>>();
+        let mut pool = self.create_pool(next_capacity(&usages));
+        let set = unsafe { pool.raw.allocate_set(layout) }
+            .expect("Can't allocate descriptor set from a new pool");
+        pool.usage.allocated += 1;
+        self.pools.push(pool);
+        Some(Allocation {
+            set,
+            pool: self.pools.len() - 1,
+        })
+    }
+
+    /// Returns a set. Once every set is back the pools are reset, which also undoes any
+    /// fragmentation.
+    pub fn free(&mut self, allocation: Allocation<B>) {
+        let pool = &mut self.pools[allocation.pool];
+        unsafe { pool.raw.free_sets(iter::once(allocation.set)) };
+        pool.usage.free();
+        if self.pools.iter().all(|pool| pool.usage.allocated == 0) {
+            self.reset();
+        }
+    }
+
+    /// Frees every set allocated so far. The sets must no longer be in use by the GPU,
+    /// nor be freed again.
+    pub fn reset(&mut self) {
+        for pool in &mut self.pools {
+            unsafe { pool.raw.reset() };
+            pool.usage.reset();
+        }
+    }
+
+    fn create_pool(&self, capacity: usize) -> Pool<B> {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|range| pso::DescriptorRangeDesc {
+                ty: range.ty,
+                count: range.count * capacity,
+            })
+            .collect::<Vec<_>>();
+        let raw = unsafe {
+            self.device.create_descriptor_pool(
+                capacity,
+                &ranges,
+                pso::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            )
+        }
+        .expect("Can't create descriptor pool");
+
+        Pool {
+            raw,
+            usage: Usage::new(capacity),
+        }
+    }
+}
+
+impl<'a, B: Backend> Drop for DescriptorAllocator<'a, B> {
+    fn drop(&mut self) {
+        for pool in self.pools.drain(..) {
+            unsafe { self.device.destroy_descriptor_pool(pool.raw) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(
+        binding: u32,
+        ty: pso::DescriptorType,
+        count: usize,
+    ) -> pso::DescriptorSetLayoutBinding {
+        pso::DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stage_flags: pso::ShaderStageFlags::FRAGMENT,
+            immutable_samplers: false,
+        }
+    }
+
+    #[test]
+    fn ranges_merge_bindings_of_a_type() {
+        let image = pso::DescriptorType::Image {
+            ty: pso::ImageDescriptorType::Sampled {
+                with_sampler: false,
+            },
+        };
+        let bindings = [
+            binding(0, image, 1),
+            binding(1, pso::DescriptorType::Sampler, 1),
+            binding(2, image, 4),
+        ];
+        let ranges = ranges(&bindings);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].ty, ranges[0].count), (image, 5));
+        assert_eq!(ranges[1].count, 1);
+        assert!(super::ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn full_pools_grow_a_larger_one() {
+        let mut usages = vec![];
+        assert_eq!(next_capacity(&usages), INITIAL_SETS_PER_POOL);
+        let mut first = Usage::new(INITIAL_SETS_PER_POOL);
+        first.allocated = first.capacity;
+        assert!(!first.has_room());
+        usages.push(first);
+        assert_eq!(next_capacity(&usages), 2 * INITIAL_SETS_PER_POOL);
+
+        usages.push(Usage::new(MAX_SETS_PER_POOL));
+        assert_eq!(next_capacity(&usages), MAX_SETS_PER_POOL);
+    }
+
+    #[test]
+    fn freeing_makes_room_again() {
+        let mut usage = Usage::new(2);
+        usage.allocated = 2;
+        usage.free();
+        assert!(usage.has_room());
+
+        // A fragmented pool is skipped until a set comes back.
+        usage.exhausted = true;
+        assert!(!usage.has_room());
+        usage.free();
+        assert!(usage.has_room());
+        assert_eq!(usage.allocated, 0);
+    }
+
+    #[test]
+    fn reset_empties_the_pool() {
+        let mut usage = Usage::new(4);
+        usage.allocated = 3;
+        usage.exhausted = true;
+        usage.reset();
+        assert_eq!(usage, Usage::new(4));
+    }
+}
@@ -1,90 +1,146 @@
-use gfx_hal::{prelude::*, pso, Backend};
+use gfx_hal::{buffer as b, image as i, prelude::*, pso, Backend};
+use std::cell::RefCell;
 use std::mem::ManuallyDrop;
 use std::ptr;
+use std::rc::Rc;
 
-pub struct DescriptorSet<'a, B: Backend> {
+use super::buffer::Buffer;
+use super::descriptor_allocator::{Allocation, DescriptorAllocator};
+use super::reflect::{self, ShaderError, ShaderInfo};
+use super::texture::Texture;
+
+/// A descriptor set layout together with the allocator every set of that layout comes
+/// from, so sets sharing a layout also share its pools.
+pub struct DescriptorLayout<'a, B: Backend> {
     device: &'a B::Device,
-    pub set_layout: ManuallyDrop<B::DescriptorSetLayout>,
-    pool: ManuallyDrop<B::DescriptorPool>,
-    pub set: B::DescriptorSet,
+    pub raw: ManuallyDrop<B::DescriptorSetLayout>,
+    pub bindings: Vec<pso::DescriptorSetLayoutBinding>,
+    allocator: RefCell<DescriptorAllocator<'a, B>>,
 }
 
-impl<'a, B: Backend> DescriptorSet<'a, B> {
-    pub fn new(device: &'a B::Device) -> Self {
-        let set_layout = Self::create_descriptor_set_layout(device);
-        let mut pool = Self::create_descriptor_pool(device);
-        let set = Self::create_descriptor_set(&mut pool, &set_layout);
+impl<'a, B: Backend> DescriptorLayout<'a, B> {
+    /// Creates a layout covering every binding `shaders` declare in `set`.
+    pub fn from_shaders(
+        device: &'a B::Device,
+        shaders: &[&ShaderInfo],
+        set: u32,
+    ) -> Result<Rc<Self>, ShaderError> {
+        Ok(Self::new(device, reflect::layout_bindings(shaders, set)?))
+    }
 
-        DescriptorSet {
-            set_layout,
-            pool,
-            set,
+    pub fn new(device: &'a B::Device, bindings: Vec<pso::DescriptorSetLayoutBinding>) -> Rc<Self> {
+        let raw = unsafe { device.create_descriptor_set_layout(&bindings, &[]) }
+            .expect("Can't create descriptor set layout");
+        let allocator = DescriptorAllocator::new(device, &bindings);
+        Rc::new(DescriptorLayout {
             device,
+            raw: ManuallyDrop::new(raw),
+            bindings,
+            allocator: RefCell::new(allocator),
+        })
+    }
+}
+
+impl<'a, B: Backend> Drop for DescriptorLayout<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_descriptor_set_layout(ManuallyDrop::into_inner(ptr::read(&self.raw)));
         }
     }
+}
 
-    pub(super) fn create_descriptor_set_layout(
-        device: &B::Device,
-    ) -> ManuallyDrop<B::DescriptorSetLayout> {
-        ManuallyDrop::new(
-            unsafe {
-                device.create_descriptor_set_layout(
-                    &[pso::DescriptorSetLayoutBinding {
-                        binding: 0,
-                        ty: pso::DescriptorType::Buffer {
-                            ty: pso::BufferDescriptorType::Uniform,
-                            format: pso::BufferDescriptorFormat::Structured {
-                                dynamic_offset: false,
-                            },
-                        },
-                        count: 1,
-                        stage_flags: pso::ShaderStageFlags::VERTEX,
-                        immutable_samplers: false,
-                    }],
-                    &[],
-                )
-            }
-            .expect("Can't create descriptor set layout"),
-        )
+/// A descriptor set allocated from a shared `DescriptorLayout` and freed back to it on drop.
+pub struct DescriptorSet<'a, B: Backend> {
+    layout: Rc<DescriptorLayout<'a, B>>,
+    /// `None` for a layout without bindings, which needs no set.
+    allocation: Option<Allocation<B>>,
+    /// Sizes in bytes of the buffers written to each binding.
+    pub buffer_sizes: Vec<(u32, u64)>,
+}
+
+impl<'a, B: Backend> DescriptorSet<'a, B> {
+    pub fn new(layout: &Rc<DescriptorLayout<'a, B>>) -> Self {
+        let allocation = layout.allocator.borrow_mut().allocate(&layout.raw);
+        DescriptorSet {
+            layout: Rc::clone(layout),
+            allocation,
+            buffer_sizes: vec![],
+        }
     }
 
-    pub(super) fn create_descriptor_pool(device: &B::Device) -> ManuallyDrop<B::DescriptorPool> {
-        ManuallyDrop::new(unsafe {
-            device
-                .create_descriptor_pool(
-                    1,
-                    &[pso::DescriptorRangeDesc {
-                        ty: pso::DescriptorType::Buffer {
-                            ty: pso::BufferDescriptorType::Uniform,
-                            format: pso::BufferDescriptorFormat::Structured {
-                                dynamic_offset: false,
-                            },
-                        },
-                        count: 1,
-                    }],
-                    pso::DescriptorPoolCreateFlags::empty(),
-                )
-                .expect("Can't create descriptor pool")
-        })
+    pub fn layout(&self) -> &DescriptorLayout<'a, B> {
+        &self.layout
     }
 
-    pub(super) fn create_descriptor_set(
-        desc_pool: &mut ManuallyDrop<B::DescriptorPool>,
-        layout: &ManuallyDrop<B::DescriptorSetLayout>,
-    ) -> B::DescriptorSet {
-        unsafe { desc_pool.allocate_set(&layout).unwrap() }
+    /// The set to bind, `None` if the layout has no bindings.
+    pub fn raw(&self) -> Option<&B::DescriptorSet> {
+        self.allocation.as_ref().map(|allocation| &allocation.set)
+    }
+
+    pub fn write_buffer<T>(&mut self, binding: u32, buffer: &Buffer<'a, B, T>) {
+        self.write(
+            binding,
+            pso::Descriptor::Buffer(&*buffer.buf, b::SubRange::WHOLE),
+        );
+        self.buffer_sizes.retain(|&(b, _)| b != binding);
+        self.buffer_sizes.push((binding, buffer.len));
+    }
+
+    /// Writes `texture` to `binding`, see `write_image`.
+    pub fn write_texture(&mut self, binding: u32, texture: &Texture<'a, B>) {
+        self.write_image(binding, &texture.view, &texture.sampler);
+    }
+
+    /// Writes an image in `ShaderReadOnlyOptimal` layout to `binding`. Depending on the
+    /// layout this binds the view, the sampler, or both as a combined image sampler.
+    pub fn write_image(&mut self, binding: u32, view: &B::ImageView, sampler: &B::Sampler) {
+        let ty = self.binding_type(binding);
+        let layout = i::Layout::ShaderReadOnlyOptimal;
+        let descriptor = match ty {
+            pso::DescriptorType::Image {
+                ty: pso::ImageDescriptorType::Sampled { with_sampler: true },
+            } => pso::Descriptor::CombinedImageSampler(view, layout, sampler),
+            pso::DescriptorType::Image { .. } => pso::Descriptor::Image(view, layout),
+            pso::DescriptorType::Sampler => pso::Descriptor::Sampler(sampler),
+            ty => panic!(
+                "Can't write an image to binding {} of type {:?}",
+                binding, ty
+            ),
+        };
+        self.write(binding, descriptor);
+    }
+
+    fn binding_type(&self, binding: u32) -> pso::DescriptorType {
+        self.layout
+            .bindings
+            .iter()
+            .find(|b| b.binding == binding)
+            .unwrap_or_else(|| panic!("No binding {} in descriptor set layout", binding))
+            .ty
+    }
+
+    fn write(&self, binding: u32, descriptor: pso::Descriptor<B>) {
+        let set = self
+            .raw()
+            .unwrap_or_else(|| panic!("No binding {} in descriptor set layout", binding));
+        unsafe {
+            self.layout
+                .device
+                .write_descriptor_sets(Some(pso::DescriptorSetWrite {
+                    set,
+                    binding,
+                    array_offset: 0,
+                    descriptors: Some(descriptor),
+                }));
+        }
     }
 }
 
 impl<'a, B: Backend> Drop for DescriptorSet<'a, B> {
     fn drop(&mut self) {
-        unsafe {
-            self.device
-                .destroy_descriptor_set_layout(ManuallyDrop::into_inner(ptr::read(
-                    &self.set_layout,
-                )));
-            self.device
-                .destroy_descriptor_pool(ManuallyDrop::into_inner(ptr::read(&self.pool)));
+        if let Some(allocation) = self.allocation.take() {
+            self.layout.allocator.borrow_mut().free(allocation);
         }
     }
 }
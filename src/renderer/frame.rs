@@ -0,0 +1,59 @@
+use gfx_hal::{window, Backend};
+
+use super::camera::Camera;
+use super::math::{Mat4, Vec3};
+use super::particles::ParticleSystem;
+use super::post::PostProcess;
+use super::Renderer;
+
+/// The parts of the next frame an `App` controls. Changes stay in effect for later frames.
+pub struct Frame<'f, 'a, B: Backend> {
+    renderer: &'f mut Renderer<'a, B>,
+}
+
+impl<'f, 'a, B: Backend> Frame<'f, 'a, B> {
+    pub(super) fn new(renderer: &'f mut Renderer<'a, B>) -> Self {
+        Frame { renderer }
+    }
+
+    /// The size of the swapchain images, or of the offscreen image.
+    pub fn dims(&self) -> window::Extent2D {
+        self.renderer.dims()
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.renderer.camera
+    }
+
+    /// Moves the camera, taking the camera controller along.
+    pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        let renderer = &mut *self.renderer;
+        renderer.camera.look_at(position, target);
+        renderer.controller.reset(&renderer.camera);
+    }
+
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.renderer.clear_color = color;
+    }
+
+    /// The transform of the triangle.
+    pub fn set_model(&mut self, model: Mat4) {
+        self.renderer.model = model;
+    }
+
+    /// The transform of the model loaded with `--mesh`, if there is one.
+    pub fn set_mesh_model(&mut self, model: Mat4) {
+        if let Some(mesh) = &mut self.renderer.mesh {
+            mesh.model = model;
+        }
+    }
+
+    pub fn particles_mut(&mut self) -> &mut ParticleSystem<'a, B> {
+        &mut self.renderer.particles
+    }
+
+    /// Post-processing effects applied between the scene and the swapchain.
+    pub fn post_process_mut(&mut self) -> &mut PostProcess<'a, B> {
+        &mut self.renderer.post
+    }
+}
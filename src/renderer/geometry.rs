@@ -0,0 +1,441 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use super::math::{cross, normalize, scale};
+use super::mesh::{Attributes, Primitive};
+use super::vertex::MeshVertex;
+
+const FRONT: [f32; 3] = [0.0, 0.0, 1.0];
+
+/// Settings shared by every shape.
+///
+/// Shapes are indexed triangle lists centered on the origin with Y up, winding
+/// counter-clockwise seen from the front and with UVs running from the top left. Flat
+/// shapes lie in the XY plane facing +Z, so they can also be turned into 2D `Vertex`es.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeOptions {
+    pub color: [f32; 4],
+    /// Applied to the generated UVs as `uv * uv_scale + uv_offset`, e.g. to repeat a
+    /// texture.
+    pub uv_scale: [f32; 2],
+    pub uv_offset: [f32; 2],
+}
+
+impl Default for ShapeOptions {
+    fn default() -> Self {
+        ShapeOptions {
+            color: [1.0; 4],
+            uv_scale: [1.0; 2],
+            uv_offset: [0.0; 2],
+        }
+    }
+}
+
+/// A shape of about unit size by name, for trying things out without a model file.
+pub fn shape(name: &str) -> Option<Primitive> {
+    let options = ShapeOptions::default();
+    let primitive = match name {
+        "polygon" => polygon(6, 0.5, options),
+        "circle" => circle(0.5, 32, options),
+        "rect" => rect(1.0, 0.5, options),
+        "rounded-rect" => rounded_rect(1.0, 0.5, 0.125, 4, options),
+        "grid" => grid(
+            1.0,
+            1.0,
+            4,
+            4,
+            ShapeOptions {
+                uv_scale: [4.0; 2],
+                ..options
+            },
+        ),
+        "cube" => cube(1.0, 1, options),
+        "cuboid" => cuboid([1.0, 0.5, 0.25], 2, options),
+        "sphere" => sphere(0.5, 32, 16, options),
+        "cylinder" => cylinder(0.5, 1.0, 32, true, options),
+        _ => return None,
+    };
+    Some(primitive)
+}
+
+/// A regular polygon with its first corner at the top.
+pub fn polygon(sides: u32, radius: f32, options: ShapeOptions) -> Primitive {
+    assert!(sides >= 3, "A polygon needs at least 3 sides");
+    let outline = (0..sides)
+        .map(|i| {
+            let angle = FRAC_PI_2 + TAU * i as f32 / sides as f32;
+            [radius * angle.cos(), radius * angle.sin()]
+        })
+        .collect::<Vec<_>>();
+    fan("polygon", &outline, [radius; 2], options)
+}
+
+pub fn circle(radius: f32, segments: u32, options: ShapeOptions) -> Primitive {
+    Primitive {
+        name: "circle".to_owned(),
+        ..polygon(segments, radius, options)
+    }
+}
+
+pub fn rect(width: f32, height: f32, options: ShapeOptions) -> Primitive {
+    Primitive {
+        name: "rect".to_owned(),
+        ..grid(width, height, 1, 1, options)
+    }
+}
+
+/// A rectangle whose corners are quarter circles of `radius`, each made of
+/// `corner_segments` edges. The radius is limited to half the shorter side.
+pub fn rounded_rect(
+    width: f32,
+    height: f32,
+    radius: f32,
+    corner_segments: u32,
+    options: ShapeOptions,
+) -> Primitive {
+    assert!(corner_segments >= 1, "A corner needs at least 1 segment");
+    let (hw, hh) = (width / 2.0, height / 2.0);
+    let radius = radius.max(0.0).min(hw.min(hh));
+    let corners = [
+        (hw - radius, hh - radius),
+        (radius - hw, hh - radius),
+        (radius - hw, radius - hh),
+        (hw - radius, radius - hh),
+    ];
+    let mut outline = vec![];
+    for (corner, &(cx, cy)) in corners.iter().enumerate() {
+        for i in 0..=corner_segments {
+            let angle = FRAC_PI_2 * (corner as f32 + i as f32 / corner_segments as f32);
+            outline.push([cx + radius * angle.cos(), cy + radius * angle.sin()]);
+        }
+    }
+    fan("rounded rect", &outline, [hw, hh], options)
+}
+
+/// A `width` x `height` rectangle split into `columns` x `rows` quads.
+pub fn grid(width: f32, height: f32, columns: u32, rows: u32, options: ShapeOptions) -> Primitive {
+    let mut builder = Builder::default();
+    builder.plane(
+        [-width / 2.0, height / 2.0, 0.0],
+        [width, 0.0, 0.0],
+        [0.0, -height, 0.0],
+        (columns, rows),
+    );
+    builder.finish("grid", options)
+}
+
+pub fn cube(size: f32, segments: u32, options: ShapeOptions) -> Primitive {
+    Primitive {
+        name: "cube".to_owned(),
+        ..cuboid([size; 3], segments, options)
+    }
+}
+
+/// A box with every face split into `segments` x `segments` quads, each face mapped to
+/// the whole UV range.
+pub fn cuboid(size: [f32; 3], segments: u32, options: ShapeOptions) -> Primitive {
+    let [x, y, z] = size;
+    let [hx, hy, hz] = [x / 2.0, y / 2.0, z / 2.0];
+    // Top left corner and the directions to the right and down, seen from outside.
+    let faces = [
+        ([-hx, hy, hz], [x, 0.0, 0.0], [0.0, -y, 0.0]),
+        ([hx, hy, -hz], [-x, 0.0, 0.0], [0.0, -y, 0.0]),
+        ([hx, hy, hz], [0.0, 0.0, -z], [0.0, -y, 0.0]),
+        ([-hx, hy, -hz], [0.0, 0.0, z], [0.0, -y, 0.0]),
+        ([-hx, hy, -hz], [x, 0.0, 0.0], [0.0, 0.0, z]),
+        ([-hx, -hy, hz], [x, 0.0, 0.0], [0.0, 0.0, -z]),
+    ];
+    let mut builder = Builder::default();
+    for &(origin, right, down) in &faces {
+        builder.plane(origin, right, down, (segments, segments));
+    }
+    builder.finish("cuboid", options)
+}
+
+/// A UV sphere with `segments` around the Y axis and `rings` from pole to pole.
+pub fn sphere(radius: f32, segments: u32, rings: u32, options: ShapeOptions) -> Primitive {
+    assert!(
+        segments >= 3 && rings >= 2,
+        "A sphere needs at least 3 segments and 2 rings"
+    );
+    let mut builder = Builder::default();
+    builder.surface((segments, rings), |u, v| {
+        let theta = TAU * u;
+        // sin(PI) isn't exactly 0, which would keep the bottom pole's vertices apart.
+        let (sin_phi, cos_phi) = if v < 1.0 {
+            ((PI * v).sin(), (PI * v).cos())
+        } else {
+            (0.0, -1.0)
+        };
+        let normal = [sin_phi * theta.sin(), cos_phi, sin_phi * theta.cos()];
+        (scale(normal, radius), normal)
+    });
+    builder.finish("sphere", options)
+}
+
+/// A cylinder around the Y axis with `segments` around it, closed at both ends when
+/// `caps` is set.
+pub fn cylinder(
+    radius: f32,
+    height: f32,
+    segments: u32,
+    caps: bool,
+    options: ShapeOptions,
+) -> Primitive {
+    assert!(segments >= 3, "A cylinder needs at least 3 segments");
+    let half = height / 2.0;
+    let mut builder = Builder::default();
+    builder.surface((segments, 1), |u, v| {
+        let theta = TAU * u;
+        let normal = [theta.sin(), 0.0, theta.cos()];
+        let [x, _, z] = scale(normal, radius);
+        ([x, half - height * v, z], normal)
+    });
+
+    if caps {
+        for &(y, sign) in &[(half, 1.0), (-half, -1.0)] {
+            let normal = [0.0, sign, 0.0];
+            let uv = |x: f32, z: f32| [unit(x, radius), unit(sign * z, radius)];
+            let center = builder.vertex([0.0, y, 0.0], normal, uv(0.0, 0.0));
+            let first = center + 1;
+            for i in 0..segments {
+                let theta = TAU * i as f32 / segments as f32;
+                let (x, z) = (radius * theta.sin(), radius * theta.cos());
+                builder.vertex([x, y, z], normal, uv(x, z));
+            }
+            for i in 0..segments {
+                let (a, b) = (first + i, first + (i + 1) % segments);
+                if sign > 0.0 {
+                    builder.triangle(center, a, b);
+                } else {
+                    builder.triangle(center, b, a);
+                }
+            }
+        }
+    }
+    builder.finish("cylinder", options)
+}
+
+/// Maps `-half_extent..half_extent` to `0.0..1.0`. Without an extent, as for a circle
+/// of radius 0, everything maps to the middle.
+fn unit(x: f32, half_extent: f32) -> f32 {
+    if half_extent > 0.0 {
+        0.5 + x / (2.0 * half_extent)
+    } else {
+        0.5
+    }
+}
+
+/// Fills a convex `outline` in the XY plane from its center. UVs map
+/// `-half_extent..half_extent` to `0.0..1.0`.
+fn fan(
+    name: &str,
+    outline: &[[f32; 2]],
+    half_extent: [f32; 2],
+    options: ShapeOptions,
+) -> Primitive {
+    let uv = |[x, y]: [f32; 2]| [unit(x, half_extent[0]), 1.0 - unit(y, half_extent[1])];
+    let mut builder = Builder::default();
+    let center = builder.vertex([0.0; 3], FRONT, uv([0.0, 0.0]));
+    for &point in outline {
+        builder.vertex([point[0], point[1], 0.0], FRONT, uv(point));
+    }
+    let n = outline.len() as u32;
+    for i in 0..n {
+        builder.triangle(center, center + 1 + i, center + 1 + (i + 1) % n);
+    }
+    builder.finish(name, options)
+}
+
+#[derive(Default)]
+struct Builder {
+    vertices: Vec<MeshVertex>,
+    indices: Vec<u32>,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> u32 {
+        self.vertices.push(MeshVertex {
+            position,
+            normal,
+            uv,
+            ..MeshVertex::default()
+        });
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Adds the triangle unless two of its corners coincide, as they do at the poles of
+    /// a sphere.
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let position = |i: u32| self.vertices[i as usize].position;
+        if position(a) != position(b) && position(b) != position(c) && position(c) != position(a) {
+            self.indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+
+    /// Adds a `columns` x `rows` grid of quads with `f(u, v)` giving the position and
+    /// normal at UV `(u, v)`. `u` must run to the right and `v` down, seen from the
+    /// side the normals point to.
+    fn surface<F>(&mut self, (columns, rows): (u32, u32), f: F)
+    where
+        F: Fn(f32, f32) -> ([f32; 3], [f32; 3]),
+    {
+        assert!(columns >= 1 && rows >= 1, "A surface needs at least 1 quad");
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                let (position, normal) = f(u, v);
+                self.vertex(position, normal, [u, v]);
+            }
+        }
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (top_left, top_right) = (index(column, row), index(column + 1, row));
+                let (bottom_left, bottom_right) =
+                    (index(column, row + 1), index(column + 1, row + 1));
+                self.triangle(top_left, bottom_left, bottom_right);
+                self.triangle(top_left, bottom_right, top_right);
+            }
+        }
+    }
+
+    /// A flat surface from `origin` spanning `right` and `down`.
+    fn plane(&mut self, origin: [f32; 3], right: [f32; 3], down: [f32; 3], quads: (u32, u32)) {
+        let normal = normalize(cross(down, right));
+        self.surface(quads, |u, v| {
+            let mut position = origin;
+            for axis in 0..3 {
+                position[axis] += right[axis] * u + down[axis] * v;
+            }
+            (position, normal)
+        });
+    }
+
+    fn finish(mut self, name: &str, options: ShapeOptions) -> Primitive {
+        for vertex in &mut self.vertices {
+            vertex.color = options.color;
+            for axis in 0..2 {
+                vertex.uv[axis] =
+                    vertex.uv[axis] * options.uv_scale[axis] + options.uv_offset[axis];
+            }
+        }
+        Primitive {
+            name: name.to_owned(),
+            vertices: self.vertices,
+            indices: self.indices,
+            attributes: Attributes {
+                normals: true,
+                uvs: true,
+                colors: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::math::{dot, sub};
+
+    const SHAPES: &[&str] = &[
+        "polygon",
+        "circle",
+        "rect",
+        "rounded-rect",
+        "grid",
+        "cube",
+        "cuboid",
+        "sphere",
+        "cylinder",
+    ];
+
+    fn triangles(primitive: &Primitive) -> impl Iterator<Item = [MeshVertex; 3]> + '_ {
+        primitive.indices.chunks(3).map(move |triangle| {
+            let vertex = |i: usize| primitive.vertices[triangle[i] as usize];
+            [vertex(0), vertex(1), vertex(2)]
+        })
+    }
+
+    #[test]
+    fn indices_stay_in_range() {
+        for &name in SHAPES {
+            let primitive = shape(name).unwrap();
+            assert!(!primitive.indices.is_empty(), "{} has no triangles", name);
+            assert_eq!(primitive.indices.len() % 3, 0, "{}", name);
+            let count = primitive.vertices.len() as u32;
+            assert!(primitive.indices.iter().all(|&i| i < count), "{}", name);
+        }
+        assert!(shape("teapot").is_none());
+    }
+
+    #[test]
+    fn triangles_wind_counter_clockwise_around_their_normals() {
+        for &name in SHAPES {
+            for [a, b, c] in triangles(&shape(name).unwrap()) {
+                let face = cross(sub(b.position, a.position), sub(c.position, a.position));
+                for vertex in &[a, b, c] {
+                    assert!(dot(face, vertex.normal) > 0.0, "{} winds clockwise", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flat_shapes_map_their_bounds_to_the_whole_uv_range() {
+        let rect = rect(2.0, 1.0, ShapeOptions::default());
+        for vertex in &rect.vertices {
+            let [x, y, _] = vertex.position;
+            assert_eq!(vertex.uv, [x / 2.0 + 0.5, 0.5 - y]);
+        }
+        let polygon = polygon(4, 1.0, ShapeOptions::default());
+        assert_eq!(polygon.vertices[0].uv, [0.5, 0.5]);
+        let top = polygon.vertices[1].uv;
+        assert!((top[0] - 0.5).abs() < 1e-6 && top[1].abs() < 1e-6);
+    }
+
+    #[test]
+    fn sphere_poles_have_no_degenerate_triangles() {
+        let (segments, rings) = (8, 4);
+        let sphere = sphere(1.0, segments, rings, ShapeOptions::default());
+        // Every ring has two triangles per segment, except for one at each pole.
+        let expected = (segments * (rings - 2) * 2 + segments * 2) as usize;
+        assert_eq!(sphere.indices.len(), expected * 3);
+        for [a, b, c] in triangles(&sphere) {
+            assert!(a.position != b.position && b.position != c.position);
+            assert!(c.position != a.position);
+        }
+    }
+
+    #[test]
+    fn zero_radius_gives_no_triangles_and_finite_uvs() {
+        let shapes = [
+            polygon(6, 0.0, ShapeOptions::default()),
+            rounded_rect(0.0, 0.0, 0.0, 2, ShapeOptions::default()),
+            cylinder(0.0, 1.0, 8, true, ShapeOptions::default()),
+            sphere(0.0, 8, 4, ShapeOptions::default()),
+        ];
+        for primitive in &shapes {
+            assert!(primitive.indices.is_empty(), "{}", primitive.name);
+            for vertex in &primitive.vertices {
+                assert!(vertex.uv.iter().all(|uv| uv.is_finite()));
+            }
+        }
+    }
+
+    #[test]
+    fn options_apply_to_every_vertex() {
+        let options = ShapeOptions {
+            color: [1.0, 0.0, 0.0, 1.0],
+            uv_scale: [2.0, 3.0],
+            uv_offset: [0.5, 0.25],
+        };
+        let grid = grid(1.0, 1.0, 1, 1, options);
+        assert!(grid
+            .vertices
+            .iter()
+            .all(|vertex| vertex.color == options.color));
+        let last = grid.vertices.last().unwrap();
+        assert_eq!(last.uv, [2.5, 3.25]);
+    }
+}
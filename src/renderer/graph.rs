@@ -0,0 +1,1649 @@
+use gfx_hal::{
+    adapter::{Adapter, MemoryType},
+    buffer as b, command, format as f, image as i, memory as m, pass,
+    prelude::*,
+    pso, Backend,
+};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::iter;
+use std::ops::Range;
+
+use super::memory;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassId(usize);
+
+/// Size of a transient image as a fraction of the graph's extent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    Relative(f32),
+}
+
+/// A transient image. With more than one sample it is usually resolved into a
+/// single-sampled image with `PassBuilder::resolve`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDesc {
+    pub format: f::Format,
+    pub size: ImageSize,
+    pub samples: i::NumSamples,
+}
+
+/// Decides which shader stages the sampled and storage uses of a pass wait for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassKind {
+    Graphics,
+    Compute,
+}
+
+/// What happens to an attachment's previous contents at the start of a pass.
+#[derive(Clone, Copy)]
+pub enum LoadOp {
+    Clear(command::ClearValue),
+    DontCare,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAccess {
+    Sampled,
+    StorageWrite,
+    TransferDst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferAccess {
+    Vertex,
+    StorageWrite,
+    TransferDst,
+}
+
+#[derive(Clone, Copy)]
+enum Use {
+    Color(ImageId, LoadOp),
+    Depth(ImageId, LoadOp),
+    /// Resolves the multisampled color attachment `.1` into `.0`.
+    Resolve(ImageId, ImageId),
+    Image(ImageId, ImageAccess),
+    Buffer(BufferId, BufferAccess),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resource {
+    Image(usize),
+    Buffer(usize),
+}
+
+impl Use {
+    fn resource(&self) -> Resource {
+        match *self {
+            Use::Color(id, _) | Use::Depth(id, _) | Use::Resolve(id, _) | Use::Image(id, _) => {
+                Resource::Image(id.0)
+            }
+            Use::Buffer(id, _) => Resource::Buffer(id.0),
+        }
+    }
+
+    fn reads(&self) -> bool {
+        match *self {
+            Use::Color(..) | Use::Depth(..) | Use::Resolve(..) => false,
+            Use::Image(_, access) => access != ImageAccess::TransferDst,
+            Use::Buffer(_, access) => access != BufferAccess::TransferDst,
+        }
+    }
+
+    fn writes(&self) -> bool {
+        match *self {
+            Use::Color(..) | Use::Depth(..) | Use::Resolve(..) => true,
+            Use::Image(_, access) => {
+                access == ImageAccess::StorageWrite || access == ImageAccess::TransferDst
+            }
+            Use::Buffer(_, access) => {
+                access == BufferAccess::StorageWrite || access == BufferAccess::TransferDst
+            }
+        }
+    }
+
+    fn is_attachment(&self) -> bool {
+        matches!(self, Use::Color(..) | Use::Depth(..) | Use::Resolve(..))
+    }
+
+    fn image_state(&self, kind: PassKind) -> ImageState {
+        let shader = shader_stages(kind);
+        let (stages, access, layout) = match *self {
+            Use::Color(..) | Use::Resolve(..) => (
+                pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+                i::Access::COLOR_ATTACHMENT_READ | i::Access::COLOR_ATTACHMENT_WRITE,
+                i::Layout::ColorAttachmentOptimal,
+            ),
+            Use::Depth(..) => (
+                pso::PipelineStage::EARLY_FRAGMENT_TESTS | pso::PipelineStage::LATE_FRAGMENT_TESTS,
+                i::Access::DEPTH_STENCIL_ATTACHMENT_READ
+                    | i::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                i::Layout::DepthStencilAttachmentOptimal,
+            ),
+            Use::Image(_, ImageAccess::Sampled) => (
+                shader,
+                i::Access::SHADER_READ,
+                i::Layout::ShaderReadOnlyOptimal,
+            ),
+            Use::Image(_, ImageAccess::StorageWrite) => (
+                shader,
+                i::Access::SHADER_READ | i::Access::SHADER_WRITE,
+                i::Layout::General,
+            ),
+            Use::Image(_, ImageAccess::TransferDst) => (
+                pso::PipelineStage::TRANSFER,
+                i::Access::TRANSFER_WRITE,
+                i::Layout::TransferDstOptimal,
+            ),
+            Use::Buffer(..) => unreachable!(),
+        };
+        ImageState {
+            stages,
+            access,
+            layout,
+            write: self.writes(),
+        }
+    }
+
+    fn buffer_state(&self, kind: PassKind) -> BufferState {
+        let shader = shader_stages(kind);
+        let access = match *self {
+            Use::Buffer(_, access) => access,
+            _ => unreachable!(),
+        };
+        let (stages, access) = match access {
+            BufferAccess::Vertex => (
+                pso::PipelineStage::VERTEX_INPUT,
+                b::Access::VERTEX_BUFFER_READ,
+            ),
+            BufferAccess::StorageWrite => {
+                (shader, b::Access::SHADER_READ | b::Access::SHADER_WRITE)
+            }
+            BufferAccess::TransferDst => (pso::PipelineStage::TRANSFER, b::Access::TRANSFER_WRITE),
+        };
+        BufferState {
+            stages,
+            access,
+            write: self.writes(),
+        }
+    }
+}
+
+fn shader_stages(kind: PassKind) -> pso::PipelineStage {
+    match kind {
+        PassKind::Graphics => {
+            pso::PipelineStage::VERTEX_SHADER | pso::PipelineStage::FRAGMENT_SHADER
+        }
+        PassKind::Compute => pso::PipelineStage::COMPUTE_SHADER,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ImageState {
+    stages: pso::PipelineStage,
+    access: i::Access,
+    layout: i::Layout,
+    write: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BufferState {
+    stages: pso::PipelineStage,
+    access: b::Access,
+    write: bool,
+}
+
+impl ImageState {
+    /// Accumulates consecutive reads in the same layout, so the next write waits for all of them.
+    fn then(self, next: ImageState) -> ImageState {
+        if !self.write && !next.write && self.layout == next.layout {
+            ImageState {
+                stages: self.stages | next.stages,
+                access: self.access | next.access,
+                ..next
+            }
+        } else {
+            next
+        }
+    }
+}
+
+impl BufferState {
+    fn then(self, next: BufferState) -> BufferState {
+        if !self.write && !next.write {
+            BufferState {
+                stages: self.stages | next.stages,
+                access: self.access | next.access,
+                ..next
+            }
+        } else {
+            next
+        }
+    }
+}
+struct Pass {
+    name: String,
+    kind: PassKind,
+    uses: Vec<Use>,
+}
+
+enum ImageSource {
+    Transient(ImageDesc),
+    /// Owned outside the graph and only seen through a view given to `execute`, going
+    /// from the first layout to the second over the frame.
+    Imported(Range<i::Layout>),
+}
+
+struct ImageResource {
+    name: String,
+    format: f::Format,
+    source: ImageSource,
+}
+
+impl ImageResource {
+    fn samples(&self) -> i::NumSamples {
+        match &self.source {
+            ImageSource::Transient(desc) => desc.samples,
+            ImageSource::Imported(_) => 1,
+        }
+    }
+}
+
+/// A barrier on a transient image. `src` is `None` for its first use, whose previous
+/// state depends on which image shared its memory before.
+struct PlannedBarrier {
+    image: usize,
+    src: Option<ImageState>,
+    dst: ImageState,
+}
+
+struct ImageBarrier {
+    image: usize,
+    states: Range<(i::Access, i::Layout)>,
+    stages: Range<pso::PipelineStage>,
+}
+
+struct BufferBarrier {
+    buffer: usize,
+    states: Range<b::Access>,
+    stages: Range<pso::PipelineStage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttachmentKind {
+    Color,
+    /// Resolved from the color attachment of the given image.
+    Resolve(usize),
+    Depth,
+}
+
+#[derive(Clone, Copy)]
+struct Attachment {
+    image: usize,
+    load: LoadOp,
+    kind: AttachmentKind,
+}
+
+/// The use of an imported attachment, which its render pass transitions from `prev`.
+/// `last` is set for its last use in the frame.
+#[derive(Clone, Copy)]
+struct ImportedUse {
+    image: usize,
+    prev: ImageState,
+    dst: ImageState,
+    last: bool,
+}
+
+/// A pass in execution order with everything `compile` works out before creating any
+/// device objects.
+struct PlannedStep {
+    pass: usize,
+    barriers: Vec<PlannedBarrier>,
+    buffer_barriers: Vec<BufferBarrier>,
+    /// The color attachments, their resolve attachments, then the depth attachment.
+    attachments: Vec<Attachment>,
+    imported: Vec<ImportedUse>,
+}
+
+struct Plan {
+    steps: Vec<PlannedStep>,
+    /// Steps each image is used in, by image.
+    lifetimes: Vec<Option<Range<usize>>>,
+    /// State each image is left in by its last use, by image.
+    last_states: Vec<Option<ImageState>>,
+}
+
+struct Step<B: Backend> {
+    planned: PlannedStep,
+    image_barriers: Vec<ImageBarrier>,
+    render_pass: Option<B::RenderPass>,
+    clear_values: Vec<command::ClearValue>,
+    /// Created on `resize` when every attachment is transient.
+    framebuffer: Option<B::Framebuffer>,
+}
+
+struct Transient<B: Backend> {
+    image: B::Image,
+    view: B::ImageView,
+    dims: i::Extent,
+    /// Memory block the image is bound to, shared with images whose lifetimes don't overlap.
+    block: usize,
+}
+
+/// The images, buffers and passes declared on a `RenderGraph`.
+#[derive(Default)]
+struct Declarations {
+    images: Vec<ImageResource>,
+    buffers: Vec<String>,
+    passes: Vec<Pass>,
+}
+
+/// Schedules passes from the images and buffers they declare to read and write.
+///
+/// Passes are added with `add_pass` and their uses declared on the returned builder.
+/// `compile` orders them so that every resource is written, by its writers in the order
+/// they were added, before it is read; passes unrelated through resources keep the order
+/// they were added in. It also plans the layout transitions and barriers between uses and
+/// creates a render pass for every pass with attachments.
+///
+/// `resize` allocates the transient images, letting images whose lifetimes don't overlap
+/// share memory. `execute` then records the barriers, begins and ends render passes, and
+/// calls back to record each pass's own commands.
+///
+/// Imported images can only be used as attachments, since the graph never sees the
+/// image itself, and imported buffers are assumed to be ready at the start of the frame.
+pub struct RenderGraph<'a, B: Backend> {
+    device: &'a B::Device,
+    memory_types: Vec<MemoryType>,
+    declarations: Declarations,
+    steps: Vec<Step<B>>,
+    lifetimes: Vec<Option<Range<usize>>>,
+    last_states: Vec<Option<ImageState>>,
+    extent: i::Extent,
+    transients: Vec<Option<Transient<B>>>,
+    blocks: Vec<B::Memory>,
+    /// Framebuffers with imported attachments by frame slot, destroyed on the next
+    /// `execute` for the same slot.
+    imported_framebuffers: Vec<Vec<B::Framebuffer>>,
+}
+
+pub struct PassBuilder<'g> {
+    pass: &'g mut Pass,
+    id: PassId,
+}
+
+impl<'g> PassBuilder<'g> {
+    pub fn id(&self) -> PassId {
+        self.id
+    }
+
+    pub fn color(&mut self, image: ImageId, load: LoadOp) -> &mut Self {
+        self.pass.uses.push(Use::Color(image, load));
+        self
+    }
+
+    pub fn depth(&mut self, image: ImageId, load: LoadOp) -> &mut Self {
+        self.pass.uses.push(Use::Depth(image, load));
+        self
+    }
+
+    /// Resolves `src`, a multisampled color attachment of this pass, into `dst` at the end
+    /// of the pass.
+    pub fn resolve(&mut self, src: ImageId, dst: ImageId) -> &mut Self {
+        self.pass.uses.push(Use::Resolve(dst, src));
+        self
+    }
+
+    pub fn image(&mut self, image: ImageId, access: ImageAccess) -> &mut Self {
+        self.pass.uses.push(Use::Image(image, access));
+        self
+    }
+
+    pub fn buffer(&mut self, buffer: BufferId, access: BufferAccess) -> &mut Self {
+        self.pass.uses.push(Use::Buffer(buffer, access));
+        self
+    }
+}
+
+impl Declarations {
+    fn create_image(&mut self, name: &str, desc: ImageDesc) -> ImageId {
+        self.images.push(ImageResource {
+            name: name.to_owned(),
+            format: desc.format,
+            source: ImageSource::Transient(desc),
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    fn import_image(
+        &mut self,
+        name: &str,
+        format: f::Format,
+        layouts: Range<i::Layout>,
+    ) -> ImageId {
+        self.images.push(ImageResource {
+            name: name.to_owned(),
+            format,
+            source: ImageSource::Imported(layouts),
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    fn import_buffer(&mut self, name: &str) -> BufferId {
+        self.buffers.push(name.to_owned());
+        BufferId(self.buffers.len() - 1)
+    }
+
+    fn add_pass(&mut self, name: &str, kind: PassKind) -> PassBuilder<'_> {
+        self.passes.push(Pass {
+            name: name.to_owned(),
+            kind,
+            uses: vec![],
+        });
+        let id = PassId(self.passes.len() - 1);
+        PassBuilder {
+            pass: self.passes.last_mut().unwrap(),
+            id,
+        }
+    }
+
+    /// Orders the passes, works out each image's lifetime and plans the barriers between
+    /// uses.
+    fn plan(&self) -> Plan {
+        let order = self.order();
+        let mut lifetimes: Vec<Option<Range<usize>>> = vec![None; self.images.len()];
+        for (step, &pass) in order.iter().enumerate() {
+            for u in &self.passes[pass].uses {
+                if let Resource::Image(image) = u.resource() {
+                    let lifetime = lifetimes[image].get_or_insert(step..step);
+                    lifetime.end = step + 1;
+                }
+            }
+        }
+
+        let mut image_states: Vec<Option<ImageState>> = self
+            .images
+            .iter()
+            .map(|image| match &image.source {
+                // Presentation engines hand out images with a semaphore waited on at
+                // this stage.
+                ImageSource::Imported(layouts) => Some(ImageState {
+                    stages: pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+                    access: i::Access::empty(),
+                    layout: layouts.start,
+                    write: false,
+                }),
+                ImageSource::Transient(_) => None,
+            })
+            .collect();
+        let mut buffer_states: Vec<Option<BufferState>> = vec![None; self.buffers.len()];
+
+        let mut steps = vec![];
+        for (step, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            let mut barriers = vec![];
+            let mut buffer_barriers = vec![];
+            let mut imported = vec![];
+
+            for u in &pass.uses {
+                match u.resource() {
+                    Resource::Image(image) => {
+                        let dst = u.image_state(pass.kind);
+                        let prev = image_states[image];
+                        if let ImageSource::Imported(layouts) = &self.images[image].source {
+                            assert!(
+                                u.is_attachment(),
+                                "imported image `{}` can only be used as an attachment",
+                                self.images[image].name
+                            );
+                            let last = lifetimes[image].as_ref().unwrap().end == step + 1;
+                            imported.push(ImportedUse {
+                                image,
+                                prev: prev.unwrap(),
+                                dst,
+                                last,
+                            });
+                            image_states[image] = Some(ImageState {
+                                layout: if last { layouts.end } else { dst.layout },
+                                ..dst
+                            });
+                            continue;
+                        }
+                        let needed = prev.is_none_or(|prev| {
+                            prev.layout != dst.layout || prev.write || dst.write
+                        });
+                        if needed {
+                            barriers.push(PlannedBarrier {
+                                image,
+                                src: prev,
+                                dst,
+                            });
+                        }
+                        image_states[image] = Some(prev.map_or(dst, |prev| prev.then(dst)));
+                    }
+                    Resource::Buffer(buffer) => {
+                        let dst = u.buffer_state(pass.kind);
+                        let prev = buffer_states[buffer];
+                        if let Some(prev) = prev {
+                            if prev.write || dst.write {
+                                buffer_barriers.push(BufferBarrier {
+                                    buffer,
+                                    states: prev.access..dst.access,
+                                    stages: prev.stages..dst.stages,
+                                });
+                            }
+                        }
+                        buffer_states[buffer] = Some(prev.map_or(dst, |prev| prev.then(dst)));
+                    }
+                }
+            }
+
+            steps.push(PlannedStep {
+                pass: index,
+                barriers,
+                buffer_barriers,
+                attachments: self.attachments(pass),
+                imported,
+            });
+        }
+
+        log::info!(
+            "render graph order: {}",
+            order
+                .iter()
+                .map(|&pass| &self.passes[pass].name[..])
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+        Plan {
+            steps,
+            lifetimes,
+            last_states: image_states,
+        }
+    }
+
+    fn attachments(&self, pass: &Pass) -> Vec<Attachment> {
+        let colors = pass.uses.iter().filter_map(|u| match *u {
+            Use::Color(id, load) => Some(Attachment {
+                image: id.0,
+                load,
+                kind: AttachmentKind::Color,
+            }),
+            _ => None,
+        });
+        let resolves = pass.uses.iter().filter_map(|u| match *u {
+            Use::Resolve(dst, src) => {
+                assert!(
+                    pass.uses
+                        .iter()
+                        .any(|u| matches!(*u, Use::Color(id, _) if id == src)),
+                    "`{}` is resolved in pass `{}` without being a color attachment of it",
+                    self.images[src.0].name,
+                    pass.name
+                );
+                Some(Attachment {
+                    image: dst.0,
+                    load: LoadOp::DontCare,
+                    kind: AttachmentKind::Resolve(src.0),
+                })
+            }
+            _ => None,
+        });
+        let depth = pass.uses.iter().filter_map(|u| match *u {
+            Use::Depth(id, load) => Some(Attachment {
+                image: id.0,
+                load,
+                kind: AttachmentKind::Depth,
+            }),
+            _ => None,
+        });
+        colors.chain(resolves).chain(depth).collect()
+    }
+
+    /// Topologically sorts the passes, preferring the order they were added in.
+    fn order(&self) -> Vec<usize> {
+        let count = self.passes.len();
+        let mut edges = vec![vec![]; count];
+        let resources = self
+            .passes
+            .iter()
+            .flat_map(|pass| pass.uses.iter().map(|u| u.resource()))
+            .fold(vec![], |mut resources, resource| {
+                if !resources.contains(&resource) {
+                    resources.push(resource);
+                }
+                resources
+            });
+        for resource in resources {
+            let users = |f: fn(&Use) -> bool| {
+                (0..count)
+                    .filter(|&pass| {
+                        self.passes[pass]
+                            .uses
+                            .iter()
+                            .any(|u| u.resource() == resource && f(u))
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let writers = users(Use::writes);
+            let readers = users(|u| u.reads() && !u.writes());
+            for pair in writers.windows(2) {
+                edges[pair[0]].push(pair[1]);
+            }
+            if let Some(&last) = writers.last() {
+                for &reader in readers.iter().filter(|&&reader| !writers.contains(&reader)) {
+                    edges[last].push(reader);
+                }
+            }
+        }
+
+        let mut incoming = vec![0; count];
+        for to in edges.iter().flatten() {
+            incoming[*to] += 1;
+        }
+        let mut ready = (0..count)
+            .filter(|&pass| incoming[pass] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
+        let mut order = vec![];
+        while let Some(Reverse(pass)) = ready.pop() {
+            order.push(pass);
+            for &to in &edges[pass] {
+                incoming[to] -= 1;
+                if incoming[to] == 0 {
+                    ready.push(Reverse(to));
+                }
+            }
+        }
+        if order.len() < count {
+            let cycle = (0..count)
+                .filter(|pass| !order.contains(pass))
+                .map(|pass| &self.passes[pass].name[..])
+                .collect::<Vec<_>>();
+            panic!("render graph has a cycle between {}", cycle.join(", "));
+        }
+        order
+    }
+
+    /// Image usage covering every use of `image`.
+    fn usage(&self, image: usize) -> i::Usage {
+        self.passes
+            .iter()
+            .flat_map(|pass| pass.uses.iter())
+            .filter(|u| u.resource() == Resource::Image(image))
+            .fold(i::Usage::empty(), |usage, u| {
+                usage
+                    | match *u {
+                        Use::Color(..) | Use::Resolve(..) => i::Usage::COLOR_ATTACHMENT,
+                        Use::Depth(..) => i::Usage::DEPTH_STENCIL_ATTACHMENT,
+                        Use::Image(_, ImageAccess::Sampled) => i::Usage::SAMPLED,
+                        Use::Image(_, ImageAccess::StorageWrite) => i::Usage::STORAGE,
+                        Use::Image(_, ImageAccess::TransferDst) => i::Usage::TRANSFER_DST,
+                        Use::Buffer(..) => unreachable!(),
+                    }
+            })
+    }
+}
+
+impl<'a, B: Backend> RenderGraph<'a, B> {
+    pub fn new(device: &'a B::Device, adapter: &Adapter<B>) -> Self {
+        RenderGraph {
+            device,
+            memory_types: adapter.physical_device.memory_properties().memory_types,
+            declarations: Declarations::default(),
+            steps: vec![],
+            lifetimes: vec![],
+            last_states: vec![],
+            extent: i::Extent {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+            transients: vec![],
+            blocks: vec![],
+            imported_framebuffers: vec![],
+        }
+    }
+
+    /// Declares an image allocated by the graph, whose contents only live for a frame.
+    pub fn create_image(&mut self, name: &str, desc: ImageDesc) -> ImageId {
+        self.declarations.create_image(name, desc)
+    }
+
+    /// Declares an image owned elsewhere, e.g. the swapchain image, that starts the frame
+    /// in `layouts.start` and must end it in `layouts.end`. It has the graph's extent.
+    pub fn import_image(
+        &mut self,
+        name: &str,
+        format: f::Format,
+        layouts: Range<i::Layout>,
+    ) -> ImageId {
+        self.declarations.import_image(name, format, layouts)
+    }
+
+    pub fn import_buffer(&mut self, name: &str) -> BufferId {
+        self.declarations.import_buffer(name)
+    }
+
+    /// Adds a pass. Graphics passes with attachments are recorded inside a render pass
+    /// created by the graph; other passes record their own commands, including any render
+    /// passes of their own.
+    pub fn add_pass(&mut self, name: &str, kind: PassKind) -> PassBuilder<'_> {
+        self.declarations.add_pass(name, kind)
+    }
+
+    /// Orders the passes, plans their barriers and creates their render passes. Must be
+    /// called once after all passes are added, followed by `resize`.
+    pub fn compile(&mut self) {
+        assert!(self.steps.is_empty(), "render graph is already compiled");
+        let plan = self.declarations.plan();
+        let steps = plan
+            .steps
+            .into_iter()
+            .map(|planned| {
+                let render_pass = if planned.attachments.is_empty() {
+                    None
+                } else {
+                    Some(self.create_render_pass(&planned))
+                };
+                let clear_values = planned
+                    .attachments
+                    .iter()
+                    .map(|attachment| match attachment.load {
+                        LoadOp::Clear(value) => value,
+                        LoadOp::DontCare => command::ClearValue {
+                            color: command::ClearColor { float32: [0.0; 4] },
+                        },
+                    })
+                    .collect();
+                Step {
+                    planned,
+                    image_barriers: vec![],
+                    render_pass,
+                    clear_values,
+                    framebuffer: None,
+                }
+            })
+            .collect();
+        self.steps = steps;
+        self.lifetimes = plan.lifetimes;
+        self.last_states = plan.last_states;
+    }
+
+    /// Allocates the transient images for `extent`, freeing the previous ones. The graph
+    /// must not be in use by the GPU.
+    pub fn resize(&mut self, extent: i::Extent) {
+        self.destroy_transients();
+        self.extent = extent;
+
+        let mut images = vec![];
+        for (index, image) in self.declarations.images.iter().enumerate() {
+            let desc = match (&image.source, &self.lifetimes[index]) {
+                (ImageSource::Transient(desc), Some(_)) => desc,
+                _ => continue,
+            };
+            let dims = match desc.size {
+                ImageSize::Relative(scale) => i::Extent {
+                    width: ((extent.width as f32 * scale) as u32).max(1),
+                    height: ((extent.height as f32 * scale) as u32).max(1),
+                    depth: 1,
+                },
+            };
+            let raw = unsafe {
+                self.device.create_image(
+                    i::Kind::D2(dims.width, dims.height, 1, desc.samples),
+                    1,
+                    desc.format,
+                    i::Tiling::Optimal,
+                    self.declarations.usage(index),
+                    i::ViewCapabilities::empty(),
+                )
+            }
+            .expect("Can't create image");
+            let requirements = unsafe { self.device.get_image_requirements(&raw) };
+            images.push((index, raw, dims, requirements));
+        }
+
+        let (blocks, assigned) = assign_blocks(
+            &images
+                .iter()
+                .map(|&(index, _, _, requirements)| (index, requirements))
+                .collect::<Vec<_>>(),
+            &self.lifetimes,
+        );
+        let unaliased: u64 = images.iter().map(|(_, _, _, req)| req.size).sum();
+        let aliased: u64 = blocks.iter().map(|req| req.size).sum();
+        log::info!(
+            "render graph: {} transient images in {} allocations, {} KiB ({} KiB without aliasing)",
+            images.len(),
+            blocks.len(),
+            aliased / 1024,
+            unaliased / 1024
+        );
+
+        self.blocks = blocks
+            .iter()
+            .map(|requirements| {
+                let memory_type = memory::memory_type(
+                    &self.memory_types,
+                    requirements,
+                    m::Properties::DEVICE_LOCAL,
+                );
+                unsafe { self.device.allocate_memory(memory_type, requirements.size) }
+                    .expect("Can't allocate image memory")
+            })
+            .collect();
+
+        self.transients = (0..self.declarations.images.len()).map(|_| None).collect();
+        for ((index, mut raw, dims, _), block) in images.into_iter().zip(assigned) {
+            let format = self.declarations.images[index].format;
+            let view = unsafe {
+                self.device
+                    .bind_image_memory(&self.blocks[block], 0, &mut raw)
+                    .expect("Can't bind image memory");
+                self.device.create_image_view(
+                    &raw,
+                    i::ViewKind::D2,
+                    format,
+                    f::Swizzle::NO,
+                    full_range(format),
+                )
+            }
+            .expect("Can't create image view");
+            self.transients[index] = Some(Transient {
+                image: raw,
+                view,
+                dims,
+                block,
+            });
+        }
+
+        let blocks = self
+            .transients
+            .iter()
+            .map(|transient| transient.as_ref().map(|t| t.block))
+            .collect::<Vec<_>>();
+        for step in &mut self.steps {
+            step.image_barriers = resolve_barriers(
+                &step.planned.barriers,
+                &blocks,
+                &self.lifetimes,
+                &self.last_states,
+            );
+        }
+        self.create_framebuffers();
+    }
+
+    pub fn extent(&self) -> i::Extent {
+        self.extent
+    }
+
+    /// The view of a transient image, e.g. to sample it in a later pass.
+    pub fn view(&self, image: ImageId) -> &B::ImageView {
+        &self.transient(image.0).view
+    }
+
+    /// The render pass created for `pass`, for building pipelines that draw in it.
+    pub fn render_pass(&self, pass: PassId) -> &B::RenderPass {
+        self.step(pass).render_pass.as_ref().unwrap_or_else(|| {
+            panic!(
+                "pass `{}` has no attachments",
+                self.declarations.passes[pass.0].name
+            )
+        })
+    }
+
+    /// Changes the value `image` is cleared to at the start of `pass`, for an attachment
+    /// declared with `LoadOp::Clear`.
+    pub fn set_clear_value(&mut self, pass: PassId, image: ImageId, value: command::ClearValue) {
+        let declarations = &self.declarations;
+        let step = self
+            .steps
+            .iter_mut()
+            .find(|step| step.planned.pass == pass.0)
+            .expect("render graph is not compiled");
+        let attachment = step
+            .planned
+            .attachments
+            .iter()
+            .position(|attachment| {
+                attachment.image == image.0 && matches!(attachment.load, LoadOp::Clear(_))
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "`{}` isn't cleared in pass `{}`",
+                    declarations.images[image.0].name, declarations.passes[pass.0].name
+                )
+            });
+        step.clear_values[attachment] = value;
+    }
+
+    /// Records every pass in order. `images` and `buffers` give the imported resources,
+    /// and `record` is called with each pass to record its commands.
+    ///
+    /// `slot` is the frame in flight `cmd_buffer` belongs to. Framebuffers over imported
+    /// views are kept until the next `execute` with the same slot, which must only come
+    /// once the GPU is done with the frame recorded now.
+    pub unsafe fn execute<F>(
+        &mut self,
+        cmd_buffer: &mut B::CommandBuffer,
+        slot: usize,
+        images: &[(ImageId, &B::ImageView)],
+        buffers: &[(BufferId, &B::Buffer)],
+        mut record: F,
+    ) where
+        F: FnMut(PassId, &mut B::CommandBuffer),
+    {
+        if self.imported_framebuffers.len() <= slot {
+            self.imported_framebuffers.resize_with(slot + 1, Vec::new);
+        }
+        for framebuffer in self.imported_framebuffers[slot].drain(..) {
+            self.device.destroy_framebuffer(framebuffer);
+        }
+
+        let declarations = &self.declarations;
+        let mut created = vec![];
+        for step in &self.steps {
+            let mut stages = pso::PipelineStage::empty()..pso::PipelineStage::empty();
+            let mut barriers = vec![];
+            for barrier in &step.image_barriers {
+                stages.start |= barrier.stages.start;
+                stages.end |= barrier.stages.end;
+                barriers.push(m::Barrier::Image {
+                    states: barrier.states.clone(),
+                    target: &self.transient(barrier.image).image,
+                    families: None,
+                    range: full_range(declarations.images[barrier.image].format),
+                });
+            }
+            for barrier in &step.planned.buffer_barriers {
+                stages.start |= barrier.stages.start;
+                stages.end |= barrier.stages.end;
+                let target = buffers
+                    .iter()
+                    .find(|(id, _)| id.0 == barrier.buffer)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "buffer `{}` was not given",
+                            declarations.buffers[barrier.buffer]
+                        )
+                    })
+                    .1;
+                barriers.push(m::Barrier::whole_buffer(target, barrier.states.clone()));
+            }
+            if !barriers.is_empty() {
+                cmd_buffer.pipeline_barrier(stages, m::Dependencies::empty(), barriers);
+            }
+
+            let id = PassId(step.planned.pass);
+            let render_pass = match &step.render_pass {
+                Some(render_pass) => render_pass,
+                None => {
+                    record(id, cmd_buffer);
+                    continue;
+                }
+            };
+
+            let extent = self.attachment_extent(step);
+            let framebuffer = match &step.framebuffer {
+                Some(framebuffer) => framebuffer,
+                None => {
+                    let views = step.planned.attachments.iter().map(|attachment| {
+                        let image = attachment.image;
+                        match &self.transients[image] {
+                            Some(transient) => &transient.view,
+                            None => {
+                                images
+                                    .iter()
+                                    .find(|(id, _)| id.0 == image)
+                                    .unwrap_or_else(|| {
+                                        panic!(
+                                            "image `{}` was not given",
+                                            declarations.images[image].name
+                                        )
+                                    })
+                                    .1
+                            }
+                        }
+                    });
+                    let framebuffer = self
+                        .device
+                        .create_framebuffer(render_pass, views, extent)
+                        .expect("Could not create frame buffer");
+                    created.push(framebuffer);
+                    created.last().unwrap()
+                }
+            };
+
+            let viewport = pso::Viewport {
+                rect: pso::Rect {
+                    x: 0,
+                    y: 0,
+                    w: extent.width as _,
+                    h: extent.height as _,
+                },
+                depth: 0.0..1.0,
+            };
+            cmd_buffer.set_viewports(0, iter::once(&viewport));
+            cmd_buffer.set_scissors(0, iter::once(&viewport.rect));
+            cmd_buffer.begin_render_pass(
+                render_pass,
+                framebuffer,
+                viewport.rect,
+                &step.clear_values,
+                command::SubpassContents::Inline,
+            );
+            record(id, cmd_buffer);
+            cmd_buffer.end_render_pass();
+        }
+        self.imported_framebuffers[slot] = created;
+    }
+
+    /// Transient attachments stay in their attachment layout, with transitions done by
+    /// barriers. Imported ones are transitioned by the render pass, since the graph only
+    /// sees their views.
+    fn create_render_pass(&self, planned: &PlannedStep) -> B::RenderPass {
+        let images = &self.declarations.images;
+        let attachments = &planned.attachments;
+        let descs = attachments
+            .iter()
+            .map(|attachment| {
+                let image = &images[attachment.image];
+                let depth = attachment.kind == AttachmentKind::Depth;
+                let layout = if depth {
+                    i::Layout::DepthStencilAttachmentOptimal
+                } else {
+                    i::Layout::ColorAttachmentOptimal
+                };
+                let load_op = match attachment.load {
+                    LoadOp::Clear(_) => pass::AttachmentLoadOp::Clear,
+                    LoadOp::DontCare => pass::AttachmentLoadOp::DontCare,
+                };
+                let layouts = match planned
+                    .imported
+                    .iter()
+                    .find(|imported| imported.image == attachment.image)
+                {
+                    Some(imported) => {
+                        let end = match (&image.source, imported.last) {
+                            (ImageSource::Imported(layouts), true) => layouts.end,
+                            _ => layout,
+                        };
+                        imported.prev.layout..end
+                    }
+                    None => layout..layout,
+                };
+                let ops = pass::AttachmentOps::new(load_op, pass::AttachmentStoreOp::Store);
+                pass::Attachment {
+                    format: Some(image.format),
+                    samples: image.samples(),
+                    ops,
+                    stencil_ops: if depth {
+                        ops
+                    } else {
+                        pass::AttachmentOps::DONT_CARE
+                    },
+                    layouts,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let colors = attachments
+            .iter()
+            .enumerate()
+            .filter(|(_, attachment)| attachment.kind == AttachmentKind::Color)
+            .map(|(n, _)| (n, i::Layout::ColorAttachmentOptimal))
+            .collect::<Vec<_>>();
+        // Either none or one per color attachment, unused where it isn't resolved.
+        let resolves = if attachments
+            .iter()
+            .any(|attachment| matches!(attachment.kind, AttachmentKind::Resolve(_)))
+        {
+            colors
+                .iter()
+                .map(|&(n, layout)| {
+                    let resolve = attachments.iter().position(|attachment| {
+                        attachment.kind == AttachmentKind::Resolve(attachments[n].image)
+                    });
+                    (resolve.unwrap_or(pass::ATTACHMENT_UNUSED), layout)
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let depth_stencil = attachments
+            .iter()
+            .position(|attachment| attachment.kind == AttachmentKind::Depth)
+            .map(|n| (n, i::Layout::DepthStencilAttachmentOptimal));
+        let subpass = pass::SubpassDesc {
+            colors: &colors,
+            depth_stencil: depth_stencil.as_ref(),
+            inputs: &[],
+            resolves: &resolves,
+            preserves: &[],
+        };
+
+        let mut dependencies = vec![];
+        for imported in &planned.imported {
+            dependencies.push(pass::SubpassDependency {
+                passes: None..Some(0),
+                stages: imported.prev.stages..imported.dst.stages,
+                accesses: imported.prev.access..imported.dst.access,
+                flags: m::Dependencies::empty(),
+            });
+            if imported.last {
+                dependencies.push(pass::SubpassDependency {
+                    passes: Some(0)..None,
+                    stages: imported.dst.stages..pso::PipelineStage::BOTTOM_OF_PIPE,
+                    accesses: imported.dst.access..i::Access::empty(),
+                    flags: m::Dependencies::empty(),
+                });
+            }
+        }
+
+        unsafe {
+            self.device
+                .create_render_pass(&descs, &[subpass], &dependencies)
+        }
+        .expect("Can't create render pass")
+    }
+
+    fn create_framebuffers(&mut self) {
+        for n in 0..self.steps.len() {
+            let step = &self.steps[n];
+            let render_pass = match &step.render_pass {
+                Some(render_pass) => render_pass,
+                None => continue,
+            };
+            let attachments = &step.planned.attachments;
+            if attachments
+                .iter()
+                .any(|attachment| self.transients[attachment.image].is_none())
+            {
+                continue;
+            }
+            let views = attachments
+                .iter()
+                .map(|attachment| &self.transient(attachment.image).view);
+            let framebuffer = unsafe {
+                self.device
+                    .create_framebuffer(render_pass, views, self.attachment_extent(step))
+            }
+            .expect("Could not create frame buffer");
+            self.steps[n].framebuffer = Some(framebuffer);
+        }
+    }
+
+    fn step(&self, pass: PassId) -> &Step<B> {
+        self.steps
+            .iter()
+            .find(|step| step.planned.pass == pass.0)
+            .expect("render graph is not compiled")
+    }
+
+    fn attachment_extent(&self, step: &Step<B>) -> i::Extent {
+        let extents = step
+            .planned
+            .attachments
+            .iter()
+            .map(|attachment| {
+                self.transients[attachment.image]
+                    .as_ref()
+                    .map_or(self.extent, |t| t.dims)
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            extents.windows(2).all(|pair| pair[0] == pair[1]),
+            "attachments of pass `{}` have different sizes",
+            self.declarations.passes[step.planned.pass].name
+        );
+        extents[0]
+    }
+
+    fn transient(&self, image: usize) -> &Transient<B> {
+        self.transients
+            .get(image)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| {
+                panic!(
+                    "image `{}` is not allocated",
+                    self.declarations.images[image].name
+                )
+            })
+    }
+
+    fn destroy_transients(&mut self) {
+        unsafe {
+            for step in &mut self.steps {
+                if let Some(framebuffer) = step.framebuffer.take() {
+                    self.device.destroy_framebuffer(framebuffer);
+                }
+            }
+            for transient in self.transients.drain(..).flatten() {
+                self.device.destroy_image_view(transient.view);
+                self.device.destroy_image(transient.image);
+            }
+            for block in self.blocks.drain(..) {
+                self.device.free_memory(block);
+            }
+        }
+    }
+}
+
+/// Assigns each of `images`, given as the image and its memory requirements, to a memory
+/// block. Largest first, each goes into the first block whose images are all used in
+/// other steps than it. Returns the requirements of every block and the block of each
+/// image.
+fn assign_blocks(
+    images: &[(usize, m::Requirements)],
+    lifetimes: &[Option<Range<usize>>],
+) -> (Vec<m::Requirements>, Vec<usize>) {
+    let mut by_size = (0..images.len()).collect::<Vec<_>>();
+    by_size.sort_by_key(|&n| Reverse(images[n].1.size));
+
+    let mut blocks: Vec<(m::Requirements, Vec<usize>)> = vec![];
+    let mut assigned = vec![0; images.len()];
+    for n in by_size {
+        let (index, requirements) = images[n];
+        let lifetime = lifetimes[index].clone().unwrap();
+        let block = blocks.iter().position(|(block, users)| {
+            block.type_mask & requirements.type_mask != 0
+                && users.iter().all(|&user| {
+                    let other = lifetimes[user].as_ref().unwrap();
+                    other.end <= lifetime.start || lifetime.end <= other.start
+                })
+        });
+        assigned[n] = match block {
+            Some(block) => {
+                let (desc, users) = &mut blocks[block];
+                desc.size = desc.size.max(requirements.size);
+                desc.alignment = desc.alignment.max(requirements.alignment);
+                desc.type_mask &= requirements.type_mask;
+                users.push(index);
+                block
+            }
+            None => {
+                blocks.push((requirements, vec![index]));
+                blocks.len() - 1
+            }
+        };
+    }
+    (
+        blocks
+            .into_iter()
+            .map(|(requirements, _)| requirements)
+            .collect(),
+        assigned,
+    )
+}
+
+/// Fills in the previous state of each transient image's first use from the last use of
+/// the image that had its memory before. That is the last image before it in the same
+/// block or, for the first image in a block, the block's last image in the previous frame,
+/// since frames in flight share the transient images. `blocks` gives each image's memory
+/// block.
+fn resolve_barriers(
+    planned: &[PlannedBarrier],
+    blocks: &[Option<usize>],
+    lifetimes: &[Option<Range<usize>>],
+    last_states: &[Option<ImageState>],
+) -> Vec<ImageBarrier> {
+    planned
+        .iter()
+        .map(|planned| {
+            let src = planned.src.unwrap_or_else(|| {
+                let block = blocks[planned.image];
+                let start = lifetimes[planned.image].as_ref().unwrap().start;
+                let sharing = blocks
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &other_block)| other_block.is_some() && other_block == block)
+                    .map(|(other, _)| (other, lifetimes[other].as_ref().unwrap().end))
+                    .collect::<Vec<_>>();
+                let previous = sharing
+                    .iter()
+                    .filter(|&&(_, end)| end <= start)
+                    .max_by_key(|&&(_, end)| end)
+                    .or_else(|| sharing.iter().max_by_key(|&&(_, end)| end))
+                    .and_then(|&(other, _)| last_states[other]);
+                ImageState {
+                    layout: i::Layout::Undefined,
+                    ..previous.unwrap_or(ImageState {
+                        stages: pso::PipelineStage::TOP_OF_PIPE,
+                        access: i::Access::empty(),
+                        layout: i::Layout::Undefined,
+                        write: false,
+                    })
+                }
+            });
+            ImageBarrier {
+                image: planned.image,
+                states: (src.access, src.layout)..(planned.dst.access, planned.dst.layout),
+                stages: src.stages..planned.dst.stages,
+            }
+        })
+        .collect()
+}
+
+fn full_range(format: f::Format) -> i::SubresourceRange {
+    i::SubresourceRange {
+        aspects: format.surface_desc().aspects,
+        levels: 0..1,
+        layers: 0..1,
+    }
+}
+
+impl<'a, B: Backend> Drop for RenderGraph<'a, B> {
+    fn drop(&mut self) {
+        self.destroy_transients();
+        unsafe {
+            for framebuffer in self.imported_framebuffers.drain(..).flatten() {
+                self.device.destroy_framebuffer(framebuffer);
+            }
+            for step in self.steps.drain(..) {
+                if let Some(render_pass) = step.render_pass {
+                    self.device.destroy_render_pass(render_pass);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color_image(declarations: &mut Declarations, name: &str) -> ImageId {
+        declarations.create_image(
+            name,
+            ImageDesc {
+                format: f::Format::Rgba8Srgb,
+                size: ImageSize::Relative(1.0),
+                samples: 1,
+            },
+        )
+    }
+
+    fn names(declarations: &Declarations, order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&pass| declarations.passes[pass].name.clone())
+            .collect()
+    }
+
+    fn requirements(size: u64, type_mask: u64) -> m::Requirements {
+        m::Requirements {
+            size,
+            alignment: 256,
+            type_mask,
+        }
+    }
+
+    #[test]
+    fn order_puts_writers_before_readers() {
+        let mut declarations = Declarations::default();
+        let scene = color_image(&mut declarations, "scene");
+        let blurred = color_image(&mut declarations, "blurred");
+        declarations
+            .add_pass("blur", PassKind::Graphics)
+            .image(scene, ImageAccess::Sampled)
+            .color(blurred, LoadOp::DontCare);
+        declarations
+            .add_pass("scene", PassKind::Graphics)
+            .color(scene, LoadOp::DontCare);
+        declarations.add_pass("unrelated", PassKind::Compute);
+        declarations
+            .add_pass("tonemap", PassKind::Graphics)
+            .image(blurred, ImageAccess::Sampled);
+
+        let order = declarations.order();
+        assert_eq!(
+            names(&declarations, &order),
+            ["scene", "blur", "unrelated", "tonemap"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle")]
+    fn order_rejects_cycles() {
+        let mut declarations = Declarations::default();
+        let a = color_image(&mut declarations, "a");
+        let b = color_image(&mut declarations, "b");
+        declarations
+            .add_pass("first", PassKind::Graphics)
+            .image(a, ImageAccess::Sampled)
+            .color(b, LoadOp::DontCare);
+        declarations
+            .add_pass("second", PassKind::Graphics)
+            .image(b, ImageAccess::Sampled)
+            .color(a, LoadOp::DontCare);
+        declarations.order();
+    }
+
+    #[test]
+    fn plan_transitions_attachments_to_sampled_reads() {
+        let mut declarations = Declarations::default();
+        let scene = color_image(&mut declarations, "scene");
+        declarations
+            .add_pass("scene", PassKind::Graphics)
+            .color(scene, LoadOp::DontCare);
+        declarations
+            .add_pass("first read", PassKind::Graphics)
+            .image(scene, ImageAccess::Sampled);
+        declarations
+            .add_pass("second read", PassKind::Compute)
+            .image(scene, ImageAccess::Sampled);
+
+        let plan = declarations.plan();
+        assert_eq!(plan.lifetimes[scene.0], Some(0..3));
+        let first = &plan.steps[0].barriers;
+        assert_eq!(first.len(), 1);
+        assert!(first[0].src.is_none());
+        assert_eq!(first[0].dst.layout, i::Layout::ColorAttachmentOptimal);
+
+        let read = &plan.steps[1].barriers;
+        assert_eq!(read.len(), 1);
+        let src = read[0].src.unwrap();
+        assert_eq!(src.layout, i::Layout::ColorAttachmentOptimal);
+        assert_eq!(src.stages, pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT);
+        assert_eq!(read[0].dst.layout, i::Layout::ShaderReadOnlyOptimal);
+        assert_eq!(read[0].dst.access, i::Access::SHADER_READ);
+
+        assert!(plan.steps[2].barriers.is_empty());
+        let last = plan.last_states[scene.0].unwrap();
+        assert_eq!(
+            last.stages,
+            pso::PipelineStage::VERTEX_SHADER
+                | pso::PipelineStage::FRAGMENT_SHADER
+                | pso::PipelineStage::COMPUTE_SHADER
+        );
+    }
+
+    #[test]
+    fn plan_orders_buffer_writes_before_vertex_reads() {
+        let mut declarations = Declarations::default();
+        let particles = declarations.import_buffer("particles");
+        let target = color_image(&mut declarations, "target");
+        declarations
+            .add_pass("simulate", PassKind::Compute)
+            .buffer(particles, BufferAccess::StorageWrite);
+        declarations
+            .add_pass("draw", PassKind::Graphics)
+            .buffer(particles, BufferAccess::Vertex)
+            .color(target, LoadOp::DontCare);
+
+        let plan = declarations.plan();
+        assert!(plan.steps[0].buffer_barriers.is_empty());
+        let barriers = &plan.steps[1].buffer_barriers;
+        assert_eq!(barriers.len(), 1);
+        assert_eq!(barriers[0].buffer, particles.0);
+        assert_eq!(
+            barriers[0].states,
+            b::Access::SHADER_READ | b::Access::SHADER_WRITE..b::Access::VERTEX_BUFFER_READ
+        );
+        assert_eq!(
+            barriers[0].stages,
+            pso::PipelineStage::COMPUTE_SHADER..pso::PipelineStage::VERTEX_INPUT
+        );
+    }
+
+    #[test]
+    fn plan_lists_colors_then_resolves_then_depth() {
+        let mut declarations = Declarations::default();
+        let backbuffer = declarations.import_image(
+            "backbuffer",
+            f::Format::Bgra8Srgb,
+            i::Layout::Undefined..i::Layout::Present,
+        );
+        let msaa = declarations.create_image(
+            "msaa",
+            ImageDesc {
+                format: f::Format::Bgra8Srgb,
+                size: ImageSize::Relative(1.0),
+                samples: 4,
+            },
+        );
+        let depth = declarations.create_image(
+            "depth",
+            ImageDesc {
+                format: f::Format::D32Sfloat,
+                size: ImageSize::Relative(1.0),
+                samples: 4,
+            },
+        );
+        declarations
+            .add_pass("scene", PassKind::Graphics)
+            .depth(depth, LoadOp::DontCare)
+            .resolve(msaa, backbuffer)
+            .color(msaa, LoadOp::DontCare);
+
+        let plan = declarations.plan();
+        let step = &plan.steps[0];
+        let attachments = step
+            .attachments
+            .iter()
+            .map(|attachment| (attachment.image, attachment.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attachments,
+            [
+                (msaa.0, AttachmentKind::Color),
+                (backbuffer.0, AttachmentKind::Resolve(msaa.0)),
+                (depth.0, AttachmentKind::Depth),
+            ]
+        );
+        assert_eq!(step.imported.len(), 1);
+        assert!(step.imported[0].last);
+        assert_eq!(step.imported[0].prev.layout, i::Layout::Undefined);
+        assert_eq!(
+            plan.last_states[backbuffer.0].unwrap().layout,
+            i::Layout::Present
+        );
+        assert_eq!(declarations.usage(backbuffer.0), i::Usage::COLOR_ATTACHMENT);
+    }
+
+    #[test]
+    #[should_panic(expected = "can only be used as an attachment")]
+    fn plan_rejects_sampling_imported_images() {
+        let mut declarations = Declarations::default();
+        let backbuffer = declarations.import_image(
+            "backbuffer",
+            f::Format::Bgra8Srgb,
+            i::Layout::Undefined..i::Layout::Present,
+        );
+        declarations
+            .add_pass("read", PassKind::Graphics)
+            .image(backbuffer, ImageAccess::Sampled);
+        declarations.plan();
+    }
+
+    #[test]
+    fn assign_blocks_aliases_images_with_disjoint_lifetimes() {
+        let lifetimes = vec![Some(0..1), Some(1..2), Some(2..3), Some(0..3)];
+        let images = [
+            (0, requirements(1000, 0b10)),
+            (1, requirements(2000, 0b11)),
+            (2, requirements(3000, 0b01)),
+            (3, requirements(500, 0b10)),
+        ];
+        let (blocks, assigned) = assign_blocks(&images, &lifetimes);
+
+        // 1 shares the block of the largest, 0 can't for its memory type and 3 is used
+        // while 0 is.
+        assert_eq!(assigned, [1, 0, 0, 2]);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].size, 3000);
+        assert_eq!(blocks[0].type_mask, 0b01);
+        assert_eq!(blocks[1].size, 1000);
+        assert_eq!(blocks[1].type_mask, 0b10);
+        assert_eq!(blocks[2].size, 500);
+    }
+
+    #[test]
+    fn assign_blocks_keeps_incompatible_memory_types_apart() {
+        let lifetimes = vec![Some(0..1), Some(1..2)];
+        let images = [(0, requirements(1000, 0b01)), (1, requirements(1000, 0b10))];
+        let (blocks, assigned) = assign_blocks(&images, &lifetimes);
+        assert_eq!(blocks.len(), 2);
+        assert_ne!(assigned[0], assigned[1]);
+    }
+
+    #[test]
+    fn resolve_barriers_waits_for_the_previous_image_in_a_block() {
+        let sampled = ImageState {
+            stages: pso::PipelineStage::FRAGMENT_SHADER,
+            access: i::Access::SHADER_READ,
+            layout: i::Layout::ShaderReadOnlyOptimal,
+            write: false,
+        };
+        let attachment = ImageState {
+            stages: pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            access: i::Access::COLOR_ATTACHMENT_WRITE,
+            layout: i::Layout::ColorAttachmentOptimal,
+            write: true,
+        };
+        let planned = (0..3)
+            .map(|image| PlannedBarrier {
+                image,
+                src: None,
+                dst: attachment,
+            })
+            .collect::<Vec<_>>();
+        let blocks = [Some(0), Some(0), Some(1)];
+        let lifetimes = [Some(0..2), Some(2..3), Some(2..3)];
+        let last_states = [Some(sampled), Some(sampled), Some(attachment)];
+        let barriers = resolve_barriers(&planned, &blocks, &lifetimes, &last_states);
+
+        // 1 follows 0 in the frame, while 0 and 2 follow the last use of their block in
+        // the previous frame.
+        assert_eq!(
+            barriers[1].states,
+            (i::Access::SHADER_READ, i::Layout::Undefined)
+                ..(
+                    i::Access::COLOR_ATTACHMENT_WRITE,
+                    i::Layout::ColorAttachmentOptimal
+                )
+        );
+        assert_eq!(
+            barriers[1].stages,
+            pso::PipelineStage::FRAGMENT_SHADER..pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(barriers[0].states, barriers[1].states);
+        assert_eq!(
+            barriers[2].stages,
+            pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
+                ..pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
+        );
+        assert_eq!(
+            barriers[2].states.start,
+            (i::Access::COLOR_ATTACHMENT_WRITE, i::Layout::Undefined)
+        );
+    }
+}
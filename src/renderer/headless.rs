@@ -0,0 +1,110 @@
+use gfx_hal::{
+    adapter::Adapter, buffer as b, command, format as f, image as i, memory as m, prelude::*, pso,
+    Backend,
+};
+use std::io;
+use std::iter;
+use std::path::Path;
+
+use super::buffer::Buffer;
+use super::memory::Memory;
+use super::render_target::RenderTarget;
+
+/// An offscreen stand-in for the swapchain when rendering without a window. Every frame
+/// is copied into host memory so the last one can be saved.
+pub struct Headless<'a, B: Backend> {
+    target: RenderTarget<'a, B>,
+    readback: Memory<'a, B, u8>,
+}
+
+impl<'a, B: Backend> Headless<'a, B> {
+    /// 8-bit sRGB, as written to PNG files.
+    pub const FORMAT: f::Format = f::Format::Rgba8Srgb;
+
+    pub fn new(device: &'a B::Device, adapter: &Adapter<B>, (width, height): (u32, u32)) -> Self {
+        let target = RenderTarget::new(device, adapter, (width, height), Self::FORMAT);
+        let limits = adapter.physical_device.limits();
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let buffer = Buffer::new(
+            device,
+            vec![0; width as usize * height as usize * 4],
+            b::Usage::TRANSFER_DST,
+            &limits,
+        );
+        Headless {
+            target,
+            readback: Memory::new(buffer, &memory_types),
+        }
+    }
+
+    pub fn dims(&self) -> i::Extent {
+        self.target.dims
+    }
+
+    /// The image view the frame is rendered to.
+    pub fn view(&self) -> &B::ImageView {
+        self.target.view()
+    }
+
+    /// Copies the frame into host memory. Must be recorded after the frame has been
+    /// rendered, leaving the image in `TransferSrcOptimal`.
+    pub unsafe fn record_readback(&self, cmd_buffer: &mut B::CommandBuffer) {
+        let image = self.target.image();
+        let buffer = &*self.readback.buffer.buf;
+        let range = i::SubresourceRange {
+            aspects: f::Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        };
+        cmd_buffer.pipeline_barrier(
+            pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT..pso::PipelineStage::TRANSFER,
+            m::Dependencies::empty(),
+            iter::once(m::Barrier::Image {
+                states: (
+                    i::Access::COLOR_ATTACHMENT_WRITE,
+                    i::Layout::TransferSrcOptimal,
+                )
+                    ..(i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal),
+                target: image,
+                families: None,
+                range,
+            }),
+        );
+        let dims = self.target.dims;
+        cmd_buffer.copy_image_to_buffer(
+            image,
+            i::Layout::TransferSrcOptimal,
+            buffer,
+            iter::once(command::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: dims.width,
+                buffer_height: dims.height,
+                image_layers: i::SubresourceLayers {
+                    aspects: f::Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: i::Offset::ZERO,
+                image_extent: dims,
+            }),
+        );
+        self.readback.buffer.barrier(
+            cmd_buffer,
+            pso::PipelineStage::TRANSFER..pso::PipelineStage::HOST,
+            b::Access::TRANSFER_WRITE..b::Access::HOST_READ,
+        );
+    }
+
+    /// Writes the last frame read back to `path` as a PNG. The GPU must be done with it.
+    pub fn save(&mut self, path: &Path) -> io::Result<()> {
+        self.readback.read_data();
+        let dims = self.target.dims;
+        image::save_buffer(
+            path,
+            &self.readback.buffer.content,
+            dims.width,
+            dims.height,
+            image::RGBA(8),
+        )
+    }
+}
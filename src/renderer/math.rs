@@ -0,0 +1,220 @@
+pub type Vec3 = [f32; 3];
+
+/// A column-major 4x4 matrix, laid out like a GLSL `mat4` in a uniform buffer.
+pub type Mat4 = [[f32; 4]; 4];
+
+pub const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn scale(v: Vec3, s: f32) -> Vec3 {
+    [v[0] * s, v[1] * s, v[2] * s]
+}
+
+pub fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn length(v: Vec3) -> f32 {
+    dot(v, v).sqrt()
+}
+
+/// Returns +Z for the zero vector.
+pub fn normalize(v: Vec3) -> Vec3 {
+    let len = length(v);
+    if len > 0.0 {
+        scale(v, 1.0 / len)
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
+
+/// `a * b`, applying `b` first.
+pub fn mul(a: Mat4, b: Mat4) -> Mat4 {
+    let mut m = [[0.0; 4]; 4];
+    for (col, b_col) in m.iter_mut().zip(&b) {
+        for (row, value) in col.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b_col[k]).sum();
+        }
+    }
+    m
+}
+
+pub fn transform_point(m: Mat4, [x, y, z]: Vec3) -> Vec3 {
+    let mut p = [m[3][0], m[3][1], m[3][2]];
+    for (row, value) in p.iter_mut().enumerate() {
+        *value += m[0][row] * x + m[1][row] * y + m[2][row] * z;
+    }
+    p
+}
+
+/// Ignores translation. Normals need `normal_matrix(m)` instead of `m` unless the scale
+/// is uniform.
+pub fn transform_vector(m: Mat4, [x, y, z]: Vec3) -> Vec3 {
+    let mut v = [0.0; 3];
+    for (row, value) in v.iter_mut().enumerate() {
+        *value = m[0][row] * x + m[1][row] * y + m[2][row] * z;
+    }
+    v
+}
+
+/// The determinant of the upper 3x3 of `m`, negative if it mirrors.
+pub fn determinant(m: Mat4) -> f32 {
+    let column = |i: usize| [m[i][0], m[i][1], m[i][2]];
+    dot(column(0), cross(column(1), column(2)))
+}
+
+/// The inverse transpose of the upper 3x3 of `m`, which transforms normals the way `m`
+/// transforms the surfaces they belong to. A singular `m` keeps its cofactors, which
+/// still point the right way.
+pub fn normal_matrix(m: Mat4) -> Mat4 {
+    let column = |i: usize| [m[i][0], m[i][1], m[i][2]];
+    let det = determinant(m);
+    let inv = if det != 0.0 { 1.0 / det } else { 1.0 };
+    let mut n = IDENTITY;
+    for (i, cofactor) in [
+        cross(column(1), column(2)),
+        cross(column(2), column(0)),
+        cross(column(0), column(1)),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let [x, y, z] = scale(*cofactor, inv);
+        n[i] = [x, y, z, 0.0];
+    }
+    n
+}
+
+pub fn translation([x, y, z]: Vec3) -> Mat4 {
+    let mut m = IDENTITY;
+    m[3] = [x, y, z, 1.0];
+    m
+}
+
+pub fn scaling([x, y, z]: Vec3) -> Mat4 {
+    [
+        [x, 0.0, 0.0, 0.0],
+        [0.0, y, 0.0, 0.0],
+        [0.0, 0.0, z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+/// Rotation by `angle` radians around `axis`, counter-clockwise looking down the axis.
+pub fn rotation(axis: Vec3, angle: f32) -> Mat4 {
+    let [x, y, z] = normalize(axis);
+    let (s, c) = angle.sin_cos();
+    let t = 1.0 - c;
+    [
+        [t * x * x + c, t * x * y + s * z, t * x * z - s * y, 0.0],
+        [t * x * y - s * z, t * y * y + c, t * y * z + s * x, 0.0],
+        [t * x * z + s * y, t * y * z - s * x, t * z * z + c, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+/// A right-handed view matrix: the camera at `eye` looks down its -Z axis at `target`.
+pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+    let f = normalize(sub(target, eye));
+    let s = normalize(cross(f, up));
+    let u = cross(s, f);
+    [
+        [s[0], u[0], -f[0], 0.0],
+        [s[1], u[1], -f[1], 0.0],
+        [s[2], u[2], -f[2], 0.0],
+        [-dot(s, eye), -dot(u, eye), dot(f, eye), 1.0],
+    ]
+}
+
+/// Maps view space to Vulkan clip space, where Y points down and depth runs from 0 at
+/// `near` to 1 at `far`. `fov_y` is in radians.
+pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let f = 1.0 / (fov_y / 2.0).tan();
+    [
+        [f / aspect, 0.0, 0.0, 0.0],
+        [0.0, -f, 0.0, 0.0],
+        [0.0, 0.0, far / (near - far), -1.0],
+        [0.0, 0.0, near * far / (near - far), 0.0],
+    ]
+}
+
+/// Like `perspective`, for a view volume `height` units tall and centered on the view axis.
+pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let width = height * aspect;
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0 / (near - far), 0.0],
+        [0.0, 0.0, near / (near - far), 1.0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(length(sub(a, b)) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    /// `m * p` after the perspective divide. The projections keep `w` in the last row.
+    fn project(m: Mat4, p: Vec3) -> Vec3 {
+        let w = m[0][3] * p[0] + m[1][3] * p[1] + m[2][3] * p[2] + m[3][3];
+        scale(transform_point(m, p), 1.0 / w)
+    }
+
+    #[test]
+    fn projections_map_near_and_far_to_depth_0_and_1() {
+        for proj in &[
+            perspective(1.0, 1.5, 0.1, 100.0),
+            orthographic(2.0, 1.5, 0.1, 100.0),
+        ] {
+            assert!(project(*proj, [0.0, 0.0, -0.1])[2].abs() < 1e-5);
+            assert!((project(*proj, [0.0, 0.0, -100.0])[2] - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn projections_flip_y_for_vulkan() {
+        for proj in &[
+            perspective(FRAC_PI_2, 1.0, 0.1, 100.0),
+            orthographic(2.0, 1.0, 0.1, 100.0),
+        ] {
+            let [x, y, _] = project(*proj, [0.0, 1.0, -1.0]);
+            assert_near([x, y, 0.0], [0.0, -1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn look_at_puts_the_target_on_the_negative_z_axis() {
+        let eye = [1.0, 2.0, 3.0];
+        let target = [-1.0, 0.0, 2.0];
+        let view = look_at(eye, target, [0.0, 1.0, 0.0]);
+        assert_near(transform_point(view, eye), [0.0; 3]);
+        assert_near(transform_point(view, target), [0.0, 0.0, -3.0]);
+        // +Y stays up.
+        let above = transform_point(view, add(eye, [0.0, 1.0, 0.0]));
+        assert!(above[1] > 0.0);
+    }
+}
@@ -50,17 +50,11 @@ impl<'a, B: Backend, T> Memory<'a, B, T> {
     }
 
     fn upload_type(properties: &[MemoryType], buffer_req: &m::Requirements) -> MemoryTypeId {
-        properties
-            .iter()
-            .enumerate()
-            .position(|(id, mem_type)| {
-                buffer_req.type_mask & (1 << id) != 0
-                    && mem_type
-                        .properties
-                        .contains(m::Properties::CPU_VISIBLE | m::Properties::COHERENT)
-            })
-            .unwrap()
-            .into()
+        memory_type(
+            properties,
+            buffer_req,
+            m::Properties::CPU_VISIBLE | m::Properties::COHERENT,
+        )
     }
 
     pub fn update_data(&mut self, offset: u64)
@@ -71,7 +65,7 @@ impl<'a, B: Backend, T> Memory<'a, B, T> {
 
         let upload_size = self.buffer.memory_size();
 
-        assert!(offset + upload_size as u64 <= self.size);
+        assert!(offset + upload_size <= self.size);
         let memory = &self.memory;
 
         unsafe {
@@ -86,6 +80,42 @@ impl<'a, B: Backend, T> Memory<'a, B, T> {
             device.unmap_memory(memory);
         }
     }
+
+    /// Copies the memory back into `buffer.content`, after the GPU wrote to it.
+    pub fn read_data(&mut self)
+    where
+        T: Copy,
+    {
+        let device = self.buffer.device;
+        let size = self.buffer.memory_size();
+        let memory = &self.memory;
+
+        unsafe {
+            let mapping = device.map_memory(memory, m::Segment::ALL).unwrap();
+            ptr::copy_nonoverlapping(
+                mapping,
+                self.buffer.content.as_mut_ptr() as *mut u8,
+                size as usize,
+            );
+            device.unmap_memory(memory);
+        }
+    }
+}
+
+/// Finds a memory type allowed by `req` that has all of `properties`.
+pub fn memory_type(
+    memory_types: &[MemoryType],
+    req: &m::Requirements,
+    properties: m::Properties,
+) -> MemoryTypeId {
+    memory_types
+        .iter()
+        .enumerate()
+        .position(|(id, mem_type)| {
+            req.type_mask & (1 << id) != 0 && mem_type.properties.contains(properties)
+        })
+        .unwrap()
+        .into()
 }
 
 impl<'a, B: Backend, T> Drop for Memory<'a, B, T> {
@@ -0,0 +1,377 @@
+use gfx_hal::{adapter::Adapter, buffer as b, prelude::*, Backend, IndexType};
+use std::fmt;
+use std::io;
+use std::iter;
+use std::path::Path;
+
+use super::buffer::Buffer;
+use super::geometry;
+use super::math::{
+    cross, determinant, normal_matrix, normalize, sub, transform_point, transform_vector, Mat4,
+};
+use super::memory::Memory;
+use super::vertex::MeshVertex;
+
+mod gltf;
+mod obj;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    Obj(tobj::LoadError),
+    Gltf(::gltf::Error),
+    Unsupported(String),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Io(err) => write!(fmt, "{}", err),
+            MeshError::Obj(err) => write!(fmt, "invalid OBJ file: {}", err),
+            MeshError::Gltf(err) => write!(fmt, "invalid glTF file: {}", err),
+            MeshError::Unsupported(what) => write!(fmt, "unsupported mesh: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+impl From<io::Error> for MeshError {
+    fn from(err: io::Error) -> Self {
+        MeshError::Io(err)
+    }
+}
+
+/// Which vertex attributes came from the file. Missing normals are computed from the
+/// triangles, missing UVs are zero and missing colors are the material's color, or white.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub normals: bool,
+    pub uvs: bool,
+    pub colors: bool,
+}
+
+/// An indexed triangle list.
+#[derive(Debug, Clone, Default)]
+pub struct Primitive {
+    pub name: String,
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    pub attributes: Attributes,
+}
+
+impl Primitive {
+    /// Sets every normal to the normalized sum of the normals of the triangles using the
+    /// vertex, weighted by their area.
+    pub fn compute_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = [0.0; 3];
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+            let normal = cross(sub(b, a), sub(c, a));
+            for &index in triangle {
+                let n = &mut self.vertices[index as usize].normal;
+                *n = [n[0] + normal[0], n[1] + normal[1], n[2] + normal[2]];
+            }
+        }
+        for vertex in &mut self.vertices {
+            vertex.normal = normalize(vertex.normal);
+        }
+    }
+
+    /// Moves the vertices by `m`. Normals are transformed by its inverse transpose, and
+    /// triangles are reversed if `m` mirrors, so they keep facing the same side.
+    pub fn transform(&mut self, m: Mat4) {
+        let normals = normal_matrix(m);
+        for vertex in &mut self.vertices {
+            vertex.position = transform_point(m, vertex.position);
+            vertex.normal = normalize(transform_vector(normals, vertex.normal));
+        }
+        if determinant(m) < 0.0 {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+
+    /// Fills in what `attributes` says is missing and checks the indices.
+    fn finish(mut self) -> Result<Self, MeshError> {
+        if let Some(&index) = self
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= self.vertices.len())
+        {
+            return Err(MeshError::Unsupported(format!(
+                "`{}` has index {} but only {} vertices",
+                self.name,
+                index,
+                self.vertices.len()
+            )));
+        }
+        if self.indices.len() % 3 != 0 {
+            return Err(MeshError::Unsupported(format!(
+                "`{}` has {} indices, not a triangle list",
+                self.name,
+                self.indices.len()
+            )));
+        }
+        if !self.attributes.normals {
+            self.compute_normals();
+        }
+        Ok(self)
+    }
+}
+
+/// Geometry loaded from a file, before it is uploaded.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub primitives: Vec<Primitive>,
+}
+
+impl MeshData {
+    /// Loads a Wavefront `.obj` or a glTF 2.0 `.gltf`/`.glb` file, picked by extension,
+    /// or a shape from `geometry::shape` when `path` is its name.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MeshError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let data = match extension.as_deref() {
+            Some("obj") => obj::load(path)?,
+            Some("gltf") | Some("glb") => gltf::load(path)?,
+            _ => match path.to_str().and_then(geometry::shape) {
+                Some(primitive) => MeshData {
+                    primitives: vec![primitive],
+                },
+                None => {
+                    return Err(MeshError::Unsupported(format!(
+                        "{} is neither an OBJ or glTF file nor a shape",
+                        path.display()
+                    )))
+                }
+            },
+        };
+        log::info!(
+            "loaded {} with {} primitive(s)",
+            path.display(),
+            data.primitives.len()
+        );
+        for primitive in &data.primitives {
+            log::debug!(
+                "primitive {:?}: {} vertices, {} indices, {:?}",
+                primitive.name,
+                primitive.vertices.len(),
+                primitive.indices.len(),
+                primitive.attributes
+            );
+        }
+        Ok(data)
+    }
+}
+
+/// `Object` block of `mesh.vert`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct MeshUniform {
+    pub model: Mat4,
+    pub normal: Mat4,
+}
+
+impl MeshUniform {
+    pub fn new(model: Mat4) -> Self {
+        MeshUniform {
+            model,
+            normal: normal_matrix(model),
+        }
+    }
+}
+
+/// A primitive's vertex and index buffers.
+pub struct MeshBuffers<'a, B: Backend> {
+    pub vertices: Memory<'a, B, MeshVertex>,
+    pub indices: Memory<'a, B, u32>,
+}
+
+/// Loaded geometry in GPU buffers, one pair per primitive.
+pub struct Mesh<'a, B: Backend> {
+    pub primitives: Vec<MeshBuffers<'a, B>>,
+}
+
+impl<'a, B: Backend> Mesh<'a, B> {
+    pub fn load<P: AsRef<Path>>(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        path: P,
+    ) -> Result<Self, MeshError> {
+        Ok(Self::new(device, adapter, &MeshData::load(path)?))
+    }
+
+    /// Uploads every non-empty primitive of `data`.
+    pub fn new(device: &'a B::Device, adapter: &Adapter<B>, data: &MeshData) -> Self {
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let limits = adapter.physical_device.limits();
+        let primitives = data
+            .primitives
+            .iter()
+            .filter(|primitive| !primitive.indices.is_empty())
+            .map(|primitive| {
+                let vertices = Buffer::new(
+                    device,
+                    primitive.vertices.clone(),
+                    b::Usage::VERTEX,
+                    &limits,
+                );
+                let indices =
+                    Buffer::new(device, primitive.indices.clone(), b::Usage::INDEX, &limits);
+                MeshBuffers {
+                    vertices: Memory::new(vertices, &memory_types),
+                    indices: Memory::new(indices, &memory_types),
+                }
+            })
+            .collect();
+        Mesh { primitives }
+    }
+
+    /// Draws every primitive with the bound pipeline, which must take `MeshVertex`.
+    pub unsafe fn draw(&self, cmd_buffer: &mut B::CommandBuffer) {
+        for primitive in &self.primitives {
+            cmd_buffer.bind_vertex_buffers(
+                0,
+                iter::once((&*primitive.vertices.buffer.buf, b::SubRange::WHOLE)),
+            );
+            cmd_buffer.bind_index_buffer(b::IndexBufferView {
+                buffer: &primitive.indices.buffer.buf,
+                range: b::SubRange::WHOLE,
+                index_type: IndexType::U32,
+            });
+            let count = primitive.indices.buffer.content.len() as u32;
+            cmd_buffer.draw_indexed(0..count, 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::math::{mul, rotation, scaling, translation};
+
+    fn vertex(position: [f32; 3]) -> MeshVertex {
+        MeshVertex {
+            position,
+            ..MeshVertex::default()
+        }
+    }
+
+    /// Two triangles of the unit square in the XY plane, facing +Z.
+    fn square() -> Primitive {
+        Primitive {
+            name: "square".to_owned(),
+            vertices: vec![
+                vertex([0.0, 0.0, 0.0]),
+                vertex([1.0, 0.0, 0.0]),
+                vertex([1.0, 1.0, 0.0]),
+                vertex([0.0, 1.0, 0.0]),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            attributes: Attributes::default(),
+        }
+    }
+
+    fn assert_near(a: [f32; 3], b: [f32; 3]) {
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn compute_normals_follows_the_winding() {
+        let mut square = square();
+        square.compute_normals();
+        for vertex in &square.vertices {
+            assert_near(vertex.normal, [0.0, 0.0, 1.0]);
+        }
+        square.indices = vec![0, 2, 1, 0, 3, 2];
+        square.compute_normals();
+        assert_near(square.vertices[0].normal, [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn compute_normals_weights_triangles_by_area() {
+        // A large triangle facing +Z and a small one facing +X share vertex 0.
+        let mut primitive = Primitive {
+            vertices: vec![
+                vertex([0.0, 0.0, 0.0]),
+                vertex([4.0, 0.0, 0.0]),
+                vertex([0.0, 4.0, 0.0]),
+                vertex([0.0, 1.0, 0.0]),
+                vertex([0.0, 0.0, 1.0]),
+            ],
+            indices: vec![0, 1, 2, 0, 3, 4],
+            ..Primitive::default()
+        };
+        primitive.compute_normals();
+        assert_near(primitive.vertices[0].normal, normalize([1.0, 0.0, 16.0]));
+        assert_near(primitive.vertices[1].normal, [0.0, 0.0, 1.0]);
+        assert_near(primitive.vertices[3].normal, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn finish_computes_missing_normals_only() {
+        let square = square().finish().unwrap();
+        assert_near(square.vertices[2].normal, [0.0, 0.0, 1.0]);
+
+        let mut given = square.clone();
+        given.attributes.normals = true;
+        given.vertices[2].normal = [1.0, 0.0, 0.0];
+        assert_eq!(given.finish().unwrap().vertices[2].normal, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn finish_rejects_bad_indices() {
+        let mut out_of_range = square();
+        out_of_range.indices[4] = 4;
+        assert!(matches!(
+            out_of_range.finish(),
+            Err(MeshError::Unsupported(_))
+        ));
+        let mut partial = square();
+        partial.indices.pop();
+        assert!(matches!(partial.finish(), Err(MeshError::Unsupported(_))));
+    }
+
+    #[test]
+    fn transform_uses_the_inverse_transpose_for_normals() {
+        // A slope at 45 degrees, squashed to half its height.
+        let mut primitive = Primitive {
+            vertices: vec![MeshVertex {
+                position: [1.0, 1.0, 0.0],
+                normal: normalize([-1.0, 1.0, 0.0]),
+                ..MeshVertex::default()
+            }],
+            ..Primitive::default()
+        };
+        primitive.transform(mul(translation([0.0, 0.0, 2.0]), scaling([1.0, 0.5, 1.0])));
+        assert_near(primitive.vertices[0].position, [1.0, 0.5, 2.0]);
+        assert_near(primitive.vertices[0].normal, normalize([-1.0, 2.0, 0.0]));
+
+        let mut square = square().finish().unwrap();
+        square.transform(rotation([1.0, 0.0, 0.0], std::f32::consts::FRAC_PI_2));
+        assert_near(square.vertices[0].normal, [0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn transform_keeps_mirrored_triangles_facing_out() {
+        let mut square = square().finish().unwrap();
+        square.transform(scaling([1.0, 1.0, -1.0]));
+        assert_near(square.vertices[0].normal, [0.0, 0.0, -1.0]);
+        assert_eq!(square.indices, [0, 2, 1, 0, 3, 2]);
+        // The computed normals agree with the transformed ones.
+        let transformed = square.vertices.clone();
+        square.compute_normals();
+        for (a, b) in square.vertices.iter().zip(&transformed) {
+            assert_near(a.normal, b.normal);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use ::gltf::{buffer, mesh::Mode, Document, Node};
+use std::path::Path;
+
+use super::{Attributes, MeshData, MeshError, MeshVertex, Primitive};
+use crate::renderer::math::{mul, Mat4, IDENTITY};
+
+/// Loads the triangle primitives of every mesh instance in the default scene, with node
+/// transforms applied by `Primitive::transform`. Files without scenes load every mesh
+/// untransformed. Vertices without colors take the material's base color.
+pub fn load(path: &Path) -> Result<MeshData, MeshError> {
+    let (document, buffers, _images) = ::gltf::import(path).map_err(MeshError::Gltf)?;
+    let mut data = MeshData::default();
+
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                load_node(&node, IDENTITY, &buffers, &mut data)?;
+            }
+        }
+        None => load_meshes(&document, &buffers, &mut data)?,
+    }
+    Ok(data)
+}
+
+fn load_node(
+    node: &Node,
+    parent: Mat4,
+    buffers: &[buffer::Data],
+    data: &mut MeshData,
+) -> Result<(), MeshError> {
+    let transform = mul(parent, node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        let name = node.name().or_else(|| mesh.name()).unwrap_or("");
+        for primitive in mesh.primitives() {
+            if let Some(primitive) = load_primitive(name, &primitive, transform, buffers)? {
+                data.primitives.push(primitive);
+            }
+        }
+    }
+    for child in node.children() {
+        load_node(&child, transform, buffers, data)?;
+    }
+    Ok(())
+}
+
+fn load_meshes(
+    document: &Document,
+    buffers: &[buffer::Data],
+    data: &mut MeshData,
+) -> Result<(), MeshError> {
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            let name = mesh.name().unwrap_or("");
+            if let Some(primitive) = load_primitive(name, &primitive, IDENTITY, buffers)? {
+                data.primitives.push(primitive);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns `None` for primitives that aren't triangle lists.
+fn load_primitive(
+    name: &str,
+    primitive: &::gltf::Primitive,
+    transform: Mat4,
+    buffers: &[buffer::Data],
+) -> Result<Option<Primitive>, MeshError> {
+    if primitive.mode() != Mode::Triangles {
+        log::warn!(
+            "skipping primitive {} of `{}` drawn as {:?}",
+            primitive.index(),
+            name,
+            primitive.mode()
+        );
+        return Ok(None);
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions = reader
+        .read_positions()
+        .ok_or_else(|| MeshError::Unsupported(format!("`{}` has no positions", name)))?;
+    let base_color = primitive
+        .material()
+        .pbr_metallic_roughness()
+        .base_color_factor();
+    let mut vertices = positions
+        .map(|position| MeshVertex {
+            position,
+            color: base_color,
+            ..MeshVertex::default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut attributes = Attributes::default();
+    if let Some(normals) = reader.read_normals() {
+        attributes.normals = true;
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            vertex.normal = normal;
+        }
+    }
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        attributes.uvs = true;
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv = uv;
+        }
+    }
+    if let Some(colors) = reader.read_colors(0) {
+        attributes.colors = true;
+        for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = color;
+        }
+    }
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    let mut primitive = Primitive {
+        name: name.to_owned(),
+        vertices,
+        indices,
+        attributes,
+    };
+    primitive.transform(transform);
+    primitive.finish().map(Some)
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use super::{Attributes, MeshData, MeshError, MeshVertex, Primitive};
+
+/// Loads every object of an OBJ file as a primitive, triangulating faces. Vertices
+/// without colors take the diffuse color of their material.
+pub fn load(path: &Path) -> Result<MeshData, MeshError> {
+    let (models, materials) =
+        tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(MeshError::Obj)?;
+    let materials = materials.unwrap_or_else(|err| {
+        log::warn!("can't load materials of {}: {}", path.display(), err);
+        vec![]
+    });
+
+    let primitives = models
+        .into_iter()
+        .map(|model| {
+            let mesh = model.mesh;
+            let attributes = Attributes {
+                normals: !mesh.normals.is_empty(),
+                uvs: !mesh.texcoords.is_empty(),
+                colors: !mesh.vertex_color.is_empty(),
+            };
+            let diffuse = mesh
+                .material_id
+                .and_then(|id| materials.get(id))
+                .map_or([1.0; 3], |material| material.diffuse);
+
+            let vertices = (0..mesh.positions.len() / 3)
+                .map(|i| {
+                    let vec3 = |data: &[f32]| [data[3 * i], data[3 * i + 1], data[3 * i + 2]];
+                    let color = if attributes.colors {
+                        vec3(&mesh.vertex_color)
+                    } else {
+                        diffuse
+                    };
+                    MeshVertex {
+                        position: vec3(&mesh.positions),
+                        normal: if attributes.normals {
+                            vec3(&mesh.normals)
+                        } else {
+                            [0.0; 3]
+                        },
+                        // OBJ puts v = 0 at the bottom of the image.
+                        uv: if attributes.uvs {
+                            [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]]
+                        } else {
+                            [0.0; 2]
+                        },
+                        color: [color[0], color[1], color[2], 1.0],
+                    }
+                })
+                .collect();
+
+            Primitive {
+                name: model.name,
+                vertices,
+                indices: mesh.indices,
+                attributes,
+            }
+            .finish()
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(MeshData { primitives })
+}
@@ -0,0 +1,576 @@
+use gfx_hal::{adapter::Adapter, buffer as b, format as f, image as i, prelude::*, pso, Backend};
+use std::iter;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::buffer::Buffer;
+use super::descriptor_set::{DescriptorLayout, DescriptorSet};
+use super::math::{Mat4, Vec3};
+use super::memory::Memory;
+use super::pipeline::{ComputePipeline, Pipeline, PipelineOptions};
+use super::reflect::ShaderInfo;
+use super::shader;
+use super::vertex::VertexLayout;
+
+/// Vertices per particle quad, generated in `particle.vert`.
+const QUAD_VERTICES: u32 = 6;
+
+/// A particle as stored in the emitter's buffer and read as instance data. It is dead
+/// once its age reaches its lifetime.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Particle {
+    pub position: [f32; 3],
+    pub age: f32,
+    pub velocity: [f32; 3],
+    pub lifetime: f32,
+}
+
+impl VertexLayout for Particle {
+    fn elements() -> Vec<pso::Element<f::Format>> {
+        vec![
+            pso::Element {
+                format: f::Format::Rgba32Sfloat,
+                offset: 0,
+            },
+            pso::Element {
+                format: f::Format::Rgba32Sfloat,
+                offset: 16,
+            },
+        ]
+    }
+}
+
+/// Values keyed on a particle's normalized age, linearly interpolated in between.
+#[derive(Debug, Clone)]
+pub struct Gradient<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Copy> Gradient<T> {
+    /// Keys the shaders can evaluate.
+    pub const MAX_KEYS: usize = 4;
+
+    /// Creates a gradient from `(time, value)` keys with times in `0.0..=1.0`.
+    ///
+    /// Panics if there are no keys or more than `MAX_KEYS`.
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        assert!(
+            !keys.is_empty() && keys.len() <= Self::MAX_KEYS,
+            "A gradient needs 1 to {} keys",
+            Self::MAX_KEYS
+        );
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Gradient { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    /// Times and values padded to `MAX_KEYS` by repeating the last key.
+    fn pack(&self) -> ([f32; 4], [T; 4]) {
+        let key = |i: usize| self.keys[i.min(self.keys.len() - 1)];
+        (
+            [key(0).0, key(1).0, key(2).0, key(3).0],
+            [key(0).1, key(1).1, key(2).1, key(3).1],
+        )
+    }
+}
+
+/// Where an emitter's particles are advanced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simulation {
+    /// On the CPU, uploading the whole buffer every frame.
+    Cpu,
+    /// In a compute shader recorded by `ParticleSystem::record_compute`.
+    Gpu,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmitterDesc {
+    pub position: [f32; 3],
+    /// Particles spawned per second.
+    pub spawn_rate: f32,
+    /// Range each particle's lifetime in seconds is picked from.
+    pub lifetime: Range<f32>,
+    pub velocity: [f32; 3],
+    /// Largest random offset added to each component of `velocity`.
+    pub spread: f32,
+    /// Acceleration applied to every particle.
+    pub gravity: [f32; 3],
+    pub color: Gradient<[f32; 4]>,
+    pub size: Gradient<f32>,
+    /// Particles alive at once. Spawning reuses the oldest slot, so this should be at
+    /// least `spawn_rate` times the longest lifetime.
+    pub capacity: u32,
+    pub simulation: Simulation,
+}
+
+impl Default for EmitterDesc {
+    fn default() -> Self {
+        EmitterDesc {
+            position: [0.0; 3],
+            spawn_rate: 100.0,
+            lifetime: 1.0..2.0,
+            velocity: [0.0, -0.5, 0.0],
+            spread: 0.1,
+            gravity: [0.0; 3],
+            color: Gradient::new(vec![(0.0, [1.0; 4]), (1.0, [1.0, 1.0, 1.0, 0.0])]),
+            size: Gradient::constant(0.05),
+            capacity: 256,
+            simulation: Simulation::Gpu,
+        }
+    }
+}
+
+/// The transform particles are drawn with and the world-space directions their quads
+/// are spanned by, usually the camera's right and up vectors.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleView {
+    pub view_proj: Mat4,
+    pub right: Vec3,
+    pub up: Vec3,
+}
+
+/// `Params` of `particle.vert`.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct DrawParams {
+    view_proj: Mat4,
+    right: [f32; 4],
+    up: [f32; 4],
+    colors: [[f32; 4]; 4],
+    color_times: [f32; 4],
+    sizes: [f32; 4],
+    size_times: [f32; 4],
+}
+
+/// `Params` of `simulate.comp`.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct SimulateParams {
+    origin_dt: [f32; 4],
+    velocity_spread: [f32; 4],
+    gravity: [f32; 4],
+    lifetime: [f32; 4],
+    /// First slot to spawn into, spawn count, capacity and random seed.
+    spawn: [u32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmitterId(usize);
+
+/// The buffers an emitter uses for one frame in flight.
+struct EmitterFrame<'a, B: Backend> {
+    draw_params: Memory<'a, B, DrawParams>,
+    simulate_params: Memory<'a, B, SimulateParams>,
+    draw_set: DescriptorSet<'a, B>,
+    simulate_set: DescriptorSet<'a, B>,
+}
+
+pub struct Emitter<'a, B: Backend> {
+    pub desc: EmitterDesc,
+    /// A single buffer the compute shader advances in place for GPU emitters, one per
+    /// frame slot for CPU emitters.
+    particles: Vec<Memory<'a, B, Particle>>,
+    /// The particles of CPU emitters, copied into the frame's buffer after each step.
+    state: Vec<Particle>,
+    /// By frame slot.
+    frames: Vec<EmitterFrame<'a, B>>,
+    /// Next slot of the ring buffer to spawn into.
+    next: u32,
+    /// Fraction of a particle carried over to the next frame's spawn count.
+    pending: f32,
+    rng: Rng,
+}
+
+impl<'a, B: Backend> Emitter<'a, B> {
+    /// Advances the emitter by `dt` seconds and uploads the parameters into frame `slot`.
+    fn update(&mut self, slot: usize, dt: f32, view: &ParticleView) {
+        let capacity = self.desc.capacity;
+        self.pending += self.desc.spawn_rate * dt;
+        let count = (self.pending as u32).min(capacity);
+        self.pending = self.pending.fract();
+        let first = self.next;
+        self.next = (self.next + count) % capacity;
+
+        let frame = &mut self.frames[slot];
+        match self.desc.simulation {
+            Simulation::Cpu => {
+                simulate(&self.desc, &mut self.state, first, count, dt, &mut self.rng);
+                let particles = &mut self.particles[slot];
+                particles.buffer.content.copy_from_slice(&self.state);
+                particles.update_data(0);
+            }
+            Simulation::Gpu => {
+                let seed = self.rng.bits();
+                let desc = &self.desc;
+                frame.simulate_params.buffer.content[0] = SimulateParams {
+                    origin_dt: extend(desc.position, dt),
+                    velocity_spread: extend(desc.velocity, desc.spread),
+                    gravity: extend(desc.gravity, 0.0),
+                    lifetime: [desc.lifetime.start, desc.lifetime.end, 0.0, 0.0],
+                    spawn: [first, count, capacity, seed],
+                };
+                frame.simulate_params.update_data(0);
+            }
+        }
+
+        let (color_times, colors) = self.desc.color.pack();
+        let (size_times, sizes) = self.desc.size.pack();
+        frame.draw_params.buffer.content[0] = DrawParams {
+            view_proj: view.view_proj,
+            right: extend(view.right, 0.0),
+            up: extend(view.up, 0.0),
+            colors,
+            color_times,
+            sizes,
+            size_times,
+        };
+        frame.draw_params.update_data(0);
+    }
+
+    /// The buffer frame `slot` draws from.
+    fn particles(&self, slot: usize) -> &Memory<'a, B, Particle> {
+        &self.particles[slot.min(self.particles.len() - 1)]
+    }
+}
+
+/// The CPU version of `simulate.comp`: spawns `count` particles into the ring buffer
+/// starting at `first` and advances the others by `dt` seconds.
+fn simulate(
+    desc: &EmitterDesc,
+    particles: &mut [Particle],
+    first: u32,
+    count: u32,
+    dt: f32,
+    rng: &mut Rng,
+) {
+    let capacity = desc.capacity;
+    for (i, p) in (0..capacity).zip(particles.iter_mut()) {
+        if (i + capacity - first) % capacity < count {
+            let mut velocity = desc.velocity;
+            for v in &mut velocity {
+                *v += (rng.next() * 2.0 - 1.0) * desc.spread;
+            }
+            let t = rng.next();
+            let lifetime = &desc.lifetime;
+            *p = Particle {
+                position: desc.position,
+                age: 0.0,
+                velocity,
+                lifetime: lifetime.start + (lifetime.end - lifetime.start) * t,
+            };
+        } else if p.age < p.lifetime {
+            for axis in 0..3 {
+                p.velocity[axis] += desc.gravity[axis] * dt;
+                p.position[axis] += p.velocity[axis] * dt;
+            }
+            p.age += dt;
+        }
+    }
+}
+
+/// xorshift32.
+#[derive(Debug, Clone, Copy)]
+struct Rng(u32);
+
+impl Rng {
+    fn bits(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// Uniform in `0.0..1.0`.
+    fn next(&mut self) -> f32 {
+        (self.bits() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Particle emitters drawn as instanced camera-facing quads with additive blending.
+///
+/// Each frame, call `update` once after the previous frame's submission has completed,
+/// then record `record_compute` outside any render pass and `draw` inside a render pass
+/// compatible with the one given to `new`.
+pub struct ParticleSystem<'a, B: Backend> {
+    device: &'a B::Device,
+    adapter: &'a Adapter<B>,
+    draw_layout: Rc<DescriptorLayout<'a, B>>,
+    simulate_layout: Rc<DescriptorLayout<'a, B>>,
+    pipeline: Pipeline<'a, B>,
+    simulate: ComputePipeline<'a, B>,
+    frames_in_flight: usize,
+    emitters: Vec<Emitter<'a, B>>,
+}
+
+impl<'a, B: Backend> ParticleSystem<'a, B> {
+    pub fn new(
+        device: &'a B::Device,
+        adapter: &'a Adapter<B>,
+        frames_in_flight: usize,
+        render_pass: &B::RenderPass,
+        samples: i::NumSamples,
+    ) -> Self {
+        let draw_infos = [&shader::PARTICLE_VERT, &shader::PARTICLE_FRAG]
+            .iter()
+            .map(|shader| shader.reflect())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let simulate_info = shader::PARTICLE_SIMULATE_COMP
+            .reflect()
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        // Every emitter's sets share these layouts, so the pipelines are compatible with all.
+        let draw_layout = Self::create_layout(device, &draw_infos.iter().collect::<Vec<_>>());
+        let simulate_layout = Self::create_layout(device, &[&simulate_info]);
+        let draw_set = DescriptorSet::new(&draw_layout);
+        let simulate_set = DescriptorSet::new(&simulate_layout);
+        let pipeline = Pipeline::new::<Particle>(
+            device,
+            &shader::PARTICLE_VERT,
+            &shader::PARTICLE_FRAG,
+            render_pass,
+            &[&draw_set],
+            PipelineOptions {
+                blend: Some(pso::BlendState::ADD),
+                vertex_rate: pso::VertexInputRate::Instance(1),
+                depth: Some(pso::DepthTest {
+                    fun: pso::Comparison::LessEqual,
+                    write: false,
+                }),
+                samples,
+                ..PipelineOptions::default()
+            },
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        let simulate = ComputePipeline::new(
+            device,
+            &shader::PARTICLE_SIMULATE_COMP,
+            &[&simulate_set],
+            pso::Specialization::default(),
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        ParticleSystem {
+            device,
+            adapter,
+            draw_layout,
+            simulate_layout,
+            pipeline,
+            simulate,
+            frames_in_flight,
+            emitters: vec![],
+        }
+    }
+
+    pub fn add_emitter(&mut self, desc: EmitterDesc) -> EmitterId {
+        assert_ne!(desc.capacity, 0, "Emitter capacity must not be 0");
+        let memory_types = self
+            .adapter
+            .physical_device
+            .memory_properties()
+            .memory_types;
+        let limits = self.adapter.physical_device.limits();
+        let buffers = match desc.simulation {
+            Simulation::Cpu => self.frames_in_flight,
+            Simulation::Gpu => 1,
+        };
+        let state = vec![Particle::default(); desc.capacity as usize];
+        let particles = (0..buffers)
+            .map(|_| {
+                let buffer = Buffer::new(
+                    self.device,
+                    state.clone(),
+                    b::Usage::STORAGE | b::Usage::VERTEX,
+                    &limits,
+                );
+                Memory::new(buffer, &memory_types)
+            })
+            .collect::<Vec<_>>();
+        let frames = (0..self.frames_in_flight)
+            .map(|slot| {
+                let draw_params = Buffer::new(
+                    self.device,
+                    vec![DrawParams::default()],
+                    b::Usage::UNIFORM,
+                    &limits,
+                );
+                let draw_params = Memory::new(draw_params, &memory_types);
+                let simulate_params = Buffer::new(
+                    self.device,
+                    vec![SimulateParams::default()],
+                    b::Usage::UNIFORM,
+                    &limits,
+                );
+                let simulate_params = Memory::new(simulate_params, &memory_types);
+
+                let mut draw_set = DescriptorSet::new(&self.draw_layout);
+                draw_set.write_buffer(0, &draw_params.buffer);
+                let mut simulate_set = DescriptorSet::new(&self.simulate_layout);
+                simulate_set.write_buffer(0, &particles[slot.min(buffers - 1)].buffer);
+                simulate_set.write_buffer(1, &simulate_params.buffer);
+                EmitterFrame {
+                    draw_params,
+                    simulate_params,
+                    draw_set,
+                    simulate_set,
+                }
+            })
+            .collect();
+
+        self.emitters.push(Emitter {
+            desc,
+            particles,
+            state,
+            frames,
+            next: 0,
+            pending: 0.0,
+            rng: Rng(0x9e37_79b9 ^ self.emitters.len() as u32),
+        });
+        EmitterId(self.emitters.len() - 1)
+    }
+
+    /// The emitter's settings, which take effect on the next `update`. `capacity` and
+    /// `simulation` can't be changed after the emitter is added.
+    pub fn emitter_mut(&mut self, id: EmitterId) -> &mut EmitterDesc {
+        &mut self.emitters[id.0].desc
+    }
+
+    /// Advances every emitter by `dt` seconds and uploads the result into frame `slot`.
+    /// The GPU must be done with the frame that last used the slot, and every recorded
+    /// `record_compute` runs the step once.
+    pub fn update(&mut self, slot: usize, dt: f32, view: &ParticleView) {
+        for emitter in &mut self.emitters {
+            emitter.update(slot, dt, view);
+        }
+    }
+
+    /// Every emitter's particle buffer for frame `slot` and where it is simulated, in the
+    /// order they were added. GPU emitters' buffers are written by `record_compute` and
+    /// all are read as vertices by `draw`.
+    pub fn particle_buffers(&self, slot: usize) -> Vec<(&B::Buffer, Simulation)> {
+        self.emitters
+            .iter()
+            .map(|emitter| {
+                (
+                    &*emitter.particles(slot).buffer.buf,
+                    emitter.desc.simulation,
+                )
+            })
+            .collect()
+    }
+
+    /// Records the simulation of GPU emitters for frame `slot`. Must be recorded outside
+    /// any render pass and followed by a barrier ordering the writes before `draw` reads
+    /// them.
+    pub unsafe fn record_compute(&self, slot: usize, cmd_buffer: &mut B::CommandBuffer) {
+        for emitter in &self.emitters {
+            if emitter.desc.simulation != Simulation::Gpu {
+                continue;
+            }
+            emitter
+                .particles(slot)
+                .buffer
+                .vertex_to_compute_barrier(cmd_buffer);
+            self.simulate.dispatch(
+                cmd_buffer,
+                &[&emitter.frames[slot].simulate_set],
+                emitter.desc.capacity,
+            );
+        }
+    }
+
+    /// Draws every emitter's particles for frame `slot`. Must be recorded inside a render
+    /// pass compatible with the one given to `new`, with the viewport already set.
+    pub unsafe fn draw(&self, slot: usize, cmd_buffer: &mut B::CommandBuffer) {
+        cmd_buffer.bind_graphics_pipeline(&self.pipeline.pipeline);
+        for emitter in &self.emitters {
+            cmd_buffer.bind_vertex_buffers(
+                0,
+                iter::once((&*emitter.particles(slot).buffer.buf, b::SubRange::WHOLE)),
+            );
+            cmd_buffer.bind_graphics_descriptor_sets(
+                &self.pipeline.pipeline_layout,
+                0,
+                emitter.frames[slot].draw_set.raw(),
+                &[],
+            );
+            cmd_buffer.draw(0..QUAD_VERTICES, 0..emitter.desc.capacity);
+        }
+    }
+
+    fn create_layout(device: &'a B::Device, infos: &[&ShaderInfo]) -> Rc<DescriptorLayout<'a, B>> {
+        DescriptorLayout::from_shaders(device, infos, 0).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+fn extend([x, y, z]: Vec3, w: f32) -> [f32; 4] {
+    [x, y, z, w]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc() -> EmitterDesc {
+        EmitterDesc {
+            position: [1.0, 2.0, 3.0],
+            lifetime: 1.0..2.0,
+            velocity: [0.0, 1.0, 0.0],
+            spread: 0.0,
+            gravity: [0.0, -10.0, 0.0],
+            capacity: 4,
+            simulation: Simulation::Cpu,
+            ..EmitterDesc::default()
+        }
+    }
+
+    #[test]
+    fn simulate_spawns_into_the_ring_buffer_and_wraps() {
+        let desc = desc();
+        let mut particles = vec![Particle::default(); 4];
+        let mut rng = Rng(1);
+        simulate(&desc, &mut particles, 3, 2, 0.1, &mut rng);
+        for &i in &[3, 0] {
+            let p = particles[i];
+            assert_eq!(p.position, desc.position);
+            assert_eq!(p.velocity, desc.velocity);
+            assert_eq!(p.age, 0.0);
+            assert!(desc.lifetime.start <= p.lifetime && p.lifetime < desc.lifetime.end);
+        }
+        for &i in &[1, 2] {
+            assert_eq!(particles[i].lifetime, 0.0);
+        }
+    }
+
+    #[test]
+    fn simulate_advances_living_particles_and_leaves_dead_ones() {
+        let desc = desc();
+        let alive = Particle {
+            position: [0.0; 3],
+            age: 0.5,
+            velocity: [1.0, 0.0, 0.0],
+            lifetime: 1.0,
+        };
+        let dead = Particle { age: 1.0, ..alive };
+        let mut particles = vec![alive, dead, alive, alive];
+        simulate(&desc, &mut particles, 0, 0, 0.5, &mut Rng(1));
+        assert_eq!(particles[0].velocity, [1.0, -5.0, 0.0]);
+        assert_eq!(particles[0].position, [0.5, -2.5, 0.0]);
+        assert_eq!(particles[0].age, 1.0);
+        assert_eq!(particles[1].position, dead.position);
+        assert_eq!(particles[1].age, dead.age);
+    }
+
+    #[test]
+    fn rng_stays_in_the_unit_interval() {
+        let mut rng = Rng(0x9e37_79b9);
+        for _ in 0..1000 {
+            let x = rng.next();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
@@ -1,11 +1,42 @@
-use gfx_hal::{format as f, pass::Subpass, prelude::*, pso, Backend};
-use std::fs::read;
-use std::io::Cursor;
+use gfx_hal::{image as i, pass::Subpass, prelude::*, pso, Backend};
 use std::mem::{self, ManuallyDrop};
 use std::ptr;
 
+use super::descriptor_set::DescriptorSet;
+use super::reflect::{Interface, ShaderError, ShaderInfo};
+use super::shader::Shader;
+use super::vertex::VertexLayout;
+
 const ENTRY_NAME: &str = "main";
 
+/// Fixed-function state and specialization of a graphics `Pipeline`.
+#[derive(Debug, Clone)]
+pub struct PipelineOptions<'s> {
+    pub vs_specialization: pso::Specialization<'s>,
+    pub primitive: pso::Primitive,
+    /// `None` writes colors unblended.
+    pub blend: Option<pso::BlendState>,
+    /// Per-vertex or per-instance stepping of the vertex buffer.
+    pub vertex_rate: pso::VertexInputRate,
+    /// Requires a depth attachment in the render pass.
+    pub depth: Option<pso::DepthTest>,
+    /// Must match the samples of the render pass attachments.
+    pub samples: i::NumSamples,
+}
+
+impl<'s> Default for PipelineOptions<'s> {
+    fn default() -> Self {
+        PipelineOptions {
+            vs_specialization: pso::Specialization::default(),
+            primitive: pso::Primitive::TriangleList,
+            blend: Some(pso::BlendState::ALPHA),
+            vertex_rate: pso::VertexInputRate::Vertex,
+            depth: None,
+            samples: 1,
+        }
+    }
+}
+
 pub struct Pipeline<'a, B: Backend> {
     device: &'a B::Device,
     pub pipeline: ManuallyDrop<B::GraphicsPipeline>,
@@ -13,31 +44,71 @@ pub struct Pipeline<'a, B: Backend> {
 }
 
 impl<'a, B: Backend> Pipeline<'a, B> {
-    pub fn new<T>(
+    /// Creates a graphics pipeline after checking the shaders' inputs, bindings,
+    /// push constants and specialization constants against `T` and `descriptor_sets`.
+    pub fn new<T: VertexLayout>(
         device: &'a B::Device,
-        vs_path: &str,
-        fs_path: &str,
+        vs: &Shader,
+        fs: &Shader,
         render_pass: &B::RenderPass,
-        set_layout: Option<&B::DescriptorSetLayout>,
-    ) -> Self {
+        descriptor_sets: &[&DescriptorSet<'a, B>],
+        options: PipelineOptions,
+    ) -> Result<Self, ShaderError> {
+        let push_constants = [];
+        let vs_specialization = options.vs_specialization;
+        let fs_specialization = pso::Specialization::default();
+
+        let attributes = T::elements()
+            .into_iter()
+            .enumerate()
+            .map(|(location, element)| pso::AttributeDesc {
+                location: location as u32,
+                binding: 0,
+                element,
+            })
+            .collect::<Vec<_>>();
+        let sets = descriptor_sets
+            .iter()
+            .map(|set| (&set.layout().bindings[..], &set.buffer_sizes[..]))
+            .collect::<Vec<_>>();
+
+        let vs_spirv = vs.spirv()?;
+        let fs_spirv = fs.spirv()?;
+        for (shader, spirv, specialization) in &[
+            (vs, &vs_spirv, &vs_specialization),
+            (fs, &fs_spirv, &fs_specialization),
+        ] {
+            ShaderInfo::reflect(shader.name, spirv)?.validate(&Interface {
+                attributes: &attributes,
+                sets: &sets,
+                push_constants: &push_constants,
+                specialization,
+            })?;
+        }
+
         let pipeline_layout = ManuallyDrop::new(
-            unsafe { device.create_pipeline_layout(set_layout, &[]) }
-                .expect("Can't create pipeline layout"),
+            unsafe {
+                device.create_pipeline_layout(
+                    descriptor_sets.iter().map(|set| &*set.layout().raw),
+                    &push_constants,
+                )
+            }
+            .expect("Can't create pipeline layout"),
         );
 
-        let vs_module = Self::load_spirv(device, vs_path);
-        let fs_module = Self::load_spirv(device, fs_path);
+        let vs_module = unsafe { device.create_shader_module(&vs_spirv) }.unwrap();
+        let fs_module = unsafe { device.create_shader_module(&fs_spirv) }.unwrap();
 
         let (vs_entry, fs_entry) = (
             pso::EntryPoint {
                 entry: ENTRY_NAME,
                 module: &vs_module,
-                specialization: gfx_hal::spec_const_list![0 => 0.8f32],
+                specialization: vs_specialization,
             },
             pso::EntryPoint {
                 entry: ENTRY_NAME,
                 module: &fs_module,
-                specialization: pso::Specialization::default(),
+                specialization: fs_specialization,
             },
         );
 
@@ -51,43 +122,40 @@ impl<'a, B: Backend> Pipeline<'a, B> {
 
         let subpass = Subpass {
             index: 0,
-            main_pass: &*render_pass,
+            main_pass: render_pass,
         };
 
         let mut pipeline_desc = pso::GraphicsPipelineDesc::new(
             shader_entries,
-            pso::Primitive::TriangleList,
+            options.primitive,
             pso::Rasterizer::FILL,
             &*pipeline_layout,
             subpass,
         );
         pipeline_desc.blender.targets.push(pso::ColorBlendDesc {
             mask: pso::ColorMask::ALL,
-            blend: Some(pso::BlendState::ALPHA),
+            blend: options.blend,
         });
+        pipeline_desc.depth_stencil.depth = options.depth;
+        if options.samples > 1 {
+            pipeline_desc.multisampling = Some(pso::Multisampling {
+                rasterization_samples: options.samples,
+                sample_shading: None,
+                sample_mask: !0,
+                alpha_coverage: false,
+                alpha_to_one: false,
+            });
+        }
 
-        pipeline_desc.vertex_buffers.push(pso::VertexBufferDesc {
-            binding: 0,
-            stride: mem::size_of::<T>() as u32,
-            rate: pso::VertexInputRate::Vertex,
-        });
+        if !attributes.is_empty() {
+            pipeline_desc.vertex_buffers.push(pso::VertexBufferDesc {
+                binding: 0,
+                stride: mem::size_of::<T>() as u32,
+                rate: options.vertex_rate,
+            });
+        }
 
-        pipeline_desc.attributes.push(pso::AttributeDesc {
-            location: 0,
-            binding: 0,
-            element: pso::Element {
-                format: f::Format::Rg32Sfloat,
-                offset: 0,
-            },
-        });
-        pipeline_desc.attributes.push(pso::AttributeDesc {
-            location: 1,
-            binding: 0,
-            element: pso::Element {
-                format: f::Format::Rgb32Sfloat,
-                offset: 8,
-            },
-        });
+        pipeline_desc.attributes = attributes;
 
         let graphic_pipeline =
             unsafe { device.create_graphics_pipeline(&pipeline_desc, None) }.unwrap();
@@ -97,17 +165,114 @@ impl<'a, B: Backend> Pipeline<'a, B> {
             device.destroy_shader_module(fs_module);
         }
 
-        Pipeline {
+        Ok(Pipeline {
             device,
             pipeline: ManuallyDrop::new(graphic_pipeline),
             pipeline_layout,
+        })
+    }
+}
+
+/// A compute pipeline and the workgroup size its shader declares.
+pub struct ComputePipeline<'a, B: Backend> {
+    device: &'a B::Device,
+    pub pipeline: ManuallyDrop<B::ComputePipeline>,
+    pub pipeline_layout: ManuallyDrop<B::PipelineLayout>,
+    pub local_size: [u32; 3],
+}
+
+impl<'a, B: Backend> ComputePipeline<'a, B> {
+    /// Creates a compute pipeline after checking the shader's bindings, push constants and
+    /// specialization constants against `descriptor_sets`.
+    pub fn new(
+        device: &'a B::Device,
+        cs: &Shader,
+        descriptor_sets: &[&DescriptorSet<'a, B>],
+        specialization: pso::Specialization,
+    ) -> Result<Self, ShaderError> {
+        let push_constants = [];
+        let sets = descriptor_sets
+            .iter()
+            .map(|set| (&set.layout().bindings[..], &set.buffer_sizes[..]))
+            .collect::<Vec<_>>();
+
+        let spirv = cs.spirv()?;
+        let info = ShaderInfo::reflect(cs.name, &spirv)?;
+        info.validate(&Interface {
+            attributes: &[],
+            sets: &sets,
+            push_constants: &push_constants,
+            specialization: &specialization,
+        })?;
+        let local_size = info.local_size.ok_or_else(|| ShaderError {
+            shader: cs.name.to_owned(),
+            messages: vec!["not a compute shader".to_owned()],
+        })?;
+
+        let pipeline_layout = ManuallyDrop::new(
+            unsafe {
+                device.create_pipeline_layout(
+                    descriptor_sets.iter().map(|set| &*set.layout().raw),
+                    &push_constants,
+                )
+            }
+            .expect("Can't create pipeline layout"),
+        );
+
+        let module = unsafe { device.create_shader_module(&spirv) }.unwrap();
+        let desc = pso::ComputePipelineDesc::new(
+            pso::EntryPoint {
+                entry: ENTRY_NAME,
+                module: &module,
+                specialization,
+            },
+            &*pipeline_layout,
+        );
+        let pipeline = unsafe { device.create_compute_pipeline(&desc, None) }.unwrap();
+        unsafe { device.destroy_shader_module(module) };
+
+        Ok(ComputePipeline {
+            device,
+            pipeline: ManuallyDrop::new(pipeline),
+            pipeline_layout,
+            local_size,
+        })
+    }
+
+    /// Binds the pipeline and `descriptor_sets` and dispatches enough workgroups to cover
+    /// `items` invocations along x.
+    pub unsafe fn dispatch(
+        &self,
+        cmd_buffer: &mut B::CommandBuffer,
+        descriptor_sets: &[&DescriptorSet<'a, B>],
+        items: u32,
+    ) {
+        cmd_buffer.bind_compute_pipeline(&self.pipeline);
+        // Sets of layouts without bindings don't exist and stay unbound.
+        for (index, set) in descriptor_sets.iter().enumerate() {
+            if let Some(set) = set.raw() {
+                cmd_buffer.bind_compute_descriptor_sets(
+                    &self.pipeline_layout,
+                    index,
+                    Some(set),
+                    &[],
+                );
+            }
         }
+        cmd_buffer.dispatch([items.div_ceil(self.local_size[0]), 1, 1]);
     }
+}
 
-    fn load_spirv(device: &B::Device, path: &str) -> B::ShaderModule {
-        let data = read(path).unwrap();
-        let spirv = pso::read_spirv(Cursor::new(&data[..])).unwrap();
-        unsafe { device.create_shader_module(&spirv) }.unwrap()
+impl<'a, B: Backend> Drop for ComputePipeline<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_compute_pipeline(ManuallyDrop::into_inner(ptr::read(&self.pipeline)));
+            self.device
+                .destroy_pipeline_layout(ManuallyDrop::into_inner(ptr::read(
+                    &self.pipeline_layout,
+                )));
+        }
     }
 }
 
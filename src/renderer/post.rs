@@ -0,0 +1,356 @@
+use gfx_hal::{adapter::Adapter, buffer as b, format as f, image as i, pass, prelude::*, Backend};
+use std::iter;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::rc::Rc;
+
+use super::buffer::Buffer;
+use super::descriptor_set::{DescriptorLayout, DescriptorSet};
+use super::memory::Memory;
+use super::pipeline::{Pipeline, PipelineOptions};
+use super::shader::{self, Shader};
+use super::texture::TextureOptions;
+
+/// Formats the scene is rendered in, best first. A float format keeps values above 1.0
+/// for tonemapping.
+const SCENE_FORMATS: &[f::Format] = &[f::Format::Rgba16Sfloat, f::Format::Rgba8Unorm];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    Tonemap,
+    Gamma,
+    Fxaa,
+    Vignette,
+    Blur,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 5] = [
+        EffectKind::Tonemap,
+        EffectKind::Gamma,
+        EffectKind::Fxaa,
+        EffectKind::Vignette,
+        EffectKind::Blur,
+    ];
+
+    fn shader(self) -> &'static Shader {
+        match self {
+            EffectKind::Tonemap => &shader::TONEMAP_FRAG,
+            EffectKind::Gamma => &shader::GAMMA_FRAG,
+            EffectKind::Fxaa => &shader::FXAA_FRAG,
+            EffectKind::Vignette => &shader::VIGNETTE_FRAG,
+            EffectKind::Blur => &shader::BLUR_FRAG,
+        }
+    }
+
+    /// Initial parameters, documented at the top of each shader in `src/data/post`.
+    fn default_params(self) -> [f32; 4] {
+        match self {
+            EffectKind::Tonemap => [1.0, 0.0, 0.0, 0.0],
+            EffectKind::Gamma => [1.0, 0.0, 0.0, 0.0],
+            EffectKind::Fxaa => [0.75, 0.0312, 8.0, 0.0],
+            EffectKind::Vignette => [0.5, 0.5, 0.5, 0.0],
+            EffectKind::Blur => [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// The swapchain is usually sRGB, so gamma is only an adjustment and off by default.
+    fn enabled_by_default(self) -> bool {
+        match self {
+            EffectKind::Tonemap | EffectKind::Fxaa => true,
+            EffectKind::Gamma | EffectKind::Vignette | EffectKind::Blur => false,
+        }
+    }
+}
+
+/// A fullscreen pass with its own pipeline and parameters.
+pub struct Effect<'a, B: Backend> {
+    pub kind: EffectKind,
+    pub enabled: bool,
+    /// Passed to the shader as `u.params`.
+    pub params: [f32; 4],
+    pipeline: Pipeline<'a, B>,
+    /// By frame slot, each reading the image given to `set_inputs`.
+    sets: Vec<DescriptorSet<'a, B>>,
+    /// By frame slot.
+    uniforms: Vec<Memory<'a, B, f32>>,
+}
+
+/// Fullscreen effects applied to the rendered scene before it reaches the swapchain.
+///
+/// The renderer's graph has a pass for every enabled effect, reading the previous result
+/// and writing a new image of the format given to `new`. The pipelines are built against a render pass
+/// of their own that is compatible with those passes, so they stay valid as effects are
+/// toggled and the graph is rebuilt. `blit` finally copies the last result into the
+/// output render pass; gfx-hal only exposes views of swapchain images, so this is a
+/// fullscreen draw rather than a transfer.
+pub struct PostProcess<'a, B: Backend> {
+    device: &'a B::Device,
+    render_pass: ManuallyDrop<B::RenderPass>,
+    sampler: ManuallyDrop<B::Sampler>,
+    effects: Vec<Effect<'a, B>>,
+    blit: Pipeline<'a, B>,
+    blit_set: DescriptorSet<'a, B>,
+}
+
+impl<'a, B: Backend> PostProcess<'a, B> {
+    /// Creates the effect pipelines for images of `format` and the blit pipeline for
+    /// `output_pass`, the render pass `blit` is recorded in. Every effect gets its
+    /// parameters uploaded separately for each of the `frames_in_flight`.
+    pub fn new(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        frames_in_flight: usize,
+        format: f::Format,
+        output_pass: &B::RenderPass,
+    ) -> Self {
+        let options = TextureOptions {
+            mipmaps: false,
+            ..TextureOptions::default()
+        };
+        let sampler = unsafe { device.create_sampler(&options.sampler_desc(adapter)) }
+            .expect("Can't create sampler");
+        let render_pass = Self::create_render_pass(device, format);
+
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let limits = adapter.physical_device.limits();
+        let vs = &shader::FULLSCREEN_VERT;
+        // Every effect declares the bindings of `common.glsl`, so one layout serves all.
+        let effect_layout = Self::create_layout(
+            device,
+            iter::once(vs).chain(EffectKind::ALL.iter().map(|kind| kind.shader())),
+        );
+        let effects = EffectKind::ALL
+            .iter()
+            .map(|&kind| {
+                let uniforms = (0..frames_in_flight)
+                    .map(|_| {
+                        let buffer = Buffer::new(device, vec![0.0; 6], b::Usage::UNIFORM, &limits);
+                        Memory::new(buffer, &memory_types)
+                    })
+                    .collect::<Vec<_>>();
+                let sets = uniforms
+                    .iter()
+                    .map(|uniform| {
+                        let mut set = DescriptorSet::new(&effect_layout);
+                        set.write_buffer(2, &uniform.buffer);
+                        set
+                    })
+                    .collect::<Vec<_>>();
+                let pipeline = Pipeline::new::<()>(
+                    device,
+                    vs,
+                    kind.shader(),
+                    &render_pass,
+                    &[&sets[0]],
+                    PipelineOptions::default(),
+                )
+                .unwrap_or_else(|err| panic!("{}", err));
+                Effect {
+                    kind,
+                    enabled: kind.enabled_by_default(),
+                    params: kind.default_params(),
+                    pipeline,
+                    sets,
+                    uniforms,
+                }
+            })
+            .collect();
+
+        let blit_layout = Self::create_layout(device, vec![vs, &shader::BLIT_FRAG]);
+        let blit_set = DescriptorSet::new(&blit_layout);
+        let blit = Pipeline::new::<()>(
+            device,
+            vs,
+            &shader::BLIT_FRAG,
+            output_pass,
+            &[&blit_set],
+            PipelineOptions::default(),
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        PostProcess {
+            device,
+            render_pass: ManuallyDrop::new(render_pass),
+            sampler: ManuallyDrop::new(sampler),
+            effects,
+            blit,
+            blit_set,
+        }
+    }
+
+    /// The best of `SCENE_FORMATS` the adapter can render to, blend and filter.
+    pub fn scene_format(adapter: &Adapter<B>) -> f::Format {
+        let features = f::ImageFeature::COLOR_ATTACHMENT
+            | f::ImageFeature::COLOR_ATTACHMENT_BLEND
+            | f::ImageFeature::SAMPLED_LINEAR;
+        SCENE_FORMATS
+            .iter()
+            .copied()
+            .find(|&format| {
+                adapter
+                    .physical_device
+                    .format_properties(Some(format))
+                    .optimal_tiling
+                    .contains(features)
+            })
+            .unwrap_or(f::Format::Rgba8Unorm)
+    }
+
+    pub fn effects(&self) -> &[Effect<'a, B>] {
+        &self.effects
+    }
+
+    /// The effects that run, in order.
+    pub fn enabled(&self) -> Vec<EffectKind> {
+        self.effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .map(|effect| effect.kind)
+            .collect()
+    }
+
+    pub fn effect_mut(&mut self, kind: EffectKind) -> &mut Effect<'a, B> {
+        self.effects
+            .iter_mut()
+            .find(|effect| effect.kind == kind)
+            .unwrap()
+    }
+
+    /// Flips whether `kind` runs, returning the new state.
+    pub fn toggle(&mut self, kind: EffectKind) -> bool {
+        let effect = self.effect_mut(kind);
+        effect.enabled = !effect.enabled;
+        log::info!(
+            "{:?} {}",
+            kind,
+            if effect.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+        effect.enabled
+    }
+
+    /// Runs the effects in `order`, followed by the ones it leaves out in their current order.
+    pub fn set_order(&mut self, order: &[EffectKind]) {
+        self.effects.sort_by_key(|effect| {
+            order
+                .iter()
+                .position(|&kind| kind == effect.kind)
+                .unwrap_or(order.len())
+        });
+    }
+
+    /// Points each of the `inputs` effects at the image it reads and `blit` at `output`.
+    /// The descriptor sets must not be in use by the GPU.
+    pub fn set_inputs(&mut self, inputs: &[(EffectKind, &B::ImageView)], output: &B::ImageView) {
+        for &(kind, view) in inputs {
+            let sampler = &*self.sampler;
+            let effect = self
+                .effects
+                .iter_mut()
+                .find(|effect| effect.kind == kind)
+                .unwrap();
+            for set in &mut effect.sets {
+                set.write_image(0, view, sampler);
+                set.write_image(1, view, sampler);
+            }
+        }
+        self.blit_set.write_image(0, output, &self.sampler);
+        self.blit_set.write_image(1, output, &self.sampler);
+    }
+
+    /// Uploads the parameters of the enabled effects for images of `extent` into frame
+    /// `slot`. The GPU must be done with the frame that last used the slot.
+    pub fn update(&mut self, slot: usize, extent: i::Extent) {
+        let texel_size = [1.0 / extent.width as f32, 1.0 / extent.height as f32];
+        for effect in self.effects.iter_mut().filter(|effect| effect.enabled) {
+            let uniform = &mut effect.uniforms[slot];
+            let content = &mut uniform.buffer.content;
+            content[..4].copy_from_slice(&effect.params);
+            content[4..].copy_from_slice(&texel_size);
+            uniform.update_data(0);
+        }
+    }
+
+    /// Draws `kind` over its input with the parameters of frame `slot`. Must be recorded
+    /// inside the effect's pass with the viewport already set.
+    pub unsafe fn record(&self, slot: usize, kind: EffectKind, cmd_buffer: &mut B::CommandBuffer) {
+        let effect = self
+            .effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .unwrap();
+        cmd_buffer.bind_graphics_pipeline(&effect.pipeline.pipeline);
+        cmd_buffer.bind_graphics_descriptor_sets(
+            &effect.pipeline.pipeline_layout,
+            0,
+            effect.sets[slot].raw(),
+            &[],
+        );
+        cmd_buffer.draw(0..3, 0..1);
+    }
+
+    /// Draws the final result. Must be recorded inside the output render pass with the
+    /// viewport already set.
+    pub unsafe fn blit(&self, cmd_buffer: &mut B::CommandBuffer) {
+        cmd_buffer.bind_graphics_pipeline(&self.blit.pipeline);
+        cmd_buffer.bind_graphics_descriptor_sets(
+            &self.blit.pipeline_layout,
+            0,
+            self.blit_set.raw(),
+            &[],
+        );
+        cmd_buffer.draw(0..3, 0..1);
+    }
+
+    /// A render pass compatible with the graph's effect passes, which have a single
+    /// color attachment of `format`.
+    fn create_render_pass(device: &B::Device, format: f::Format) -> B::RenderPass {
+        let attachment = pass::Attachment {
+            format: Some(format),
+            samples: 1,
+            ops: pass::AttachmentOps::new(
+                pass::AttachmentLoadOp::DontCare,
+                pass::AttachmentStoreOp::Store,
+            ),
+            stencil_ops: pass::AttachmentOps::DONT_CARE,
+            layouts: i::Layout::ColorAttachmentOptimal..i::Layout::ColorAttachmentOptimal,
+        };
+        let subpass = pass::SubpassDesc {
+            colors: &[(0, i::Layout::ColorAttachmentOptimal)],
+            depth_stencil: None,
+            inputs: &[],
+            resolves: &[],
+            preserves: &[],
+        };
+        unsafe { device.create_render_pass(iter::once(attachment), &[subpass], &[]) }
+            .expect("Can't create render pass")
+    }
+
+    fn create_layout<'s>(
+        device: &'a B::Device,
+        shaders: impl IntoIterator<Item = &'s Shader>,
+    ) -> Rc<DescriptorLayout<'a, B>> {
+        let infos = shaders
+            .into_iter()
+            .map(|shader| shader.reflect())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        DescriptorLayout::from_shaders(device, &infos.iter().collect::<Vec<_>>(), 0)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+impl<'a, B: Backend> Drop for PostProcess<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_sampler(ManuallyDrop::into_inner(ptr::read(&self.sampler)));
+            self.device
+                .destroy_render_pass(ManuallyDrop::into_inner(ptr::read(&self.render_pass)));
+        }
+    }
+}
@@ -0,0 +1,1206 @@
+use gfx_hal::{format as f, pso};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+const MAGIC: u32 = 0x0723_0203;
+
+mod op {
+    pub const NAME: u16 = 5;
+    pub const MEMBER_NAME: u16 = 6;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const EXECUTION_MODE: u16 = 16;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const SPEC_CONSTANT_TRUE: u16 = 48;
+    pub const SPEC_CONSTANT_FALSE: u16 = 49;
+    pub const SPEC_CONSTANT: u16 = 50;
+    pub const VARIABLE: u16 = 59;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+}
+
+mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const NON_WRITABLE: u32 = 24;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+mod storage {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+/// Error produced when a shader can't be parsed or doesn't match the Rust-side layout.
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    pub shader: String,
+    pub messages: Vec<String>,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "shader {}:", self.shader)?;
+        for message in &self.messages {
+            write!(fmt, "\n    {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Int {
+        width: u32,
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+    Vector {
+        component: Box<Type>,
+        count: u32,
+    },
+    Matrix {
+        column: Box<Type>,
+        count: u32,
+    },
+    Array {
+        element: Box<Type>,
+        len: u32,
+        stride: Option<u32>,
+    },
+    RuntimeArray {
+        element: Box<Type>,
+    },
+    Struct {
+        members: Vec<Member>,
+    },
+    Image {
+        dim: u32,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: Option<u32>,
+    pub matrix_stride: Option<u32>,
+    pub built_in: bool,
+    pub non_writable: bool,
+}
+
+impl Type {
+    /// Size in bytes as laid out in a buffer, or 0 for opaque and runtime-sized types.
+    pub fn size(&self) -> u64 {
+        match self {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => *width as u64 / 8,
+            Type::Vector { component, count } => component.size() * *count as u64,
+            Type::Matrix { column, count } => column.size() * *count as u64,
+            Type::Array {
+                element,
+                len,
+                stride,
+            } => stride.map_or_else(|| element.size(), u64::from) * *len as u64,
+            Type::Struct { members } => members
+                .iter()
+                .map(|m| {
+                    let size = match (&m.ty, m.matrix_stride) {
+                        (Type::Matrix { count, .. }, Some(stride)) => stride as u64 * *count as u64,
+                        (ty, _) => ty.size(),
+                    };
+                    m.offset.unwrap_or(0) as u64 + size
+                })
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn has_built_in(&self) -> bool {
+        match self {
+            Type::Struct { members } => members.iter().any(|m| m.built_in),
+            _ => false,
+        }
+    }
+
+    fn element(&self) -> (&Type, u32) {
+        match self {
+            Type::Array { element, len, .. } => (element, *len),
+            Type::RuntimeArray { element } => (element, 0),
+            ty => (ty, 1),
+        }
+    }
+
+    /// The shader-side type a vertex attribute of `format` is read as.
+    pub fn from_format(format: f::Format) -> Option<Type> {
+        use gfx_hal::format::Format::*;
+        let (scalar, count) = match format {
+            R32Sfloat | R16Sfloat | R8Unorm | R8Snorm | R16Unorm | R16Snorm => (0, 1),
+            Rg32Sfloat | Rg16Sfloat | Rg8Unorm | Rg8Snorm | Rg16Unorm | Rg16Snorm => (0, 2),
+            Rgb32Sfloat | Rgb16Sfloat | Rgb8Unorm | Rgb8Snorm => (0, 3),
+            Rgba32Sfloat | Rgba16Sfloat | Rgba8Unorm | Rgba8Snorm | Rgba16Unorm | Rgba16Snorm
+            | Bgra8Unorm => (0, 4),
+            R32Sint | R16Sint | R8Sint => (1, 1),
+            Rg32Sint | Rg16Sint | Rg8Sint => (1, 2),
+            Rgb32Sint => (1, 3),
+            Rgba32Sint | Rgba16Sint | Rgba8Sint => (1, 4),
+            R32Uint | R16Uint | R8Uint => (2, 1),
+            Rg32Uint | Rg16Uint | Rg8Uint => (2, 2),
+            Rgb32Uint => (2, 3),
+            Rgba32Uint | Rgba16Uint | Rgba8Uint => (2, 4),
+            _ => return None,
+        };
+        let scalar = match scalar {
+            0 => Type::Float { width: 32 },
+            1 => Type::Int {
+                width: 32,
+                signed: true,
+            },
+            _ => Type::Int {
+                width: 32,
+                signed: false,
+            },
+        };
+        Some(if count == 1 {
+            scalar
+        } else {
+            Type::Vector {
+                component: Box::new(scalar),
+                count,
+            }
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = |ty: &Type| match ty {
+            Type::Bool => "b",
+            Type::Int { signed: true, .. } => "i",
+            Type::Int { signed: false, .. } => "u",
+            Type::Float { width: 64 } => "d",
+            _ => "",
+        };
+        match self {
+            Type::Bool => write!(fmt, "bool"),
+            Type::Int { signed: true, .. } => write!(fmt, "int"),
+            Type::Int { signed: false, .. } => write!(fmt, "uint"),
+            Type::Float { width: 64 } => write!(fmt, "double"),
+            Type::Float { .. } => write!(fmt, "float"),
+            Type::Vector { component, count } => {
+                write!(fmt, "{}vec{}", prefix(component), count)
+            }
+            Type::Matrix { column, count } => match &**column {
+                Type::Vector {
+                    component,
+                    count: rows,
+                } if rows == count => {
+                    write!(fmt, "{}mat{}", prefix(component), count)
+                }
+                Type::Vector {
+                    component,
+                    count: rows,
+                } => {
+                    write!(fmt, "{}mat{}x{}", prefix(component), count, rows)
+                }
+                _ => write!(fmt, "mat"),
+            },
+            Type::Array { element, len, .. } => write!(fmt, "{}[{}]", element, len),
+            Type::RuntimeArray { element } => write!(fmt, "{}[]", element),
+            Type::Struct { .. } => write!(fmt, "struct"),
+            Type::Image { .. } => write!(fmt, "image"),
+            Type::Sampler => write!(fmt, "sampler"),
+            Type::SampledImage => write!(fmt, "sampler image"),
+            Type::Unknown => write!(fmt, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    UniformBuffer,
+    StorageBuffer { read_only: bool },
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    SampledImage,
+    CombinedImageSampler,
+    StorageImage,
+    Sampler,
+    InputAttachment,
+}
+
+impl BindingKind {
+    pub fn descriptor_type(self) -> pso::DescriptorType {
+        use gfx_hal::pso::{
+            BufferDescriptorFormat as Bf, BufferDescriptorType as Bt, DescriptorType as D,
+            ImageDescriptorType as It,
+        };
+        match self {
+            BindingKind::UniformBuffer => D::Buffer {
+                ty: Bt::Uniform,
+                format: Bf::Structured {
+                    dynamic_offset: false,
+                },
+            },
+            BindingKind::StorageBuffer { read_only } => D::Buffer {
+                ty: Bt::Storage { read_only },
+                format: Bf::Structured {
+                    dynamic_offset: false,
+                },
+            },
+            BindingKind::UniformTexelBuffer => D::Buffer {
+                ty: Bt::Uniform,
+                format: Bf::Texel,
+            },
+            BindingKind::StorageTexelBuffer => D::Buffer {
+                ty: Bt::Storage { read_only: false },
+                format: Bf::Texel,
+            },
+            BindingKind::SampledImage => D::Image {
+                ty: It::Sampled {
+                    with_sampler: false,
+                },
+            },
+            BindingKind::CombinedImageSampler => D::Image {
+                ty: It::Sampled { with_sampler: true },
+            },
+            BindingKind::StorageImage => D::Image {
+                ty: It::Storage { read_only: false },
+            },
+            BindingKind::Sampler => D::Sampler,
+            BindingKind::InputAttachment => D::InputAttachment,
+        }
+    }
+
+    /// Whether a layout binding of type `ty` can back a shader binding of this kind.
+    fn accepts(self, ty: &pso::DescriptorType) -> bool {
+        let writes = match self.descriptor_type() {
+            pso::DescriptorType::Buffer {
+                ty: pso::BufferDescriptorType::Storage { read_only },
+                ..
+            }
+            | pso::DescriptorType::Image {
+                ty: pso::ImageDescriptorType::Storage { read_only },
+            } => !read_only,
+            _ => false,
+        };
+        let read_only_layout = match ty {
+            pso::DescriptorType::Buffer {
+                ty: pso::BufferDescriptorType::Storage { read_only },
+                ..
+            }
+            | pso::DescriptorType::Image {
+                ty: pso::ImageDescriptorType::Storage { read_only },
+            } => *read_only,
+            _ => false,
+        };
+        normalize(self.descriptor_type()) == normalize(*ty) && !(writes && read_only_layout)
+    }
+
+    /// Kind able to serve both `self` and `other`, if they describe the same resource.
+    fn merge(self, other: BindingKind) -> Option<BindingKind> {
+        match (self, other) {
+            (
+                BindingKind::StorageBuffer { read_only: a },
+                BindingKind::StorageBuffer { read_only: b },
+            ) => Some(BindingKind::StorageBuffer { read_only: a && b }),
+            (a, b) if a == b => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Drops the properties a shader can't express: dynamic offsets and read-only storage.
+fn normalize(ty: pso::DescriptorType) -> pso::DescriptorType {
+    use gfx_hal::pso::{
+        BufferDescriptorFormat as Bf, BufferDescriptorType as Bt, DescriptorType as D,
+    };
+    match ty {
+        D::Buffer { ty, format } => D::Buffer {
+            ty: match ty {
+                Bt::Storage { .. } => Bt::Storage { read_only: false },
+                ty => ty,
+            },
+            format: match format {
+                Bf::Structured { .. } => Bf::Structured {
+                    dynamic_offset: false,
+                },
+                format => format,
+            },
+        },
+        D::Image {
+            ty: pso::ImageDescriptorType::Storage { .. },
+        } => D::Image {
+            ty: pso::ImageDescriptorType::Storage { read_only: false },
+        },
+        ty => ty,
+    }
+}
+
+/// Builds the layout of descriptor set `set` from the bindings all `shaders` declare in it.
+/// A binding used by several stages is visible to each of them.
+pub fn layout_bindings(
+    shaders: &[&ShaderInfo],
+    set: u32,
+) -> Result<Vec<pso::DescriptorSetLayoutBinding>, ShaderError> {
+    let mut merged: Vec<(&Binding, BindingKind, u32, pso::ShaderStageFlags)> = vec![];
+    let mut messages = vec![];
+    for shader in shaders {
+        for b in shader.bindings.iter().filter(|b| b.set == set) {
+            match merged.iter_mut().find(|(m, ..)| m.binding == b.binding) {
+                Some((m, kind, count, stages)) => match kind.merge(b.kind) {
+                    Some(k) => {
+                        *kind = k;
+                        *count = (*count).max(b.count);
+                        *stages |= shader.stage;
+                    }
+                    None => messages.push(format!(
+                        "binding {} is `{}` ({:?}) in one stage and `{}` ({:?}) in {}",
+                        b.binding, m.name, kind, b.name, b.kind, shader.name
+                    )),
+                },
+                None => merged.push((b, b.kind, b.count, shader.stage)),
+            }
+        }
+    }
+    if !messages.is_empty() {
+        return Err(ShaderError {
+            shader: format!("set {}", set),
+            messages,
+        });
+    }
+
+    let mut bindings = merged
+        .into_iter()
+        .map(
+            |(b, kind, count, stage_flags)| pso::DescriptorSetLayoutBinding {
+                binding: b.binding,
+                ty: kind.descriptor_type(),
+                count: count.max(1) as usize,
+                stage_flags,
+                immutable_samplers: false,
+            },
+        )
+        .collect::<Vec<_>>();
+    bindings.sort_by_key(|b| b.binding);
+    Ok(bindings)
+}
+
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub location: u32,
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: String,
+    pub kind: BindingKind,
+    /// Array length, 0 for runtime-sized arrays.
+    pub count: u32,
+    /// Size of the buffer block in bytes, 0 for non-buffer bindings.
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PushConstants {
+    pub name: String,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpecConstant {
+    pub id: u32,
+    pub name: String,
+    pub ty: Type,
+}
+
+/// Interface of a single-entry-point SPIR-V module.
+#[derive(Debug, Clone)]
+pub struct ShaderInfo {
+    pub name: String,
+    pub stage: pso::ShaderStageFlags,
+    pub inputs: Vec<Input>,
+    pub bindings: Vec<Binding>,
+    pub push_constants: Option<PushConstants>,
+    pub spec_constants: Vec<SpecConstant>,
+    /// Workgroup size of a compute shader.
+    pub local_size: Option<[u32; 3]>,
+}
+
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    spec_id: Option<u32>,
+    array_stride: Option<u32>,
+    buffer_block: bool,
+    built_in: bool,
+    non_writable: bool,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    built_in: bool,
+    non_writable: bool,
+}
+
+fn read_string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .take_while(|&b| b != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl ShaderInfo {
+    pub fn reflect(name: &str, spirv: &[u32]) -> Result<Self, ShaderError> {
+        let error = |message: &str| ShaderError {
+            shader: name.to_owned(),
+            messages: vec![message.to_owned()],
+        };
+        if spirv.len() < 5 || spirv[0] != MAGIC {
+            return Err(error("not a SPIR-V module"));
+        }
+
+        let mut stage = None;
+        let mut local_size = None;
+        let mut names = HashMap::new();
+        let mut decorations: HashMap<u32, Decorations> = HashMap::new();
+        let mut members: HashMap<(u32, u32), MemberDecorations> = HashMap::new();
+        let mut member_names = HashMap::new();
+        let mut types = HashMap::new();
+        let mut constants = HashMap::new();
+        let mut pointers = HashMap::new();
+        let mut array_elements = HashMap::new();
+        let mut variables = vec![];
+        let mut spec_constants = vec![];
+
+        let mut words = &spirv[5..];
+        while !words.is_empty() {
+            let count = (words[0] >> 16) as usize;
+            let opcode = words[0] as u16;
+            if count == 0 || count > words.len() {
+                return Err(error("truncated instruction"));
+            }
+            let args = &words[1..count];
+            words = &words[count..];
+
+            let arg = |i: usize| {
+                args.get(i)
+                    .copied()
+                    .ok_or_else(|| error("instruction has too few operands"))
+            };
+            let ty = |id: u32| types.get(&id).cloned().unwrap_or(Type::Unknown);
+
+            match opcode {
+                op::NAME => {
+                    names.insert(arg(0)?, read_string(&args[1..]));
+                }
+                op::MEMBER_NAME => {
+                    member_names.insert((arg(0)?, arg(1)?), read_string(&args[2..]));
+                }
+                op::ENTRY_POINT => {
+                    if stage.is_some() {
+                        return Err(error("multiple entry points are not supported"));
+                    }
+                    stage = Some(match arg(0)? {
+                        0 => pso::ShaderStageFlags::VERTEX,
+                        1 => pso::ShaderStageFlags::HULL,
+                        2 => pso::ShaderStageFlags::DOMAIN,
+                        3 => pso::ShaderStageFlags::GEOMETRY,
+                        4 => pso::ShaderStageFlags::FRAGMENT,
+                        5 => pso::ShaderStageFlags::COMPUTE,
+                        model => {
+                            return Err(error(&format!("unsupported execution model {}", model)))
+                        }
+                    });
+                }
+                op::EXECUTION_MODE if arg(1)? == EXECUTION_MODE_LOCAL_SIZE => {
+                    local_size = Some([arg(2)?, arg(3)?, arg(4)?]);
+                }
+                op::DECORATE => {
+                    let d = decorations.entry(arg(0)?).or_default();
+                    match arg(1)? {
+                        decoration::SPEC_ID => d.spec_id = Some(arg(2)?),
+                        decoration::BUFFER_BLOCK => d.buffer_block = true,
+                        decoration::ARRAY_STRIDE => d.array_stride = Some(arg(2)?),
+                        decoration::BUILT_IN => d.built_in = true,
+                        decoration::NON_WRITABLE => d.non_writable = true,
+                        decoration::LOCATION => d.location = Some(arg(2)?),
+                        decoration::BINDING => d.binding = Some(arg(2)?),
+                        decoration::DESCRIPTOR_SET => d.set = Some(arg(2)?),
+                        _ => {}
+                    }
+                }
+                op::MEMBER_DECORATE => {
+                    let d = members.entry((arg(0)?, arg(1)?)).or_default();
+                    match arg(2)? {
+                        decoration::OFFSET => d.offset = Some(arg(3)?),
+                        decoration::MATRIX_STRIDE => d.matrix_stride = Some(arg(3)?),
+                        decoration::BUILT_IN => d.built_in = true,
+                        decoration::NON_WRITABLE => d.non_writable = true,
+                        _ => {}
+                    }
+                }
+                op::TYPE_BOOL => {
+                    types.insert(arg(0)?, Type::Bool);
+                }
+                op::TYPE_INT => {
+                    let t = Type::Int {
+                        width: arg(1)?,
+                        signed: arg(2)? != 0,
+                    };
+                    types.insert(arg(0)?, t);
+                }
+                op::TYPE_FLOAT => {
+                    types.insert(arg(0)?, Type::Float { width: arg(1)? });
+                }
+                op::TYPE_VECTOR => {
+                    let t = Type::Vector {
+                        component: Box::new(ty(arg(1)?)),
+                        count: arg(2)?,
+                    };
+                    types.insert(arg(0)?, t);
+                }
+                op::TYPE_MATRIX => {
+                    let t = Type::Matrix {
+                        column: Box::new(ty(arg(1)?)),
+                        count: arg(2)?,
+                    };
+                    types.insert(arg(0)?, t);
+                }
+                op::TYPE_IMAGE => {
+                    let t = Type::Image {
+                        dim: arg(2)?,
+                        sampled: arg(6)?,
+                    };
+                    types.insert(arg(0)?, t);
+                }
+                op::TYPE_SAMPLER => {
+                    types.insert(arg(0)?, Type::Sampler);
+                }
+                op::TYPE_SAMPLED_IMAGE => {
+                    types.insert(arg(0)?, Type::SampledImage);
+                }
+                op::TYPE_ARRAY => {
+                    let id = arg(0)?;
+                    array_elements.insert(id, arg(1)?);
+                    let t = Type::Array {
+                        element: Box::new(ty(arg(1)?)),
+                        len: constants.get(&arg(2)?).copied().unwrap_or(0),
+                        stride: decorations.get(&id).and_then(|d| d.array_stride),
+                    };
+                    types.insert(id, t);
+                }
+                op::TYPE_RUNTIME_ARRAY => {
+                    array_elements.insert(arg(0)?, arg(1)?);
+                    let t = Type::RuntimeArray {
+                        element: Box::new(ty(arg(1)?)),
+                    };
+                    types.insert(arg(0)?, t);
+                }
+                op::TYPE_STRUCT => {
+                    let id = arg(0)?;
+                    let t = Type::Struct {
+                        members: args[1..]
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &member)| {
+                                let key = (id, i as u32);
+                                let d = members.remove(&key).unwrap_or_default();
+                                Member {
+                                    name: member_names.remove(&key).unwrap_or_default(),
+                                    ty: ty(member),
+                                    offset: d.offset,
+                                    matrix_stride: d.matrix_stride,
+                                    built_in: d.built_in,
+                                    non_writable: d.non_writable,
+                                }
+                            })
+                            .collect(),
+                    };
+                    types.insert(id, t);
+                }
+                op::TYPE_POINTER => {
+                    pointers.insert(arg(0)?, (arg(1)?, arg(2)?));
+                }
+                op::CONSTANT => {
+                    constants.insert(arg(1)?, arg(2)?);
+                }
+                op::SPEC_CONSTANT_TRUE | op::SPEC_CONSTANT_FALSE | op::SPEC_CONSTANT => {
+                    spec_constants.push((arg(1)?, ty(arg(0)?)));
+                }
+                op::VARIABLE => {
+                    variables.push((arg(1)?, arg(0)?, arg(2)?));
+                }
+                _ => {}
+            }
+        }
+
+        let name_of = |id: u32| names.get(&id).cloned().unwrap_or_default();
+        let mut info = ShaderInfo {
+            name: name.to_owned(),
+            stage: stage.ok_or_else(|| error("no entry point"))?,
+            inputs: vec![],
+            bindings: vec![],
+            push_constants: None,
+            spec_constants: spec_constants
+                .into_iter()
+                .filter_map(|(id, ty)| {
+                    let spec_id = decorations.get(&id)?.spec_id?;
+                    Some(SpecConstant {
+                        id: spec_id,
+                        name: name_of(id),
+                        ty,
+                    })
+                })
+                .collect(),
+            local_size,
+        };
+
+        let no_decorations = Decorations::default();
+        for (id, pointer, storage_class) in variables {
+            let ty = match pointers.get(&pointer) {
+                Some(&(_, pointee)) => types.get(&pointee).cloned().unwrap_or(Type::Unknown),
+                None => continue,
+            };
+            let d = decorations.get(&id).unwrap_or(&no_decorations);
+            match storage_class {
+                storage::INPUT => {
+                    if d.built_in || ty.has_built_in() {
+                        continue;
+                    }
+                    if let Some(location) = d.location {
+                        info.inputs.push(Input {
+                            location,
+                            name: name_of(id),
+                            ty,
+                        });
+                    }
+                }
+                storage::UNIFORM | storage::UNIFORM_CONSTANT | storage::STORAGE_BUFFER => {
+                    let (set, binding) = match (d.set, d.binding) {
+                        (Some(set), Some(binding)) => (set, binding),
+                        _ => continue,
+                    };
+                    let (element, count) = ty.element();
+                    let block = pointers.get(&pointer).map(|&(_, pointee)| {
+                        array_elements.get(&pointee).copied().unwrap_or(pointee)
+                    });
+                    let buffer_block = block
+                        .and_then(|block| decorations.get(&block))
+                        .is_some_and(|d| d.buffer_block);
+                    let kind = match (storage_class, element) {
+                        (storage::STORAGE_BUFFER, _) => BindingKind::StorageBuffer {
+                            read_only: d.non_writable || all_non_writable(element),
+                        },
+                        (storage::UNIFORM, _) if buffer_block => BindingKind::StorageBuffer {
+                            read_only: d.non_writable || all_non_writable(element),
+                        },
+                        (storage::UNIFORM, _) => BindingKind::UniformBuffer,
+                        (_, Type::Sampler) => BindingKind::Sampler,
+                        (_, Type::SampledImage) => BindingKind::CombinedImageSampler,
+                        (_, Type::Image { dim: 5, sampled: 2 }) => BindingKind::StorageTexelBuffer,
+                        (_, Type::Image { dim: 5, .. }) => BindingKind::UniformTexelBuffer,
+                        (_, Type::Image { dim: 6, .. }) => BindingKind::InputAttachment,
+                        (_, Type::Image { sampled: 2, .. }) => BindingKind::StorageImage,
+                        (_, Type::Image { .. }) => BindingKind::SampledImage,
+                        (_, other) => {
+                            return Err(error(&format!(
+                                "unsupported uniform `{}` of type {}",
+                                name_of(id),
+                                other
+                            )))
+                        }
+                    };
+                    let size = match kind {
+                        BindingKind::UniformBuffer | BindingKind::StorageBuffer { .. } => {
+                            element.size()
+                        }
+                        _ => 0,
+                    };
+                    info.bindings.push(Binding {
+                        set,
+                        binding,
+                        name: name_of(id),
+                        kind,
+                        count,
+                        size,
+                    });
+                }
+                storage::PUSH_CONSTANT => {
+                    info.push_constants = Some(PushConstants {
+                        name: name_of(id),
+                        size: ty.size() as u32,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        info.inputs.sort_by_key(|input| input.location);
+        info.bindings.sort_by_key(|b| (b.set, b.binding));
+        Ok(info)
+    }
+}
+
+fn all_non_writable(ty: &Type) -> bool {
+    match ty {
+        Type::Struct { members } => !members.is_empty() && members.iter().all(|m| m.non_writable),
+        _ => false,
+    }
+}
+
+/// Layout bindings of a descriptor set and the sizes of the buffers bound to them.
+pub type SetInterface<'a> = (&'a [pso::DescriptorSetLayoutBinding], &'a [(u32, u64)]);
+
+/// Rust-side layout a shader is checked against.
+pub struct Interface<'a> {
+    pub attributes: &'a [pso::AttributeDesc],
+    /// Every bound descriptor set, indexed by set number.
+    pub sets: &'a [SetInterface<'a>],
+    pub push_constants: &'a [(pso::ShaderStageFlags, Range<u32>)],
+    pub specialization: &'a pso::Specialization<'a>,
+}
+
+impl ShaderInfo {
+    pub fn validate(&self, interface: &Interface) -> Result<(), ShaderError> {
+        let mut messages = vec![];
+        if self.stage == pso::ShaderStageFlags::VERTEX {
+            self.check_inputs(interface.attributes, &mut messages);
+        }
+        self.check_bindings(interface.sets, &mut messages);
+        self.check_push_constants(interface.push_constants, &mut messages);
+        self.check_specialization(interface.specialization, &mut messages);
+
+        if messages.is_empty() {
+            Ok(())
+        } else {
+            Err(ShaderError {
+                shader: self.name.clone(),
+                messages,
+            })
+        }
+    }
+
+    fn check_inputs(&self, attributes: &[pso::AttributeDesc], messages: &mut Vec<String>) {
+        for input in &self.inputs {
+            let attribute = match attributes.iter().find(|a| a.location == input.location) {
+                Some(attribute) => attribute,
+                None => {
+                    messages.push(format!(
+                        "input `{}` at location {} has no vertex attribute",
+                        input.name, input.location
+                    ));
+                    continue;
+                }
+            };
+            let format = attribute.element.format;
+            match Type::from_format(format) {
+                Some(ty) if ty == input.ty => {}
+                Some(ty) => messages.push(format!(
+                    "input `{}` at location {} is {} but the vertex attribute is {:?} ({})",
+                    input.name, input.location, input.ty, format, ty
+                )),
+                None => messages.push(format!(
+                    "input `{}` at location {} uses unsupported vertex format {:?}",
+                    input.name, input.location, format
+                )),
+            }
+        }
+        for attribute in attributes {
+            if !self.inputs.iter().any(|i| i.location == attribute.location) {
+                log::warn!(
+                    "shader {}: vertex attribute at location {} is not used",
+                    self.name,
+                    attribute.location
+                );
+            }
+        }
+    }
+
+    fn check_bindings(&self, sets: &[SetInterface], messages: &mut Vec<String>) {
+        for b in &self.bindings {
+            let (layout, sizes) = match sets.get(b.set as usize) {
+                Some(set) => set,
+                None => {
+                    messages.push(format!(
+                        "`{}` uses descriptor set {} but only {} set(s) are bound",
+                        b.name,
+                        b.set,
+                        sets.len()
+                    ));
+                    continue;
+                }
+            };
+            let desc = match layout.iter().find(|desc| desc.binding == b.binding) {
+                Some(desc) => desc,
+                None => {
+                    messages.push(format!(
+                        "`{}` (set {}, binding {}) is missing from the descriptor set layout",
+                        b.name, b.set, b.binding
+                    ));
+                    continue;
+                }
+            };
+            if !b.kind.accepts(&desc.ty) {
+                messages.push(format!(
+                    "`{}` (set {}, binding {}) is {:?} but the layout declares {:?}",
+                    b.name, b.set, b.binding, b.kind, desc.ty
+                ));
+            }
+            if !desc.stage_flags.contains(self.stage) {
+                messages.push(format!(
+                    "`{}` (set {}, binding {}) is not visible to stage {:?}",
+                    b.name, b.set, b.binding, self.stage
+                ));
+            }
+            if desc.count < b.count as usize {
+                messages.push(format!(
+                    "`{}` (set {}, binding {}) has {} element(s) but the layout declares {}",
+                    b.name, b.set, b.binding, b.count, desc.count
+                ));
+            }
+            if let Some(&(_, size)) = sizes.iter().find(|(binding, _)| *binding == b.binding) {
+                if size < b.size {
+                    messages.push(format!(
+                        "`{}` (set {}, binding {}) needs {} bytes but the bound buffer has {}",
+                        b.name, b.set, b.binding, b.size, size
+                    ));
+                }
+            }
+        }
+    }
+
+    fn check_push_constants(
+        &self,
+        ranges: &[(pso::ShaderStageFlags, Range<u32>)],
+        messages: &mut Vec<String>,
+    ) {
+        let push = match &self.push_constants {
+            Some(push) => push,
+            None => return,
+        };
+        let covered = ranges
+            .iter()
+            .filter(|(stages, _)| stages.contains(self.stage))
+            .any(|(_, range)| range.start == 0 && range.end >= push.size);
+        if !covered {
+            messages.push(format!(
+                "push constant block `{}` ({} bytes) is not covered by a push constant range",
+                push.name, push.size
+            ));
+        }
+    }
+
+    fn check_specialization(&self, spec: &pso::Specialization, messages: &mut Vec<String>) {
+        for constant in spec.constants.iter() {
+            let size = (constant.range.end - constant.range.start) as u64;
+            match self.spec_constants.iter().find(|c| c.id == constant.id) {
+                Some(c) if c.ty.size() != size => messages.push(format!(
+                    "specialization constant `{}` (id {}) is {} but {} bytes were provided",
+                    c.name, c.id, c.ty, size
+                )),
+                Some(_) => {}
+                None => log::warn!(
+                    "shader {}: no specialization constant with id {}",
+                    self.name,
+                    constant.id
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::shader;
+    use crate::renderer::vertex::{Vertex, VertexLayout};
+
+    fn float(width: u32) -> Type {
+        Type::Float { width }
+    }
+
+    fn vector(count: u32) -> Type {
+        Type::Vector {
+            component: Box::new(float(32)),
+            count,
+        }
+    }
+
+    fn instruction(opcode: u16, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode as u32];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn string(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    /// A compute shader with a float and a bool specialization constant, which the
+    /// shaders of this crate don't have enough of.
+    fn spec_constant_module() -> Vec<u32> {
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 6, 0];
+        words.extend(instruction(
+            op::ENTRY_POINT,
+            &[vec![5, 1], string("main")].concat(),
+        ));
+        words.extend(instruction(
+            op::EXECUTION_MODE,
+            &[1, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1],
+        ));
+        words.extend(instruction(op::NAME, &[vec![3], string("scale")].concat()));
+        words.extend(instruction(
+            op::NAME,
+            &[vec![5], string("enabled")].concat(),
+        ));
+        words.extend(instruction(op::DECORATE, &[3, decoration::SPEC_ID, 0]));
+        words.extend(instruction(op::DECORATE, &[5, decoration::SPEC_ID, 7]));
+        words.extend(instruction(op::TYPE_FLOAT, &[2, 32]));
+        words.extend(instruction(op::SPEC_CONSTANT, &[2, 3, 1.0f32.to_bits()]));
+        words.extend(instruction(op::TYPE_BOOL, &[4]));
+        words.extend(instruction(op::SPEC_CONSTANT_TRUE, &[4, 5]));
+        words
+    }
+
+    fn triangle_attributes() -> Vec<pso::AttributeDesc> {
+        Vertex::elements()
+            .into_iter()
+            .enumerate()
+            .map(|(location, element)| pso::AttributeDesc {
+                location: location as u32,
+                binding: 0,
+                element,
+            })
+            .collect()
+    }
+
+    fn validate(
+        info: &ShaderInfo,
+        attributes: &[pso::AttributeDesc],
+        sets: &[SetInterface],
+        specialization: &pso::Specialization,
+    ) -> Result<(), ShaderError> {
+        info.validate(&Interface {
+            attributes,
+            sets,
+            push_constants: &[],
+            specialization,
+        })
+    }
+
+    #[test]
+    fn reflects_vertex_inputs_and_uniform_blocks() {
+        let info = shader::TRIANGLE_VERT.reflect().unwrap();
+        assert_eq!(info.stage, pso::ShaderStageFlags::VERTEX);
+        let inputs = info
+            .inputs
+            .iter()
+            .map(|input| (input.location, input.name.as_str(), input.ty.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(inputs, [(0, "a_pos", vector(2)), (1, "a_color", vector(3))]);
+        let bindings = info
+            .bindings
+            .iter()
+            .map(|b| (b.set, b.binding, b.kind, b.count, b.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            [
+                (0, 0, BindingKind::UniformBuffer, 1, 128),
+                (0, 1, BindingKind::UniformBuffer, 1, 64)
+            ]
+        );
+        assert!(info.push_constants.is_none());
+        assert_eq!(info.local_size, None);
+    }
+
+    #[test]
+    fn reflects_images_and_samplers() {
+        let info = shader::TEXTURED_FRAG.reflect().unwrap();
+        assert_eq!(info.stage, pso::ShaderStageFlags::FRAGMENT);
+        let kinds = info.bindings.iter().map(|b| b.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [BindingKind::SampledImage, BindingKind::Sampler]);
+        assert!(info.bindings.iter().all(|b| b.size == 0));
+    }
+
+    #[test]
+    fn reflects_compute_local_size_and_storage_buffers() {
+        let info = shader::PARTICLE_SIMULATE_COMP.reflect().unwrap();
+        assert_eq!(info.stage, pso::ShaderStageFlags::COMPUTE);
+        assert_eq!(info.local_size, Some([64, 1, 1]));
+        assert!(info.inputs.is_empty());
+        assert_eq!(
+            info.bindings[0].kind,
+            BindingKind::StorageBuffer { read_only: false }
+        );
+        assert_eq!(info.bindings[1].kind, BindingKind::UniformBuffer);
+        assert_eq!(info.bindings[1].size, 80);
+    }
+
+    #[test]
+    fn reflects_spec_constants() {
+        let info = ShaderInfo::reflect("spec", &spec_constant_module()).unwrap();
+        let constants = info
+            .spec_constants
+            .iter()
+            .map(|c| (c.id, c.name.as_str(), c.ty.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            constants,
+            [(0, "scale", float(32)), (7, "enabled", Type::Bool)]
+        );
+        assert_eq!(info.local_size, Some([8, 4, 1]));
+    }
+
+    #[test]
+    fn rejects_what_is_not_spirv() {
+        assert!(ShaderInfo::reflect("empty", &[]).is_err());
+        let mut truncated = spec_constant_module();
+        truncated.truncate(7);
+        assert!(ShaderInfo::reflect("truncated", &truncated).is_err());
+    }
+
+    #[test]
+    fn layout_bindings_merge_stages_and_reject_conflicts() {
+        let vert = shader::PARTICLE_VERT.reflect().unwrap();
+        let frag = shader::PARTICLE_FRAG.reflect().unwrap();
+        let bindings = layout_bindings(&[&vert, &frag], 0).unwrap();
+        assert_eq!(bindings.len(), 1);
+        assert!(bindings[0]
+            .stage_flags
+            .contains(pso::ShaderStageFlags::VERTEX));
+
+        let textured = shader::TEXTURED_FRAG.reflect().unwrap();
+        assert!(layout_bindings(&[&vert, &textured], 0).is_err());
+    }
+
+    #[test]
+    fn validate_accepts_the_rust_layout() {
+        let info = shader::TRIANGLE_VERT.reflect().unwrap();
+        let layout = layout_bindings(&[&info], 0).unwrap();
+        let sizes = [(0, 128), (1, 64)];
+        let result = validate(
+            &info,
+            &triangle_attributes(),
+            &[(&layout, &sizes)],
+            &pso::Specialization::default(),
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn validate_checks_storage_buffer_access() {
+        let info = shader::PARTICLE_SIMULATE_COMP.reflect().unwrap();
+        let mut layout = layout_bindings(&[&info], 0).unwrap();
+        let no_spec = pso::Specialization::default();
+        assert!(validate(&info, &[], &[(&layout, &[])], &no_spec).is_ok());
+        // The shader writes the particles, so a read-only binding can't back them.
+        layout[0].ty = BindingKind::StorageBuffer { read_only: true }.descriptor_type();
+        assert!(validate(&info, &[], &[(&layout, &[])], &no_spec).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_mismatches() {
+        let info = shader::TRIANGLE_VERT.reflect().unwrap();
+        let layout = layout_bindings(&[&info], 0).unwrap();
+        let sizes = [(0, 128), (1, 64)];
+        let no_spec = pso::Specialization::default();
+        let messages = |result: Result<(), ShaderError>| result.unwrap_err().messages.len();
+
+        let mut attributes = triangle_attributes();
+        attributes[1].element.format = f::Format::Rg32Sfloat;
+        assert_eq!(
+            messages(validate(&info, &attributes, &[(&layout, &sizes)], &no_spec)),
+            1
+        );
+        assert_eq!(
+            messages(validate(
+                &info,
+                &attributes[..1],
+                &[(&layout, &sizes)],
+                &no_spec
+            )),
+            1
+        );
+
+        let attributes = triangle_attributes();
+        assert_eq!(messages(validate(&info, &attributes, &[], &no_spec)), 2);
+        let small = [(0, 64), (1, 64)];
+        assert_eq!(
+            messages(validate(&info, &attributes, &[(&layout, &small)], &no_spec)),
+            1
+        );
+        let mut hidden = layout.clone();
+        hidden[1].stage_flags = pso::ShaderStageFlags::FRAGMENT;
+        assert_eq!(
+            messages(validate(&info, &attributes, &[(&hidden, &sizes)], &no_spec)),
+            1
+        );
+        let mut storage = layout.clone();
+        storage[0].ty = BindingKind::StorageBuffer { read_only: false }.descriptor_type();
+        assert_eq!(
+            messages(validate(
+                &info,
+                &attributes,
+                &[(&storage, &sizes)],
+                &no_spec
+            )),
+            1
+        );
+
+        let spec = ShaderInfo::reflect("spec", &spec_constant_module()).unwrap();
+        let double = gfx_hal::spec_const_list![0 => 1.0f64];
+        assert_eq!(messages(validate(&spec, &[], &[], &double)), 1);
+        let single = gfx_hal::spec_const_list![0 => 1.0f32];
+        assert!(validate(&spec, &[], &[], &single).is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+use gfx_hal::{
+    adapter::{Adapter, MemoryType},
+    format as f, image as i, memory as m,
+    prelude::*,
+    Backend,
+};
+use std::mem::ManuallyDrop;
+use std::ptr;
+
+use super::memory;
+
+const DEPTH_FORMATS: &[f::Format] = &[
+    f::Format::D32Sfloat,
+    f::Format::D32SfloatS8Uint,
+    f::Format::D24UnormS8Uint,
+    f::Format::D16Unorm,
+];
+
+/// An image with its own memory and a view covering it.
+struct Attachment<B: Backend> {
+    image: B::Image,
+    memory: B::Memory,
+    view: B::ImageView,
+}
+
+/// An off-screen color image for the render graph to import as an attachment and to copy
+/// from afterwards, e.g. to read a headless frame back.
+///
+/// The render passes and their transitions belong to the graph; the image only has to
+/// allow `COLOR_ATTACHMENT` and `TRANSFER_SRC` use.
+pub struct RenderTarget<'a, B: Backend> {
+    device: &'a B::Device,
+    color: ManuallyDrop<Attachment<B>>,
+    pub dims: i::Extent,
+}
+
+impl<'a, B: Backend> RenderTarget<'a, B> {
+    /// Creates a `width` x `height` target of `format`.
+    pub fn new(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        (width, height): (u32, u32),
+        format: f::Format,
+    ) -> Self {
+        let dims = i::Extent {
+            width,
+            height,
+            depth: 1,
+        };
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let color = Self::create_attachment(
+            device,
+            &memory_types,
+            dims,
+            format,
+            1,
+            i::Usage::COLOR_ATTACHMENT | i::Usage::TRANSFER_SRC,
+            f::Aspects::COLOR,
+        );
+        RenderTarget {
+            device,
+            color: ManuallyDrop::new(color),
+            dims,
+        }
+    }
+
+    pub fn image(&self) -> &B::Image {
+        &self.color.image
+    }
+
+    pub fn view(&self) -> &B::ImageView {
+        &self.color.view
+    }
+
+    /// The highest sample count up to `requested` that color (and depth) attachments
+    /// support.
+    pub fn supported_samples(
+        adapter: &Adapter<B>,
+        requested: i::NumSamples,
+        depth: bool,
+    ) -> i::NumSamples {
+        let limits = adapter.physical_device.limits();
+        let mut counts = limits.framebuffer_color_sample_counts;
+        if depth {
+            counts &= limits.framebuffer_depth_sample_counts;
+        }
+        let samples = (0..8)
+            .rev()
+            .map(|bit| 1 << bit)
+            .find(|&samples| samples <= requested && counts & samples != 0)
+            .unwrap_or(1);
+        if samples != requested {
+            log::warn!(
+                "{} samples per pixel aren't supported, using {}",
+                requested,
+                samples
+            );
+        }
+        samples
+    }
+
+    /// The first of `DEPTH_FORMATS` the adapter can render to.
+    pub fn depth_format(adapter: &Adapter<B>) -> f::Format {
+        DEPTH_FORMATS
+            .iter()
+            .copied()
+            .find(|&format| {
+                adapter
+                    .physical_device
+                    .format_properties(Some(format))
+                    .optimal_tiling
+                    .contains(f::ImageFeature::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .expect("No supported depth format")
+    }
+
+    fn create_attachment(
+        device: &B::Device,
+        memory_types: &[MemoryType],
+        dims: i::Extent,
+        format: f::Format,
+        samples: i::NumSamples,
+        usage: i::Usage,
+        aspects: f::Aspects,
+    ) -> Attachment<B> {
+        let mut image = unsafe {
+            device.create_image(
+                i::Kind::D2(dims.width, dims.height, 1, samples),
+                1,
+                format,
+                i::Tiling::Optimal,
+                usage,
+                i::ViewCapabilities::empty(),
+            )
+        }
+        .expect("Can't create image");
+        let requirements = unsafe { device.get_image_requirements(&image) };
+        let memory_type =
+            memory::memory_type(memory_types, &requirements, m::Properties::DEVICE_LOCAL);
+        let memory = unsafe {
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Can't allocate image memory");
+            device
+                .bind_image_memory(&memory, 0, &mut image)
+                .expect("Can't bind image memory");
+            memory
+        };
+        let view = unsafe {
+            device.create_image_view(
+                &image,
+                i::ViewKind::D2,
+                format,
+                f::Swizzle::NO,
+                i::SubresourceRange {
+                    aspects,
+                    levels: 0..1,
+                    layers: 0..1,
+                },
+            )
+        }
+        .expect("Can't create image view");
+
+        Attachment {
+            image,
+            memory,
+            view,
+        }
+    }
+}
+
+impl<'a, B: Backend> Drop for RenderTarget<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            let color = ManuallyDrop::into_inner(ptr::read(&self.color));
+            self.device.destroy_image_view(color.view);
+            self.device.destroy_image(color.image);
+            self.device.free_memory(color.memory);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use gfx_hal::pso;
+use std::borrow::Cow;
+use std::env;
+use std::fs::read;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use super::reflect::{ShaderError, ShaderInfo};
+
+/// Environment variable naming a directory whose `<name>.spv` files take
+/// precedence over the shaders embedded at build time.
+pub const SHADER_DIR_ENV: &str = "GFX_SHADER_DIR";
+
+macro_rules! embed_spirv {
+    ($name:expr) => {
+        Shader {
+            name: $name,
+            spirv: include_bytes!(concat!(env!("OUT_DIR"), "/", $name, ".spv")),
+        }
+    };
+}
+
+pub const TRIANGLE_VERT: Shader = embed_spirv!("triangle.vert");
+pub const TRIANGLE_FRAG: Shader = embed_spirv!("triangle.frag");
+pub const TEXTURED_VERT: Shader = embed_spirv!("textured.vert");
+pub const TEXTURED_FRAG: Shader = embed_spirv!("textured.frag");
+pub const MESH_VERT: Shader = embed_spirv!("mesh.vert");
+pub const MESH_FRAG: Shader = embed_spirv!("mesh.frag");
+pub const FULLSCREEN_VERT: Shader = embed_spirv!("post/fullscreen.vert");
+pub const BLIT_FRAG: Shader = embed_spirv!("post/blit.frag");
+pub const TONEMAP_FRAG: Shader = embed_spirv!("post/tonemap.frag");
+pub const GAMMA_FRAG: Shader = embed_spirv!("post/gamma.frag");
+pub const FXAA_FRAG: Shader = embed_spirv!("post/fxaa.frag");
+pub const VIGNETTE_FRAG: Shader = embed_spirv!("post/vignette.frag");
+pub const BLUR_FRAG: Shader = embed_spirv!("post/blur.frag");
+pub const PARTICLE_VERT: Shader = embed_spirv!("particles/particle.vert");
+pub const PARTICLE_FRAG: Shader = embed_spirv!("particles/particle.frag");
+pub const PARTICLE_SIMULATE_COMP: Shader = embed_spirv!("particles/simulate.comp");
+
+/// SPIR-V compiled by `build.rs` and embedded into the binary.
+#[derive(Debug, Clone, Copy)]
+pub struct Shader {
+    pub name: &'static str,
+    spirv: &'static [u8],
+}
+
+impl Shader {
+    /// Returns the SPIR-V for this shader, preferring `$GFX_SHADER_DIR/<name>.spv`
+    /// when the variable is set and the file exists.
+    pub fn load(&self) -> Cow<'static, [u8]> {
+        if let Some(path) = self.override_path() {
+            match read(&path) {
+                Ok(data) => {
+                    log::info!("loaded shader override {}", path.display());
+                    return Cow::Owned(data);
+                }
+                Err(err) => log::warn!(
+                    "can't read shader override {}: {}, using embedded shader",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        Cow::Borrowed(self.spirv)
+    }
+
+    pub fn spirv(&self) -> Result<Vec<u32>, ShaderError> {
+        let data = self.load();
+        pso::read_spirv(Cursor::new(&data[..])).map_err(|err| ShaderError {
+            shader: self.name.to_owned(),
+            messages: vec![err.to_string()],
+        })
+    }
+
+    pub fn reflect(&self) -> Result<ShaderInfo, ShaderError> {
+        ShaderInfo::reflect(self.name, &self.spirv()?)
+    }
+
+    fn override_path(&self) -> Option<PathBuf> {
+        let dir = env::var_os(SHADER_DIR_ENV)?;
+        let path = PathBuf::from(dir).join(self.name.to_owned() + ".spv");
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use gfx_hal::{adapter::Adapter, format as f, prelude::*, pso, window, Backend};
 
+use crate::config::PresentMode;
+
 pub struct Swapchain<'a, B: Backend> {
     device: &'a B::Device,
     adapter: &'a Adapter<B>,
@@ -7,6 +9,13 @@ pub struct Swapchain<'a, B: Backend> {
     pub dims: window::Extent2D,
     pub surface: &'a mut B::Surface,
     pub format: f::Format,
+    /// The mode asked for, which `recreate` falls back from if it isn't supported.
+    pub present_mode: PresentMode,
+    /// The number of images asked for, `None` for the surface's default. `recreate`
+    /// clamps it to what the surface supports.
+    pub image_count: Option<window::SwapImageIndex>,
+    /// The mode and image count the swapchain was last created with.
+    current: Option<(PresentMode, window::SwapImageIndex)>,
 }
 
 impl<'a, B: Backend> Swapchain<'a, B> {
@@ -15,6 +24,8 @@ impl<'a, B: Backend> Swapchain<'a, B> {
         surface: &'a mut B::Surface,
         adapter: &'a Adapter<B>,
         dims: window::Extent2D,
+        present_mode: PresentMode,
+        image_count: Option<window::SwapImageIndex>,
     ) -> Self {
         let caps = surface.capabilities(&adapter.physical_device);
         let formats = surface.supported_formats(&adapter.physical_device);
@@ -25,9 +36,9 @@ impl<'a, B: Backend> Swapchain<'a, B> {
                 .copied()
                 .unwrap_or(formats[0])
         });
-        let mut swap_config = window::SwapchainConfig::from_caps(&caps, format, dims);
-        swap_config.present_mode = window::PresentMode::FIFO;
-        let extent = swap_config.extent.to_extent();
+        let extent = window::SwapchainConfig::from_caps(&caps, format, dims)
+            .extent
+            .to_extent();
 
         let viewport = pso::Viewport {
             rect: pso::Rect {
@@ -46,23 +57,58 @@ impl<'a, B: Backend> Swapchain<'a, B> {
             viewport,
             format,
             dims,
+            present_mode,
+            image_count,
+            current: None,
         };
 
         swapchain.recreate();
         swapchain
     }
 
+    /// Recreates the swapchain at `dims` with `present_mode` and `image_count`, or the
+    /// closest the surface supports.
     pub fn recreate(&mut self) {
         let caps = self.surface.capabilities(&self.adapter.physical_device);
 
-        let swap_config = window::SwapchainConfig::from_caps(&caps, self.format, self.dims);
+        let present_mode = self
+            .present_mode
+            .fallbacks()
+            .iter()
+            .copied()
+            .find(|mode| caps.present_modes.contains(mode.to_gfx()))
+            .unwrap_or(PresentMode::Fifo);
+        let mut swap_config = window::SwapchainConfig::from_caps(&caps, self.format, self.dims)
+            .with_present_mode(present_mode.to_gfx());
+        if let Some(image_count) = self.image_count {
+            swap_config.image_count =
+                image_count.clamp(*caps.image_count.start(), *caps.image_count.end());
+        }
+
+        let current = Some((present_mode, swap_config.image_count));
+        if current != self.current {
+            if present_mode != self.present_mode {
+                log::warn!(
+                    "{:?} isn't supported by the surface, using {:?}",
+                    self.present_mode,
+                    present_mode
+                );
+            }
+            log::info!(
+                "presenting with {:?} and {} swapchain images",
+                present_mode,
+                swap_config.image_count
+            );
+            self.current = current;
+        }
         let extent = swap_config.extent;
         unsafe {
             self.surface
-                .configure_swapchain(&self.device, swap_config)
+                .configure_swapchain(self.device, swap_config)
                 .expect("Can't create swapchain");
         }
 
+        self.dims = extent;
         self.viewport.rect.w = extent.width as _;
         self.viewport.rect.h = extent.height as _;
     }
@@ -70,6 +116,6 @@ impl<'a, B: Backend> Swapchain<'a, B> {
 
 impl<'a, B: Backend> Drop for Swapchain<'a, B> {
     fn drop(&mut self) {
-        unsafe { self.surface.unconfigure_swapchain(&self.device) }
+        unsafe { self.surface.unconfigure_swapchain(self.device) }
     }
 }
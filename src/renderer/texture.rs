@@ -0,0 +1,556 @@
+use gfx_hal::{
+    adapter::Adapter, buffer as b, command, format as f, image as i, memory as m, prelude::*, pso,
+    Backend, Features,
+};
+use image::{imageops, FilterType};
+use std::iter;
+use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::path::Path;
+use std::ptr;
+
+use super::buffer::Buffer;
+use super::memory::{self, Memory};
+
+mod compressed;
+
+use compressed::Container;
+pub use compressed::TextureError;
+
+/// Sampling and mip chain settings of a `Texture`.
+#[derive(Debug, Clone)]
+pub struct TextureOptions {
+    /// Generate a full mip chain when uploading.
+    pub mipmaps: bool,
+    pub filter: i::Filter,
+    pub mip_filter: i::Filter,
+    pub wrap: i::WrapMode,
+    pub lod_bias: f32,
+    pub lod_range: Range<f32>,
+    /// Maximum anisotropy, ignored if the adapter doesn't support anisotropic filtering.
+    pub anisotropy: Option<u8>,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions {
+            mipmaps: true,
+            filter: i::Filter::Linear,
+            mip_filter: i::Filter::Linear,
+            wrap: i::WrapMode::Tile,
+            lod_bias: 0.0,
+            lod_range: 0.0..1000.0,
+            anisotropy: None,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub(super) fn sampler_desc<B: Backend>(&self, adapter: &Adapter<B>) -> i::SamplerDesc {
+        let mut desc = i::SamplerDesc::new(self.filter, self.wrap);
+        desc.mip_filter = self.mip_filter;
+        desc.lod_bias = i::Lod(self.lod_bias);
+        desc.lod_range = i::Lod(self.lod_range.start)..i::Lod(self.lod_range.end);
+        desc.anisotropy_clamp = self.anisotropy.and_then(|anisotropy| {
+            let physical_device = &adapter.physical_device;
+            if physical_device
+                .features()
+                .contains(Features::SAMPLER_ANISOTROPY)
+            {
+                let max = physical_device.limits().max_sampler_anisotropy as u8;
+                Some(anisotropy.min(max.max(1)))
+            } else {
+                log::warn!("anisotropic filtering is not supported, ignoring");
+                None
+            }
+        });
+        desc
+    }
+}
+
+/// A sampled 2D image living in device-local memory.
+pub struct Texture<'a, B: Backend> {
+    device: &'a B::Device,
+    pub image: ManuallyDrop<B::Image>,
+    memory: ManuallyDrop<B::Memory>,
+    pub view: ManuallyDrop<B::ImageView>,
+    pub sampler: ManuallyDrop<B::Sampler>,
+    pub dims: i::Extent,
+    pub format: f::Format,
+    pub mip_levels: i::Level,
+}
+
+impl<'a, B: Backend> Texture<'a, B> {
+    /// Reads a KTX2 or DDS file, see `from_compressed`, or decodes a PNG or JPEG file,
+    /// and uploads it.
+    pub fn load<P: AsRef<Path>>(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        path: P,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let data = std::fs::read(path)?;
+        if Container::is_container(&data) {
+            Self::from_compressed(device, adapter, command_pool, queue, &data, options)
+        } else {
+            Ok(Self::from_memory(
+                device,
+                adapter,
+                command_pool,
+                queue,
+                &data,
+                options,
+            )?)
+        }
+    }
+
+    pub fn from_memory(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        data: &[u8],
+        options: TextureOptions,
+    ) -> image::ImageResult<Self> {
+        let image = image::load_from_memory(data)?.to_rgba();
+        Ok(Self::from_rgba(
+            device,
+            adapter,
+            command_pool,
+            queue,
+            &image,
+            options,
+        ))
+    }
+
+    /// Uploads the contents of a KTX2 or DDS container. Block-compressed (BC, ETC2, ASTC)
+    /// mips are uploaded as they are when the adapter can sample the format, and decoded
+    /// to RGBA8 on the CPU otherwise.
+    pub fn from_compressed(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        data: &[u8],
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let container = Container::parse(data)?;
+        let sampled = adapter
+            .physical_device
+            .format_properties(Some(container.format))
+            .optimal_tiling
+            .contains(f::ImageFeature::SAMPLED);
+        let (format, levels) = if sampled {
+            (container.format, container.levels)
+        } else {
+            log::info!(
+                "{:?} isn't supported by the adapter, decoding on the CPU",
+                container.format
+            );
+            container.decode()?
+        };
+
+        let mip_levels = if options.mipmaps {
+            mip_level_count(container.width, container.height).max(levels.len() as i::Level)
+        } else {
+            levels.len() as i::Level
+        };
+        Ok(Self::from_levels(
+            device,
+            adapter,
+            command_pool,
+            queue,
+            format,
+            (container.width, container.height),
+            mip_levels,
+            &levels,
+            options,
+        ))
+    }
+
+    /// Uploads `rgba`. The mip chain, if requested, is blitted on the GPU when the format
+    /// supports linear filtering and resized on the CPU otherwise.
+    pub fn from_rgba(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        rgba: &image::RgbaImage,
+        options: TextureOptions,
+    ) -> Self {
+        let format = f::Format::Rgba8Srgb;
+        let (width, height) = rgba.dimensions();
+        let mip_levels = if options.mipmaps {
+            mip_level_count(width, height)
+        } else {
+            1
+        };
+
+        let levels = if mip_levels > 1 && !can_blit(adapter, format) {
+            log::info!(
+                "{:?} can't be blitted, generating mipmaps on the CPU",
+                format
+            );
+            resize_levels(rgba, mip_levels)
+        } else {
+            vec![rgba.clone().into_raw()]
+        };
+
+        Self::from_levels(
+            device,
+            adapter,
+            command_pool,
+            queue,
+            format,
+            (width, height),
+            mip_levels,
+            &levels,
+            options,
+        )
+    }
+
+    /// Uploads tightly packed mip `levels` of `format`, starting at level 0. Levels beyond
+    /// those given, up to `mip_levels`, are generated by GPU blits.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_levels(
+        device: &'a B::Device,
+        adapter: &Adapter<B>,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        format: f::Format,
+        (width, height): (u32, u32),
+        mut mip_levels: i::Level,
+        levels: &[Vec<u8>],
+        options: TextureOptions,
+    ) -> Self {
+        assert!(!levels.is_empty() && levels.len() <= mip_levels as usize);
+        if levels.len() < mip_levels as usize && !can_blit(adapter, format) {
+            log::warn!("{:?} can't be blitted, skipping mipmap generation", format);
+            mip_levels = levels.len() as i::Level;
+        }
+        let dims = i::Extent {
+            width,
+            height,
+            depth: 1,
+        };
+        let memory_types = adapter.physical_device.memory_properties().memory_types;
+        let limits = adapter.physical_device.limits();
+
+        let (staging, regions) =
+            Self::create_staging(device, format, dims, levels, &limits, &memory_types);
+
+        let mut image = unsafe {
+            device.create_image(
+                i::Kind::D2(width, height, 1, 1),
+                mip_levels,
+                format,
+                i::Tiling::Optimal,
+                i::Usage::TRANSFER_SRC | i::Usage::TRANSFER_DST | i::Usage::SAMPLED,
+                i::ViewCapabilities::empty(),
+            )
+        }
+        .expect("Can't create image");
+        let requirements = unsafe { device.get_image_requirements(&image) };
+        let memory_type =
+            memory::memory_type(&memory_types, &requirements, m::Properties::DEVICE_LOCAL);
+        let image_memory = unsafe {
+            let memory = device
+                .allocate_memory(memory_type, requirements.size)
+                .expect("Can't allocate image memory");
+            device
+                .bind_image_memory(&memory, 0, &mut image)
+                .expect("Can't bind image memory");
+            memory
+        };
+
+        let uploaded = levels.len() as i::Level;
+        submit_once::<B, _>(device, command_pool, queue, |cmd_buffer| unsafe {
+            cmd_buffer.pipeline_barrier(
+                pso::PipelineStage::TOP_OF_PIPE..pso::PipelineStage::TRANSFER,
+                m::Dependencies::empty(),
+                iter::once(m::Barrier::Image {
+                    states: (i::Access::empty(), i::Layout::Undefined)
+                        ..(i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
+                    target: &image,
+                    families: None,
+                    range: color_range(0..mip_levels),
+                }),
+            );
+            cmd_buffer.copy_buffer_to_image(
+                &staging.buffer.buf,
+                &image,
+                i::Layout::TransferDstOptimal,
+                &regions,
+            );
+            if uploaded < mip_levels {
+                Self::record_blits(cmd_buffer, &image, dims, uploaded - 1, mip_levels);
+            }
+            cmd_buffer.pipeline_barrier(
+                pso::PipelineStage::TRANSFER..pso::PipelineStage::FRAGMENT_SHADER,
+                m::Dependencies::empty(),
+                iter::once(m::Barrier::Image {
+                    states: (i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal)
+                        ..(i::Access::SHADER_READ, i::Layout::ShaderReadOnlyOptimal),
+                    target: &image,
+                    families: None,
+                    range: color_range(0..mip_levels),
+                }),
+            );
+        });
+        drop(staging);
+
+        let view = unsafe {
+            device.create_image_view(
+                &image,
+                i::ViewKind::D2,
+                format,
+                f::Swizzle::NO,
+                color_range(0..mip_levels),
+            )
+        }
+        .expect("Can't create image view");
+        let sampler = unsafe { device.create_sampler(&options.sampler_desc(adapter)) }
+            .expect("Can't create sampler");
+
+        Texture {
+            device,
+            image: ManuallyDrop::new(image),
+            memory: ManuallyDrop::new(image_memory),
+            view: ManuallyDrop::new(view),
+            sampler: ManuallyDrop::new(sampler),
+            dims,
+            format,
+            mip_levels,
+        }
+    }
+
+    /// Fills levels `base + 1..mip_levels` by successively halving level `base`, leaving
+    /// every level in `TransferDstOptimal`.
+    unsafe fn record_blits(
+        cmd_buffer: &mut B::CommandBuffer,
+        image: &B::Image,
+        dims: i::Extent,
+        base: i::Level,
+        mip_levels: i::Level,
+    ) {
+        for level in base + 1..mip_levels {
+            let src = level - 1;
+            cmd_buffer.pipeline_barrier(
+                pso::PipelineStage::TRANSFER..pso::PipelineStage::TRANSFER,
+                m::Dependencies::empty(),
+                iter::once(m::Barrier::Image {
+                    states: (i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal)
+                        ..(i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal),
+                    target: image,
+                    families: None,
+                    range: color_range(src..level),
+                }),
+            );
+            let (src_w, src_h) = mip_extent(dims.width, dims.height, src);
+            let (dst_w, dst_h) = mip_extent(dims.width, dims.height, level);
+            cmd_buffer.blit_image(
+                image,
+                i::Layout::TransferSrcOptimal,
+                image,
+                i::Layout::TransferDstOptimal,
+                i::Filter::Linear,
+                iter::once(command::ImageBlit {
+                    src_subresource: color_layers(src),
+                    src_bounds: i::Offset::ZERO..i::Offset {
+                        x: src_w as i32,
+                        y: src_h as i32,
+                        z: 1,
+                    },
+                    dst_subresource: color_layers(level),
+                    dst_bounds: i::Offset::ZERO..i::Offset {
+                        x: dst_w as i32,
+                        y: dst_h as i32,
+                        z: 1,
+                    },
+                }),
+            );
+            cmd_buffer.pipeline_barrier(
+                pso::PipelineStage::TRANSFER..pso::PipelineStage::TRANSFER,
+                m::Dependencies::empty(),
+                iter::once(m::Barrier::Image {
+                    states: (i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal)
+                        ..(i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
+                    target: image,
+                    families: None,
+                    range: color_range(src..level),
+                }),
+            );
+        }
+    }
+
+    /// Packs every level into one transfer buffer, returning the copy region of each.
+    fn create_staging(
+        device: &'a B::Device,
+        format: f::Format,
+        dims: i::Extent,
+        levels: &[Vec<u8>],
+        limits: &gfx_hal::Limits,
+        memory_types: &[gfx_hal::adapter::MemoryType],
+    ) -> (Memory<'a, B, u8>, Vec<command::BufferImageCopy>) {
+        let desc = format.surface_desc();
+        let (block_w, block_h) = (desc.dim.0 as u32, desc.dim.1 as u32);
+        let alignment = (limits.optimal_buffer_copy_offset_alignment as usize)
+            .max(desc.bits as usize / 8)
+            .max(4);
+
+        let mut content = vec![];
+        let mut regions = vec![];
+        for (level, data) in levels.iter().enumerate() {
+            let offset = content.len().div_ceil(alignment) * alignment;
+            content.resize(offset, 0);
+            content.extend_from_slice(data);
+
+            let (width, height) = mip_extent(dims.width, dims.height, level as i::Level);
+            regions.push(command::BufferImageCopy {
+                buffer_offset: offset as u64,
+                buffer_width: width.div_ceil(block_w) * block_w,
+                buffer_height: height.div_ceil(block_h) * block_h,
+                image_layers: color_layers(level as i::Level),
+                image_offset: i::Offset::ZERO,
+                image_extent: i::Extent {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            });
+        }
+
+        let buffer = Buffer::new(device, content, b::Usage::TRANSFER_SRC, limits);
+        (Memory::new(buffer, memory_types), regions)
+    }
+}
+
+/// Number of levels in a full mip chain for a `width` x `height` image.
+pub fn mip_level_count(width: u32, height: u32) -> i::Level {
+    (32 - width.max(height).max(1).leading_zeros()) as i::Level
+}
+
+/// Size of mip `level` of a `width` x `height` image.
+pub fn mip_extent(width: u32, height: u32, level: i::Level) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// `rgba` followed by `mip_levels - 1` successively halved copies, as raw RGBA8 data.
+fn resize_levels(rgba: &image::RgbaImage, mip_levels: i::Level) -> Vec<Vec<u8>> {
+    let (width, height) = rgba.dimensions();
+    let mut levels = vec![rgba.clone()];
+    for level in 1..mip_levels {
+        let (w, h) = mip_extent(width, height, level);
+        let previous = levels.last().unwrap();
+        levels.push(imageops::resize(previous, w, h, FilterType::Triangle));
+    }
+    levels.into_iter().map(|level| level.into_raw()).collect()
+}
+
+fn can_blit<B: Backend>(adapter: &Adapter<B>, format: f::Format) -> bool {
+    adapter
+        .physical_device
+        .format_properties(Some(format))
+        .optimal_tiling
+        .contains(
+            f::ImageFeature::SAMPLED_LINEAR | f::ImageFeature::BLIT_SRC | f::ImageFeature::BLIT_DST,
+        )
+}
+
+fn color_range(levels: Range<i::Level>) -> i::SubresourceRange {
+    i::SubresourceRange {
+        aspects: f::Aspects::COLOR,
+        levels,
+        layers: 0..1,
+    }
+}
+
+fn color_layers(level: i::Level) -> i::SubresourceLayers {
+    i::SubresourceLayers {
+        aspects: f::Aspects::COLOR,
+        level,
+        layers: 0..1,
+    }
+}
+
+impl<'a, B: Backend> Drop for Texture<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_sampler(ManuallyDrop::into_inner(ptr::read(&self.sampler)));
+            self.device
+                .destroy_image_view(ManuallyDrop::into_inner(ptr::read(&self.view)));
+            self.device
+                .destroy_image(ManuallyDrop::into_inner(ptr::read(&self.image)));
+            self.device
+                .free_memory(ManuallyDrop::into_inner(ptr::read(&self.memory)));
+        }
+    }
+}
+
+/// Records commands into a one-time command buffer, submits it and waits for completion.
+pub fn submit_once<B: Backend, F: FnOnce(&mut B::CommandBuffer)>(
+    device: &B::Device,
+    command_pool: &mut B::CommandPool,
+    queue: &mut B::CommandQueue,
+    record: F,
+) {
+    unsafe {
+        let mut cmd_buffer = command_pool.allocate_one(command::Level::Primary);
+        cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+        record(&mut cmd_buffer);
+        cmd_buffer.finish();
+
+        let fence = device.create_fence(false).expect("Could not create fence");
+        queue.submit_without_semaphores(iter::once(&cmd_buffer), Some(&fence));
+        device
+            .wait_for_fence(&fence, !0)
+            .expect("Can't wait for fence");
+        device.destroy_fence(fence);
+        command_pool.free(iter::once(cmd_buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_level_count_halves_the_larger_side_down_to_one() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(0, 0), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(256, 1), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_level_count(512, 513), 10);
+    }
+
+    #[test]
+    fn mip_extent_clamps_each_side_to_one() {
+        assert_eq!(mip_extent(256, 64, 0), (256, 64));
+        assert_eq!(mip_extent(256, 64, 3), (32, 8));
+        assert_eq!(mip_extent(256, 64, 7), (2, 1));
+        assert_eq!(mip_extent(256, 64, 8), (1, 1));
+        assert_eq!(mip_extent(5, 3, 1), (2, 1));
+    }
+
+    #[test]
+    fn resize_levels_builds_the_whole_chain() {
+        let rgba = image::RgbaImage::from_fn(8, 4, |x, _| {
+            if x < 4 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 255])
+            }
+        });
+        let levels = resize_levels(&rgba, mip_level_count(8, 4));
+        let sizes = levels.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes, [8 * 4 * 4, 4 * 2 * 4, 2 * 4, 4]);
+        assert_eq!(levels[0], rgba.into_raw());
+        // The left half stays red after halving.
+        assert_eq!(&levels[1][..4], &[255, 0, 0, 255]);
+        assert_eq!(&levels[1][12..16], &[0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,442 @@
+use ddsfile::{D3DFormat, Dds, DxgiFormat};
+use gfx_hal::format::Format;
+use std::fmt;
+use std::io;
+
+const KTX2_MAGIC: &[u8] = b"\xABKTX 20\xBB\r\n\x1A\n";
+const DDS_MAGIC: &[u8] = b"DDS ";
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    Image(image::ImageError),
+    Ktx2(ktx2::ParseError),
+    Dds(ddsfile::Error),
+    Unsupported(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Io(err) => write!(fmt, "{}", err),
+            TextureError::Image(err) => write!(fmt, "invalid image: {}", err),
+            TextureError::Ktx2(err) => write!(fmt, "invalid KTX2 file: {}", err),
+            TextureError::Dds(err) => write!(fmt, "invalid DDS file: {}", err),
+            TextureError::Unsupported(what) => write!(fmt, "unsupported texture: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<io::Error> for TextureError {
+    fn from(err: io::Error) -> Self {
+        TextureError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(err: image::ImageError) -> Self {
+        TextureError::Image(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Rgba8,
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6h { signed: bool },
+    Bc7,
+    Etc2Rgb,
+    Etc2Rgba1,
+    Etc2Rgba8,
+    Astc,
+}
+
+const FORMATS: &[(Format, Codec)] = &[
+    (Format::Rgba8Unorm, Codec::Rgba8),
+    (Format::Rgba8Srgb, Codec::Rgba8),
+    (Format::Bc1RgbUnorm, Codec::Bc1),
+    (Format::Bc1RgbSrgb, Codec::Bc1),
+    (Format::Bc1RgbaUnorm, Codec::Bc1),
+    (Format::Bc1RgbaSrgb, Codec::Bc1),
+    (Format::Bc2Unorm, Codec::Bc2),
+    (Format::Bc2Srgb, Codec::Bc2),
+    (Format::Bc3Unorm, Codec::Bc3),
+    (Format::Bc3Srgb, Codec::Bc3),
+    (Format::Bc4Unorm, Codec::Bc4),
+    (Format::Bc4Snorm, Codec::Bc4),
+    (Format::Bc5Unorm, Codec::Bc5),
+    (Format::Bc5Snorm, Codec::Bc5),
+    (Format::Bc6hUfloat, Codec::Bc6h { signed: false }),
+    (Format::Bc6hSfloat, Codec::Bc6h { signed: true }),
+    (Format::Bc7Unorm, Codec::Bc7),
+    (Format::Bc7Srgb, Codec::Bc7),
+    (Format::Etc2R8g8b8Unorm, Codec::Etc2Rgb),
+    (Format::Etc2R8g8b8Srgb, Codec::Etc2Rgb),
+    (Format::Etc2R8g8b8a1Unorm, Codec::Etc2Rgba1),
+    (Format::Etc2R8g8b8a1Srgb, Codec::Etc2Rgba1),
+    (Format::Etc2R8g8b8a8Unorm, Codec::Etc2Rgba8),
+    (Format::Etc2R8g8b8a8Srgb, Codec::Etc2Rgba8),
+    (Format::Astc4x4Unorm, Codec::Astc),
+    (Format::Astc4x4Srgb, Codec::Astc),
+    (Format::Astc5x4Unorm, Codec::Astc),
+    (Format::Astc5x4Srgb, Codec::Astc),
+    (Format::Astc5x5Unorm, Codec::Astc),
+    (Format::Astc5x5Srgb, Codec::Astc),
+    (Format::Astc6x5Unorm, Codec::Astc),
+    (Format::Astc6x5Srgb, Codec::Astc),
+    (Format::Astc6x6Unorm, Codec::Astc),
+    (Format::Astc6x6Srgb, Codec::Astc),
+    (Format::Astc8x5Unorm, Codec::Astc),
+    (Format::Astc8x5Srgb, Codec::Astc),
+    (Format::Astc8x6Unorm, Codec::Astc),
+    (Format::Astc8x6Srgb, Codec::Astc),
+    (Format::Astc8x8Unorm, Codec::Astc),
+    (Format::Astc8x8Srgb, Codec::Astc),
+    (Format::Astc10x5Unorm, Codec::Astc),
+    (Format::Astc10x5Srgb, Codec::Astc),
+    (Format::Astc10x6Unorm, Codec::Astc),
+    (Format::Astc10x6Srgb, Codec::Astc),
+    (Format::Astc10x8Unorm, Codec::Astc),
+    (Format::Astc10x8Srgb, Codec::Astc),
+    (Format::Astc10x10Unorm, Codec::Astc),
+    (Format::Astc10x10Srgb, Codec::Astc),
+    (Format::Astc12x10Unorm, Codec::Astc),
+    (Format::Astc12x10Srgb, Codec::Astc),
+    (Format::Astc12x12Unorm, Codec::Astc),
+    (Format::Astc12x12Srgb, Codec::Astc),
+];
+
+fn codec(format: Format) -> Option<Codec> {
+    FORMATS
+        .iter()
+        .find(|(f, _)| *f == format)
+        .map(|&(_, codec)| codec)
+}
+
+/// Texture data read from a KTX2 or DDS container, still in its stored format.
+pub struct Container {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Container {
+    /// Whether `data` starts like a KTX2 or DDS file.
+    pub fn is_container(data: &[u8]) -> bool {
+        data.starts_with(KTX2_MAGIC) || data.starts_with(DDS_MAGIC)
+    }
+
+    /// Parses a KTX2 or DDS file, telling them apart by their magic number.
+    pub fn parse(data: &[u8]) -> Result<Self, TextureError> {
+        if data.starts_with(KTX2_MAGIC) {
+            Self::parse_ktx2(data)
+        } else if data.starts_with(DDS_MAGIC) {
+            Self::parse_dds(data)
+        } else {
+            Err(TextureError::Unsupported(
+                "not a KTX2 or DDS file".to_owned(),
+            ))
+        }
+    }
+
+    fn parse_ktx2(data: &[u8]) -> Result<Self, TextureError> {
+        let reader = ktx2::Reader::new(data).map_err(TextureError::Ktx2)?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(TextureError::Unsupported(format!(
+                "KTX2 supercompression {:?}",
+                scheme
+            )));
+        }
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(TextureError::Unsupported(
+                "KTX2 arrays, cube maps and 3D textures".to_owned(),
+            ));
+        }
+        let vk_format = header
+            .format
+            .ok_or_else(|| TextureError::Unsupported("KTX2 without a VkFormat".to_owned()))?
+            .0
+            .get();
+        // gfx-hal's formats are numbered like `VkFormat`.
+        let format = FORMATS
+            .iter()
+            .map(|&(format, _)| format)
+            .find(|&format| format as u32 == vk_format)
+            .ok_or_else(|| TextureError::Unsupported(format!("VkFormat {}", vk_format)))?;
+
+        let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+        let mut levels = vec![];
+        for (level, data) in reader.levels().enumerate() {
+            let size = level_size(format, (width >> level).max(1), (height >> level).max(1));
+            if data.len() != size {
+                return Err(TextureError::Unsupported(format!(
+                    "KTX2 level {} has {} bytes instead of {}",
+                    level,
+                    data.len(),
+                    size
+                )));
+            }
+            levels.push(data.to_vec());
+        }
+
+        Ok(Container {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn parse_dds(data: &[u8]) -> Result<Self, TextureError> {
+        let dds = Dds::read(data).map_err(TextureError::Dds)?;
+        let format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+            (Some(dxgi), _) => dxgi_format(dxgi),
+            (None, Some(d3d)) => d3d_format(d3d),
+            (None, None) => None,
+        }
+        .ok_or_else(|| TextureError::Unsupported("DDS pixel format".to_owned()))?;
+        if dds.get_depth() > 1 {
+            return Err(TextureError::Unsupported("DDS volume textures".to_owned()));
+        }
+
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let mut data = dds.get_data(0).map_err(TextureError::Dds)?;
+        let mut levels = vec![];
+        for level in 0..dds.get_num_mipmap_levels().max(1) {
+            let size = level_size(format, (width >> level).max(1), (height >> level).max(1));
+            if data.len() < size {
+                break;
+            }
+            let (head, tail) = data.split_at(size);
+            levels.push(head.to_vec());
+            data = tail;
+        }
+        if levels.is_empty() {
+            return Err(TextureError::Unsupported("truncated DDS data".to_owned()));
+        }
+
+        Ok(Container {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Decodes every level to RGBA8, returning the format the result should be uploaded as.
+    pub fn decode(&self) -> Result<(Format, Vec<Vec<u8>>), TextureError> {
+        let srgb = self.format.base_format().1 == gfx_hal::format::ChannelType::Srgb;
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let width = (self.width >> level).max(1);
+                let height = (self.height >> level).max(1);
+                decode(self.format, data, width, height)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let format = if srgb {
+            Format::Rgba8Srgb
+        } else {
+            Format::Rgba8Unorm
+        };
+        Ok((format, levels))
+    }
+}
+
+/// Size in bytes of a tightly packed `width` x `height` image of `format`.
+fn level_size(format: Format, width: u32, height: u32) -> usize {
+    let desc = format.surface_desc();
+    let (block_w, block_h) = (desc.dim.0 as u32, desc.dim.1 as u32);
+    let blocks = width.div_ceil(block_w) * height.div_ceil(block_h);
+    blocks as usize * desc.bits as usize / 8
+}
+
+fn decode(format: Format, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>, TextureError> {
+    use texture2ddecoder as t;
+
+    let (w, h) = (width as usize, height as usize);
+    let codec = codec(format).ok_or_else(|| TextureError::Unsupported(format!("{:?}", format)))?;
+    if codec == Codec::Rgba8 {
+        return match data.get(..w * h * 4) {
+            Some(pixels) => Ok(pixels.to_vec()),
+            None => Err(TextureError::Unsupported(format!(
+                "truncated {:?} data",
+                format
+            ))),
+        };
+    }
+
+    let mut pixels = vec![0u32; w * h];
+    let result = match codec {
+        Codec::Bc1 => t::decode_bc1(data, w, h, &mut pixels),
+        Codec::Bc2 => t::decode_bc2(data, w, h, &mut pixels),
+        Codec::Bc3 => t::decode_bc3(data, w, h, &mut pixels),
+        Codec::Bc4 => t::decode_bc4(data, w, h, &mut pixels),
+        Codec::Bc5 => t::decode_bc5(data, w, h, &mut pixels),
+        Codec::Bc6h { signed } => t::decode_bc6(data, w, h, &mut pixels, signed),
+        Codec::Bc7 => t::decode_bc7(data, w, h, &mut pixels),
+        Codec::Etc2Rgb => t::decode_etc2_rgb(data, w, h, &mut pixels),
+        Codec::Etc2Rgba1 => t::decode_etc2_rgba1(data, w, h, &mut pixels),
+        Codec::Etc2Rgba8 => t::decode_etc2_rgba8(data, w, h, &mut pixels),
+        Codec::Astc => {
+            let (block_w, block_h) = format.surface_desc().dim;
+            t::decode_astc(data, w, h, block_w as usize, block_h as usize, &mut pixels)
+        }
+        Codec::Rgba8 => unreachable!(),
+    };
+    result.map_err(|err| TextureError::Unsupported(format!("decoding {:?}: {}", format, err)))?;
+
+    // The decoder produces BGRA packed into little-endian words.
+    Ok(pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            vec![r, g, b, a]
+        })
+        .collect())
+}
+
+fn dxgi_format(format: DxgiFormat) -> Option<Format> {
+    Some(match format {
+        DxgiFormat::R8G8B8A8_UNorm => Format::Rgba8Unorm,
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => Format::Rgba8Srgb,
+        DxgiFormat::BC1_UNorm => Format::Bc1RgbaUnorm,
+        DxgiFormat::BC1_UNorm_sRGB => Format::Bc1RgbaSrgb,
+        DxgiFormat::BC2_UNorm => Format::Bc2Unorm,
+        DxgiFormat::BC2_UNorm_sRGB => Format::Bc2Srgb,
+        DxgiFormat::BC3_UNorm => Format::Bc3Unorm,
+        DxgiFormat::BC3_UNorm_sRGB => Format::Bc3Srgb,
+        DxgiFormat::BC4_UNorm => Format::Bc4Unorm,
+        DxgiFormat::BC4_SNorm => Format::Bc4Snorm,
+        DxgiFormat::BC5_UNorm => Format::Bc5Unorm,
+        DxgiFormat::BC5_SNorm => Format::Bc5Snorm,
+        DxgiFormat::BC6H_UF16 => Format::Bc6hUfloat,
+        DxgiFormat::BC6H_SF16 => Format::Bc6hSfloat,
+        DxgiFormat::BC7_UNorm => Format::Bc7Unorm,
+        DxgiFormat::BC7_UNorm_sRGB => Format::Bc7Srgb,
+        _ => return None,
+    })
+}
+
+fn d3d_format(format: D3DFormat) -> Option<Format> {
+    Some(match format {
+        D3DFormat::A8B8G8R8 => Format::Rgba8Unorm,
+        D3DFormat::DXT1 => Format::Bc1RgbaUnorm,
+        D3DFormat::DXT3 => Format::Bc2Unorm,
+        D3DFormat::DXT5 => Format::Bc3Unorm,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A KTX2 file of `format` with the given levels and an empty data format descriptor.
+    fn ktx2(format: Format, width: u32, height: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let index_end = 80 + 24 * levels.len();
+        let dfd = 4u32.to_le_bytes();
+        let mut data = KTX2_MAGIC.to_vec();
+        for value in &[
+            format as u32,
+            1,
+            width,
+            height,
+            0,
+            0,
+            1,
+            levels.len() as u32,
+            0,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in &[index_end as u32, dfd.len() as u32, 0, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0; 16]);
+        let mut offset = index_end + dfd.len();
+        for level in levels {
+            for value in &[offset, level.len(), level.len()] {
+                data.extend_from_slice(&(*value as u64).to_le_bytes());
+            }
+            offset += level.len();
+        }
+        data.extend_from_slice(&dfd);
+        for level in levels {
+            data.extend_from_slice(level);
+        }
+        data
+    }
+
+    #[test]
+    fn parse_reads_ktx2_levels() {
+        let file = ktx2(Format::Rgba8Unorm, 4, 2, &[&[1; 32], &[2; 8], &[3; 4]]);
+        let container = Container::parse(&file).unwrap();
+        assert_eq!(container.format, Format::Rgba8Unorm);
+        assert_eq!((container.width, container.height), (4, 2));
+        assert_eq!(container.levels, [vec![1; 32], vec![2; 8], vec![3; 4]]);
+    }
+
+    #[test]
+    fn parse_rejects_ktx2_levels_of_the_wrong_size() {
+        let file = ktx2(Format::Rgba8Unorm, 4, 2, &[&[1; 32], &[2; 4]]);
+        assert!(matches!(
+            Container::parse(&file),
+            Err(TextureError::Unsupported(_))
+        ));
+        let file = ktx2(Format::Bc1RgbaUnorm, 8, 8, &[&[0; 16]]);
+        assert!(matches!(
+            Container::parse(&file),
+            Err(TextureError::Unsupported(_))
+        ));
+        assert!(Container::parse(&ktx2(Format::Bc1RgbaUnorm, 8, 8, &[&[0; 32]])).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_other_files() {
+        assert!(!Container::is_container(b"\x89PNG\r\n"));
+        assert!(matches!(
+            Container::parse(b"\x89PNG\r\n"),
+            Err(TextureError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn level_size_rounds_up_to_whole_blocks() {
+        assert_eq!(level_size(Format::Rgba8Unorm, 3, 5), 60);
+        assert_eq!(level_size(Format::Bc1RgbaUnorm, 1, 1), 8);
+        assert_eq!(level_size(Format::Bc2Unorm, 5, 4), 32);
+        assert_eq!(level_size(Format::Astc6x5Unorm, 12, 6), 64);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_rgba8() {
+        assert_eq!(decode(Format::Rgba8Unorm, &[7; 16], 2, 2).unwrap(), [7; 16]);
+        assert!(matches!(
+            decode(Format::Rgba8Unorm, &[7; 12], 2, 2),
+            Err(TextureError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn decode_bc2_uses_the_explicit_alpha() {
+        // Alpha alternates between 15 and 0, the color block is pure red.
+        let mut block = vec![0x0f; 8];
+        block.extend_from_slice(&0xf800u16.to_le_bytes());
+        block.extend_from_slice(&[0; 6]);
+        let pixels = decode(Format::Bc2Unorm, &block, 4, 4).unwrap();
+        assert_eq!(pixels.len(), 64);
+        assert_eq!(&pixels[..8], &[255, 0, 0, 255, 255, 0, 0, 0]);
+        assert!(decode(Format::Bc2Unorm, &block[..8], 4, 4).is_err());
+    }
+}
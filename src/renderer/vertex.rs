@@ -1,27 +1,164 @@
+use gfx_hal::{format as f, pso};
+
 pub type Coord = [f32; 2];
 
 const R: [f32; 3] = [1.0, 0.0, 0.0];
 const G: [f32; 3] = [0.0, 1.0, 0.0];
 const B: [f32; 3] = [0.0, 0.0, 1.0];
 
+/// Only read by the vertex shader.
 #[derive(Debug, Clone, Copy)]
-#[allow(non_snake_case)]
+#[allow(non_snake_case, dead_code)]
 pub struct Vertex {
     a_Pos: Coord,
     a_Color: [f32; 3],
 }
 
+/// Vertex types describe their attributes so pipelines and shader validation can use them.
+pub trait VertexLayout {
+    /// Attribute formats and offsets, in shader location order.
+    fn elements() -> Vec<pso::Element<f::Format>>;
+}
+
+/// No vertex input, for pipelines that generate their vertices in the shader.
+impl VertexLayout for () {
+    fn elements() -> Vec<pso::Element<f::Format>> {
+        vec![]
+    }
+}
+
+impl VertexLayout for Vertex {
+    fn elements() -> Vec<pso::Element<f::Format>> {
+        vec![
+            pso::Element {
+                format: f::Format::Rg32Sfloat,
+                offset: 0,
+            },
+            pso::Element {
+                format: f::Format::Rgb32Sfloat,
+                offset: 8,
+            },
+        ]
+    }
+}
+
+/// Keeps the XY position and RGB color, for flat shapes from `geometry`.
+impl From<MeshVertex> for Vertex {
+    fn from(vertex: MeshVertex) -> Self {
+        let [x, y, _] = vertex.position;
+        let [r, g, b, _] = vertex.color;
+        Vertex {
+            a_Pos: [x, y],
+            a_Color: [r, g, b],
+        }
+    }
+}
+
 pub const TRIANGLE: [Vertex; 3] = [
     Vertex {
-        a_Pos: [0.0, -0.4],
+        a_Pos: [0.0, 0.4],
         a_Color: R,
     },
     Vertex {
-        a_Pos: [-0.4, 0.3],
+        a_Pos: [-0.4, -0.3],
         a_Color: G,
     },
     Vertex {
-        a_Pos: [0.4, 0.3],
+        a_Pos: [0.4, -0.3],
         a_Color: B,
     },
 ];
+
+#[derive(Debug, Clone, Copy)]
+#[allow(non_snake_case, dead_code)]
+pub struct TexturedVertex {
+    a_Pos: Coord,
+    a_Uv: [f32; 2],
+}
+
+impl VertexLayout for TexturedVertex {
+    fn elements() -> Vec<pso::Element<f::Format>> {
+        vec![
+            pso::Element {
+                format: f::Format::Rg32Sfloat,
+                offset: 0,
+            },
+            pso::Element {
+                format: f::Format::Rg32Sfloat,
+                offset: 8,
+            },
+        ]
+    }
+}
+
+/// Two triangles covering the middle of the screen, for use with the `textured` shaders.
+pub const QUAD: [TexturedVertex; 6] = [
+    TexturedVertex {
+        a_Pos: [-0.5, -0.5],
+        a_Uv: [0.0, 0.0],
+    },
+    TexturedVertex {
+        a_Pos: [-0.5, 0.5],
+        a_Uv: [0.0, 1.0],
+    },
+    TexturedVertex {
+        a_Pos: [0.5, 0.5],
+        a_Uv: [1.0, 1.0],
+    },
+    TexturedVertex {
+        a_Pos: [-0.5, -0.5],
+        a_Uv: [0.0, 0.0],
+    },
+    TexturedVertex {
+        a_Pos: [0.5, 0.5],
+        a_Uv: [1.0, 1.0],
+    },
+    TexturedVertex {
+        a_Pos: [0.5, -0.5],
+        a_Uv: [1.0, 0.0],
+    },
+];
+
+/// A vertex of a loaded mesh. Attributes missing from the file hold the defaults
+/// described on `Attributes`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Default for MeshVertex {
+    fn default() -> Self {
+        MeshVertex {
+            position: [0.0; 3],
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl VertexLayout for MeshVertex {
+    fn elements() -> Vec<pso::Element<f::Format>> {
+        vec![
+            pso::Element {
+                format: f::Format::Rgb32Sfloat,
+                offset: 0,
+            },
+            pso::Element {
+                format: f::Format::Rgb32Sfloat,
+                offset: 12,
+            },
+            pso::Element {
+                format: f::Format::Rg32Sfloat,
+                offset: 24,
+            },
+            pso::Element {
+                format: f::Format::Rgba32Sfloat,
+                offset: 32,
+            },
+        ]
+    }
+}
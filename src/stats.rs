@@ -0,0 +1,245 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The spread of one per-frame measurement, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Distribution {
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl Distribution {
+    fn new(mut samples: Vec<f32>) -> Self {
+        if samples.is_empty() {
+            return Distribution::default();
+        }
+        samples.sort_by(|a, b| a.total_cmp(b));
+        // Nearest rank.
+        let percentile = |p: f32| {
+            let rank = (p * samples.len() as f32).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Distribution {
+            min: samples[0],
+            avg: samples.iter().sum::<f32>() / samples.len() as f32,
+            max: samples[samples.len() - 1],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} ms (min {:.2}, p50 {:.2}, p95 {:.2}, p99 {:.2}, max {:.2})",
+            self.avg, self.min, self.p50, self.p95, self.p99, self.max
+        )
+    }
+}
+
+/// Frame statistics over one `FrameStats` interval.
+///
+/// The time the GPU spent on a frame isn't among them. gfx-hal 0.5 can write timestamp
+/// queries, but exposes neither the timestamp period nor which queues have valid
+/// timestamps, so their values can't be turned into time. `blocked` is the closest
+/// measure.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    pub frames: usize,
+    pub fps: f32,
+    /// Wall time from the end of one frame to the end of the next.
+    pub frame_time: Distribution,
+    /// The part of the frame time the render thread was working.
+    pub cpu_time: Distribution,
+    /// The part of the frame time the render thread was blocked acquiring a swapchain
+    /// image, on an earlier frame's fence and presenting. Under vsync this is mostly
+    /// waiting for the display rather than for GPU work.
+    pub blocked: Distribution,
+}
+
+impl Summary {
+    /// A one-line summary for the window title, in milliseconds.
+    pub fn short(&self) -> String {
+        format!(
+            "{:.0} fps, {:.2} ms, p99 {:.2}, cpu {:.2}, blocked {:.2}",
+            self.fps, self.frame_time.avg, self.frame_time.p99, self.cpu_time.avg, self.blocked.avg
+        )
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps over {} frames, frame {}, cpu {}, blocked on acquire, fence and present \
+             {}",
+            self.fps, self.frames, self.frame_time, self.cpu_time, self.blocked
+        )
+    }
+}
+
+/// Collects frame times and summarizes them once per interval.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    interval: Duration,
+    started: Instant,
+    last: Instant,
+    frame_times: Vec<f32>,
+    cpu_times: Vec<f32>,
+    blocked_times: Vec<f32>,
+}
+
+impl Default for FrameStats {
+    /// Summarizes every second.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl FrameStats {
+    /// Starts measuring the first frame.
+    pub fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        FrameStats {
+            interval,
+            started: now,
+            last: now,
+            frame_times: vec![],
+            cpu_times: vec![],
+            blocked_times: vec![],
+        }
+    }
+
+    /// Ends a frame that spent `blocked` waiting on the GPU and the presentation engine.
+    /// Once per interval, returns a summary of the frames since the previous one.
+    pub fn end_frame(&mut self, blocked: Duration) -> Option<Summary> {
+        self.end_frame_at(Instant::now(), blocked)
+    }
+
+    fn end_frame_at(&mut self, now: Instant, blocked: Duration) -> Option<Summary> {
+        let frame_time = now.duration_since(self.last);
+        self.last = now;
+        self.frame_times.push(millis(frame_time));
+        self.cpu_times
+            .push(millis(frame_time.saturating_sub(blocked)));
+        self.blocked_times.push(millis(blocked.min(frame_time)));
+
+        if now.duration_since(self.started) < self.interval {
+            return None;
+        }
+        let summary = self.summary();
+        self.started = now;
+        self.frame_times.clear();
+        self.cpu_times.clear();
+        self.blocked_times.clear();
+        summary
+    }
+
+    /// The frames of the current interval so far, `None` before the first one ends.
+    pub fn summary(&self) -> Option<Summary> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        let total = self.frame_times.iter().sum::<f32>();
+        Some(Summary {
+            frames: self.frame_times.len(),
+            fps: self.frame_times.len() as f32 * 1000.0 / total.max(f32::EPSILON),
+            frame_time: Distribution::new(self.frame_times.clone()),
+            cpu_time: Distribution::new(self.cpu_times.clone()),
+            blocked: Distribution::new(self.blocked_times.clone()),
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_uses_nearest_rank() {
+        let samples = (1..=100).rev().map(|n| n as f32).collect();
+        let distribution = Distribution::new(samples);
+        assert_eq!(distribution.min, 1.0);
+        assert_eq!(distribution.max, 100.0);
+        assert_eq!(distribution.avg, 50.5);
+        assert_eq!(distribution.p50, 50.0);
+        assert_eq!(distribution.p95, 95.0);
+        assert_eq!(distribution.p99, 99.0);
+
+        let distribution = Distribution::new(vec![3.0, 1.0, 2.0]);
+        assert_eq!(distribution.p50, 2.0);
+        assert_eq!(distribution.p95, 3.0);
+        assert_eq!(distribution.p99, 3.0);
+
+        assert_eq!(Distribution::new(vec![]), Distribution::default());
+    }
+
+    #[test]
+    fn end_frame_summarizes_once_per_interval() {
+        let mut stats = FrameStats::new(Duration::from_millis(100));
+        let start = stats.started;
+        let at = |millis| start + Duration::from_millis(millis);
+        assert_eq!(stats.summary(), None);
+
+        assert_eq!(stats.end_frame_at(at(40), Duration::from_millis(10)), None);
+        assert_eq!(stats.end_frame_at(at(80), Duration::from_millis(30)), None);
+        assert_eq!(stats.summary().unwrap().frames, 2);
+
+        let summary = stats.end_frame_at(at(100), Duration::ZERO).unwrap();
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.frame_time.max, 40.0);
+        assert_eq!(summary.frame_time.min, 20.0);
+        assert_eq!(summary.blocked.max, 30.0);
+        assert_eq!(summary.cpu_time.p50, 20.0);
+        assert!((summary.fps - 30.0).abs() < 1e-3);
+
+        // The next interval starts with the frame that ended the previous one.
+        assert_eq!(stats.summary(), None);
+        assert_eq!(stats.end_frame_at(at(150), Duration::ZERO), None);
+        assert_eq!(
+            stats.end_frame_at(at(200), Duration::ZERO).unwrap().frames,
+            2
+        );
+    }
+
+    #[test]
+    fn short_fits_in_a_title() {
+        let ms = |avg, p99| Distribution {
+            avg,
+            p99,
+            ..Distribution::default()
+        };
+        let summary = Summary {
+            frames: 60,
+            fps: 59.9,
+            frame_time: ms(16.69, 17.5),
+            cpu_time: ms(2.1, 3.0),
+            blocked: ms(14.59, 15.0),
+        };
+        assert_eq!(
+            summary.short(),
+            "60 fps, 16.69 ms, p99 17.50, cpu 2.10, blocked 14.59"
+        );
+    }
+
+    #[test]
+    fn blocked_is_clamped_to_the_frame_time() {
+        let mut stats = FrameStats::new(Duration::from_secs(1));
+        let start = stats.started;
+        stats.end_frame_at(start + Duration::from_millis(10), Duration::from_millis(50));
+        let summary = stats.summary().unwrap();
+        assert_eq!(summary.blocked.max, 10.0);
+        assert_eq!(summary.cpu_time.max, 0.0);
+    }
+}
@@ -0,0 +1,117 @@
+use winit::dpi::PhysicalSize;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{Fullscreen, Window};
+
+use crate::config::{Config, FullscreenMode};
+
+/// Switches a window between windowed and fullscreen, remembering its windowed size.
+///
+/// The window reports its new size once it has switched, which recreates the swapchain.
+#[derive(Debug, Clone)]
+pub struct WindowMode {
+    pub mode: FullscreenMode,
+    /// An index into `Window::available_monitors`, `None` for the one the window is on.
+    pub monitor: Option<usize>,
+    /// The size to restore when leaving fullscreen.
+    windowed_size: Option<PhysicalSize<u32>>,
+}
+
+impl WindowMode {
+    /// Logs the monitors `config.monitor` can choose from.
+    pub fn new(config: &Config, window: &Window) -> Self {
+        for (index, monitor) in window.available_monitors().enumerate() {
+            log::info!(
+                "monitor {}: {} ({}x{} at {:?}, scale {})",
+                index,
+                monitor.name().unwrap_or_default(),
+                monitor.size().width,
+                monitor.size().height,
+                monitor.position(),
+                monitor.scale_factor()
+            );
+        }
+        WindowMode {
+            mode: config.fullscreen_mode,
+            monitor: config.monitor,
+            windowed_size: None,
+        }
+    }
+
+    pub fn is_fullscreen(&self, window: &Window) -> bool {
+        window.fullscreen().is_some()
+    }
+
+    pub fn toggle(&mut self, window: &Window) {
+        if self.is_fullscreen(window) {
+            self.leave(window);
+        } else {
+            self.enter(window);
+        }
+    }
+
+    /// Makes `window` cover the chosen monitor in `mode`.
+    pub fn enter(&mut self, window: &Window) {
+        if !self.is_fullscreen(window) {
+            self.windowed_size = Some(window.inner_size());
+        }
+        let monitor = self.monitor(window);
+        let fullscreen = match self.mode {
+            FullscreenMode::Borderless => Fullscreen::Borderless(monitor),
+            FullscreenMode::Exclusive => match video_mode(&monitor) {
+                Some(video_mode) => Fullscreen::Exclusive(video_mode),
+                None => {
+                    log::warn!("the monitor has no video modes, using borderless fullscreen");
+                    Fullscreen::Borderless(monitor)
+                }
+            },
+        };
+        match &fullscreen {
+            Fullscreen::Borderless(monitor) => log::info!(
+                "borderless fullscreen on {}",
+                monitor.name().unwrap_or_default()
+            ),
+            Fullscreen::Exclusive(video_mode) => log::info!(
+                "exclusive fullscreen on {} at {}x{}, {} Hz",
+                video_mode.monitor().name().unwrap_or_default(),
+                video_mode.size().width,
+                video_mode.size().height,
+                video_mode.refresh_rate()
+            ),
+        }
+        window.set_fullscreen(Some(fullscreen));
+    }
+
+    /// Returns `window` to the size it had before `enter`.
+    pub fn leave(&mut self, window: &Window) {
+        window.set_fullscreen(None);
+        if let Some(size) = self.windowed_size.take() {
+            window.set_inner_size(size);
+        }
+        log::info!("windowed");
+    }
+
+    fn monitor(&self, window: &Window) -> MonitorHandle {
+        if let Some(index) = self.monitor {
+            match window.available_monitors().nth(index) {
+                Some(monitor) => return monitor,
+                None => log::warn!("there is no monitor {}, using the current one", index),
+            }
+        }
+        window.current_monitor()
+    }
+}
+
+/// The video mode at the monitor's native size with the highest refresh rate, or the
+/// largest one if none is native.
+fn video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let size = monitor.size();
+    let rank = |mode: &VideoMode| {
+        (
+            mode.size() == size,
+            mode.size().width * mode.size().height,
+            mode.refresh_rate(),
+            mode.bit_depth(),
+        )
+    };
+    monitor.video_modes().max_by_key(rank)
+}